@@ -0,0 +1,353 @@
+use nih_plug::prelude::Enum;
+use std::fmt;
+
+use crate::analysis::Histogram;
+
+/// The largest number of samples on either side of the center sample a window can have.
+pub const MAX_RADIUS: usize = 32;
+/// The highest order used for the linear prediction. Lower orders are used when the window doesn't
+/// contain enough samples.
+const MAX_LPC_ORDER: usize = 8;
+
+/// The different ways of estimating what a sample should have been. All of them compare the
+/// center sample of a window against that estimate, relative to the range spanned by the other
+/// samples in the window, and replace it with the estimate if it strays too far.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Algorithm {
+    /// The midpoint between the lowest and highest surrounding sample. This is the original
+    /// algorithm described in the readme.
+    #[default]
+    #[id = "window-average"]
+    #[name = "Window Average"]
+    WindowAverage,
+    /// The median of the surrounding samples, which is less affected by a second outlier in the
+    /// window.
+    #[id = "median"]
+    #[name = "Median"]
+    Median,
+    /// Linear prediction from both sides of the window, which follows the waveform's curvature
+    /// much better than the other estimates. This works best with wider windows.
+    #[id = "lpc"]
+    #[name = "LPC"]
+    Lpc,
+    /// The value that leaves the window with the least energy in the upper half of its spectrum,
+    /// where a pop's energy goes but little of the music's does. This is band-limited interpolation
+    /// rather than a fit to the waveform's shape, and also works best with wider windows.
+    #[id = "spectral"]
+    #[name = "Spectral"]
+    Spectral,
+}
+
+impl Algorithm {
+    /// Estimate the value of the sample between `left` and `right`, which should have the same
+    /// length. `left` is in chronological order, so its last sample is the one closest to the
+    /// center.
+    pub fn estimate(self, left: &[f32], right: &[f32]) -> f64 {
+        match self {
+            Algorithm::WindowAverage => {
+                let (min, max) = range(left, right);
+                (max + min) / 2.0
+            }
+            Algorithm::Median => median(left, right),
+            Algorithm::Lpc => lpc_estimate(left, right),
+            Algorithm::Spectral => spectral_estimate(left, right),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::variants()[self.to_index()])
+    }
+}
+
+/// Convert a window length in microseconds to the number of samples on either side of the center
+/// sample at the given sample rate. The window always spans at least three samples.
+pub fn radius_for_duration(window_us: f32, sample_rate: f32) -> usize {
+    let window_samples = window_us * sample_rate / 1_000_000.0;
+    (((window_samples - 1.0) / 2.0).round().max(1.0) as usize).min(MAX_RADIUS)
+}
+
+/// Clean the samples in `input` using windows of `2 * radius + 1` samples. `output` receives the
+/// cleaned center samples and should thus be `2 * radius` samples shorter than `input`, where
+/// `output[i]` corresponds to `input[i + radius]`. Every sample's deviation is recorded in
+/// `histogram`, and the largest one is returned.
+pub fn clean(
+    algorithm: Algorithm,
+    input: &[f32],
+    output: &mut [f32],
+    radius: usize,
+    threshold: f32,
+    histogram: &mut Histogram,
+) -> f64 {
+    debug_assert!((1..=MAX_RADIUS).contains(&radius));
+    debug_assert_eq!(output.len() + 2 * radius, input.len());
+
+    let threshold = threshold as f64;
+    let mut peak_deviation = 0.0f64;
+    for (i, output_sample) in output.iter_mut().enumerate() {
+        let left = &input[i..i + radius];
+        let point = input[i + radius];
+        let right = &input[i + radius + 1..i + 2 * radius + 1];
+
+        let (min, max) = range(left, right);
+        let distance = (max - min).abs();
+        let estimate = algorithm.estimate(left, right);
+
+        let deviation = (point as f64 - estimate).abs() / distance;
+        histogram.record(deviation);
+        // Windows of pure silence make for a NaN here
+        if !deviation.is_nan() {
+            peak_deviation = peak_deviation.max(deviation);
+        }
+
+        *output_sample = if deviation > threshold {
+            estimate as f32
+        } else {
+            point
+        };
+    }
+
+    peak_deviation
+}
+
+fn range(left: &[f32], right: &[f32]) -> (f64, f64) {
+    left.iter()
+        .chain(right)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &sample| {
+            (min.min(sample as f64), max.max(sample as f64))
+        })
+}
+
+fn median(left: &[f32], right: &[f32]) -> f64 {
+    // This runs on the audio thread, so the samples are sorted on the stack
+    let mut samples = [0.0f64; MAX_RADIUS * 2];
+    let len = left.len() + right.len();
+    for (target, &sample) in samples.iter_mut().zip(left.iter().chain(right)) {
+        *target = sample as f64;
+    }
+
+    let samples = &mut samples[..len];
+    samples.sort_unstable_by(f64::total_cmp);
+    if len.is_multiple_of(2) {
+        (samples[len / 2 - 1] + samples[len / 2]) / 2.0
+    } else {
+        samples[len / 2]
+    }
+}
+
+/// Predict the center sample forwards from `left` and backwards from `right` using a predictor
+/// fitted to both sides, and average the two predictions. The predictor is fitted with the
+/// covariance method (least squares), since the autocorrelation method is heavily biased on
+/// segments this short.
+fn lpc_estimate(left: &[f32], right: &[f32]) -> f64 {
+    // Each side needs to provide at least as many equations as there are coefficients
+    let order = MAX_LPC_ORDER.min(left.len() / 2);
+    let nearest_average = (*left.last().unwrap() as f64 + right[0] as f64) / 2.0;
+    if order == 0 {
+        return nearest_average;
+    }
+
+    // `coefficients[k]` applies to the sample `k + 1` steps away from the predicted one. The right
+    // side is traversed backwards so both sides predict towards the center.
+    let mut matrix = [[0.0f64; MAX_LPC_ORDER]; MAX_LPC_ORDER];
+    let mut vector = [0.0f64; MAX_LPC_ORDER];
+    let mut add_equations = |sample: &dyn Fn(usize) -> f64, len: usize| {
+        for n in order..len {
+            for k in 0..order {
+                vector[k] += sample(n - 1 - k) * sample(n);
+                for (l, entry) in matrix[k].iter_mut().enumerate().take(order) {
+                    *entry += sample(n - 1 - k) * sample(n - 1 - l);
+                }
+            }
+        }
+    };
+    add_equations(&|i| left[i] as f64, left.len());
+    add_equations(&|i| right[right.len() - 1 - i] as f64, right.len());
+
+    let Some(coefficients) = solve(&mut matrix, &mut vector, order) else {
+        return nearest_average;
+    };
+
+    let forward: f64 = coefficients[..order]
+        .iter()
+        .zip(left.iter().rev())
+        .map(|(&a, &x)| a * x as f64)
+        .sum();
+    let backward: f64 = coefficients[..order]
+        .iter()
+        .zip(right)
+        .map(|(&a, &x)| a * x as f64)
+        .sum();
+
+    // Another pop inside of the window can throw off the fit completely, so the prediction is
+    // never allowed to leave the range of the surrounding samples
+    let (min, max) = range(left, right);
+    ((forward + backward) / 2.0).clamp(min, max)
+}
+
+/// Find the center sample that minimizes the energy of the window's spectrum above a quarter of
+/// the sample rate, so in the bins `f` with `|f| > n / 4` of the window's `n` point DFT.
+///
+/// Setting the derivative of that energy to zero leaves a weighted sum of the other samples, where
+/// the weights follow from the Dirichlet kernel `D(k) = sin((2m + 1)πk / n) / sin(πk / n)` of the
+/// `2m + 1` bins below the cutoff: the estimate is `Σ D(k) x[k] / (n - 2m - 1)` over all `k != 0`.
+fn spectral_estimate(left: &[f32], right: &[f32]) -> f64 {
+    let radius = left.len();
+    let len = 2 * radius + 1;
+    let low_bins = 2 * (len / 4) + 1;
+    let weight = |distance: usize| {
+        let angle = std::f64::consts::PI * distance as f64 / len as f64;
+        (low_bins as f64 * angle).sin() / angle.sin()
+    };
+
+    let sum: f64 = (1..=radius)
+        .map(|distance| {
+            weight(distance) * (left[radius - distance] as f64 + right[distance - 1] as f64)
+        })
+        .sum();
+
+    // Like with LPC, another pop inside of the window shouldn't be able to throw off the estimate
+    let (min, max) = range(left, right);
+    (sum / (len - low_bins) as f64).clamp(min, max)
+}
+
+/// Solve the `order` by `order` system in the top left of `matrix` using Gaussian elimination with
+/// partial pivoting. The diagonal is regularized slightly so that silence or pure DC doesn't
+/// produce a singular system. Returns `None` if the system is singular regardless.
+fn solve(
+    matrix: &mut [[f64; MAX_LPC_ORDER]; MAX_LPC_ORDER],
+    vector: &mut [f64; MAX_LPC_ORDER],
+    order: usize,
+) -> Option<[f64; MAX_LPC_ORDER]> {
+    let trace: f64 = (0..order).map(|i| matrix[i][i]).sum();
+    if !trace.is_finite() || trace <= f64::EPSILON {
+        return None;
+    }
+    for (i, row) in matrix.iter_mut().enumerate().take(order) {
+        row[i] += trace * 1e-9;
+    }
+
+    for column in 0..order {
+        let pivot = (column..order)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))
+            .unwrap();
+        if matrix[pivot][column].abs() <= f64::EPSILON * trace {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        vector.swap(column, pivot);
+
+        for row in column + 1..order {
+            let (pivot_rows, rows) = matrix.split_at_mut(row);
+            let (pivot_row, row_values) = (&pivot_rows[column], &mut rows[0]);
+
+            let factor = row_values[column] / pivot_row[column];
+            for (value, pivot_value) in row_values[column..order]
+                .iter_mut()
+                .zip(&pivot_row[column..order])
+            {
+                *value -= factor * pivot_value;
+            }
+            vector[row] -= factor * vector[column];
+        }
+    }
+
+    let mut solution = [0.0f64; MAX_LPC_ORDER];
+    for row in (0..order).rev() {
+        let known: f64 = (row + 1..order).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (vector[row] - known) / matrix[row][row];
+    }
+
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sine wave with a single corrupted sample in the middle.
+    fn corrupted_sine(len: usize) -> (Vec<f32>, Vec<f32>) {
+        let clean: Vec<f32> = (0..len).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        let mut corrupted = clean.clone();
+        corrupted[len / 2] = -0.9;
+
+        (clean, corrupted)
+    }
+
+    #[test]
+    fn all_algorithms_repair_a_pop() {
+        let radius = 4;
+        let (clean, corrupted) = corrupted_sine(101);
+        for algorithm in [
+            Algorithm::WindowAverage,
+            Algorithm::Median,
+            Algorithm::Lpc,
+            Algorithm::Spectral,
+        ] {
+            let mut output = vec![0.0; corrupted.len() - 2 * radius];
+            clean_into(algorithm, &corrupted, &mut output, radius);
+
+            let repaired = output[50 - radius];
+            assert!(
+                (repaired - clean[50]).abs() < 0.05,
+                "{algorithm:?} repaired the pop to {repaired}, expected {}",
+                clean[50]
+            );
+            // Nothing else should be touched
+            assert_eq!(&output[..50 - radius], &corrupted[radius..50]);
+        }
+    }
+
+    #[test]
+    fn lpc_follows_the_curve() {
+        let radius = 8;
+        let (clean, corrupted) = corrupted_sine(101);
+        let mut output = vec![0.0; corrupted.len() - 2 * radius];
+        clean_into(Algorithm::Lpc, &corrupted, &mut output, radius);
+
+        assert!((output[50 - radius] - clean[50]).abs() < 1e-3);
+    }
+
+    #[test]
+    fn spectral_interpolates_between_neighbours() {
+        // The shortest window has no room for anything but a straight line
+        assert_eq!(spectral_estimate(&[1.0], &[2.0]), 1.5);
+        // DC passes through unchanged
+        assert!((spectral_estimate(&[0.25; 8], &[0.25; 8]) - 0.25).abs() < 1e-6);
+
+        let radius = 8;
+        let (clean, corrupted) = corrupted_sine(101);
+        let mut output = vec![0.0; corrupted.len() - 2 * radius];
+        clean_into(Algorithm::Spectral, &corrupted, &mut output, radius);
+
+        assert!((output[50 - radius] - clean[50]).abs() < 1e-2);
+    }
+
+    #[test]
+    fn window_duration_to_radius() {
+        // The default window matches the original five sample window at 44.1 kHz
+        assert_eq!(radius_for_duration(113.0, 44_100.0), 2);
+        assert_eq!(radius_for_duration(113.0, 48_000.0), 2);
+        assert_eq!(radius_for_duration(113.0, 96_000.0), 5);
+        assert_eq!(radius_for_duration(0.0, 44_100.0), 1);
+        assert_eq!(radius_for_duration(1_000_000.0, 44_100.0), MAX_RADIUS);
+    }
+
+    #[test]
+    fn median_of_even_and_odd_lengths() {
+        assert_eq!(median(&[1.0, 5.0], &[2.0, 3.0]), 2.5);
+        assert_eq!(median(&[9.0], &[-1.0, 4.0]), 4.0);
+    }
+
+    fn clean_into(algorithm: Algorithm, input: &[f32], output: &mut [f32], radius: usize) {
+        clean(
+            algorithm,
+            input,
+            output,
+            radius,
+            2.0,
+            &mut Histogram::default(),
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The number of 1 dB wide bins in a [`Histogram`]. A sample's bin is determined by how far it
+/// strays from its neighbours relative to their spread, which is exactly what the threshold
+/// parameter is compared against. The last bin also collects everything above it.
+pub const NUM_BINS: usize = 24;
+/// How many one second slices of detection history are kept around for analysis.
+pub const MAX_HISTORY_SECONDS: usize = 60;
+/// The number of detections per second that is still considered acceptable when suggesting a
+/// threshold. Genuine pops are rare, so anything flagged more often than this is most likely
+/// program material.
+const MAX_SUGGESTED_RATE: f32 = 1.0;
+
+/// Counts of near-misses and detections binned by their deviation in decibels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Histogram {
+    pub counts: [u32; NUM_BINS],
+}
+
+impl Histogram {
+    /// Record a sample that deviates from its window by `deviation` times the window's spread.
+    /// Anything below 0 dB is not interesting enough to keep track of.
+    pub fn record(&mut self, deviation: f64) {
+        if deviation.is_nan() || deviation < 1.0 {
+            return;
+        }
+
+        let bin = (20.0 * deviation.log10()) as usize;
+        self.counts[bin.min(NUM_BINS - 1)] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    /// The lowest threshold in decibels at which at most `MAX_SUGGESTED_RATE` samples per second
+    /// would be repaired, assuming this histogram covers `seconds` seconds of audio.
+    pub fn suggest_threshold_db(&self, seconds: usize) -> f32 {
+        let allowed = MAX_SUGGESTED_RATE * seconds as f32;
+
+        let mut above = 0;
+        for (bin, count) in self.counts.iter().enumerate().rev() {
+            above += count;
+            if above as f32 > allowed {
+                return (bin + 1) as f32;
+            }
+        }
+
+        0.0
+    }
+}
+
+/// One second slices of detection histograms, shared between the audio thread and the background
+/// analysis task.
+#[derive(Debug)]
+pub struct DetectionHistory {
+    slices: Vec<Histogram>,
+    /// The index in `slices` the next slice will be written to.
+    next: usize,
+    /// The number of slices written so far, capped at `MAX_HISTORY_SECONDS`.
+    len: usize,
+    /// Everything recorded since the total was last reset, for the editor's histogram.
+    total: Histogram,
+}
+
+impl Default for DetectionHistory {
+    fn default() -> Self {
+        Self {
+            slices: vec![Histogram::default(); MAX_HISTORY_SECONDS],
+            next: 0,
+            len: 0,
+            total: Histogram::default(),
+        }
+    }
+}
+
+impl DetectionHistory {
+    fn push(&mut self, slice: Histogram) {
+        self.slices[self.next] = slice;
+        self.next = (self.next + 1) % MAX_HISTORY_SECONDS;
+        self.len = (self.len + 1).min(MAX_HISTORY_SECONDS);
+        self.total.merge(&slice);
+    }
+
+    /// Every recorded slice since the last call to [`reset_total()`][Self::reset_total()],
+    /// including the ones that no longer fit in the history.
+    pub fn total(&self) -> Histogram {
+        self.total
+    }
+
+    pub fn reset_total(&mut self) {
+        self.total = Histogram::default();
+    }
+
+    /// Sum up the last `seconds` slices. Returns the histogram along with the number of seconds
+    /// it actually covers.
+    pub fn last_seconds(&self, seconds: usize) -> (Histogram, usize) {
+        let seconds = seconds.min(self.len);
+
+        let mut histogram = Histogram::default();
+        for i in 1..=seconds {
+            let idx = (self.next + MAX_HISTORY_SECONDS - i) % MAX_HISTORY_SECONDS;
+            histogram.merge(&self.slices[idx]);
+        }
+
+        (histogram, seconds)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The audio thread's side of the [`DetectionHistory`]. Samples are accumulated locally and only
+/// handed over once per second, and a slice is carried over to the next second if the history is
+/// locked at that time so the audio thread never has to wait.
+#[derive(Debug, Default)]
+pub struct DetectionRecorder {
+    pub current: Histogram,
+    samples_in_slice: usize,
+    slice_length: usize,
+    history: Arc<Mutex<DetectionHistory>>,
+}
+
+impl DetectionRecorder {
+    pub fn new(history: Arc<Mutex<DetectionHistory>>) -> Self {
+        Self {
+            history,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.slice_length = sample_rate as usize;
+    }
+
+    /// Advance the recorder's clock after `num_samples` samples have been recorded.
+    pub fn advance(&mut self, num_samples: usize) {
+        self.samples_in_slice += num_samples;
+        if self.samples_in_slice < self.slice_length {
+            return;
+        }
+
+        if let Some(mut history) = self.history.try_lock() {
+            history.push(self.current);
+            self.current = Histogram::default();
+            self.samples_in_slice -= self.slice_length;
+        }
+    }
+}
+
+/// Expensive statistics that are computed on the background thread, see
+/// [`Plugin::task_executor()`][nih_plug::prelude::Plugin::task_executor()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisTask {
+    /// Analyze the detections over the last `seconds` seconds.
+    Analyze { seconds: usize },
+}
+
+/// The outcome of the last [`AnalysisTask::Analyze`], shown in the editor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalysisResult {
+    pub histogram: Histogram,
+    /// The number of seconds that could actually be analyzed.
+    pub seconds: usize,
+    pub suggested_threshold_db: f32,
+}
+
+pub fn run_task(
+    task: AnalysisTask,
+    history: &Mutex<DetectionHistory>,
+    result: &Mutex<Option<AnalysisResult>>,
+) {
+    match task {
+        AnalysisTask::Analyze { seconds } => {
+            let (histogram, seconds) = history.lock().last_seconds(seconds);
+            *result.lock() = Some(AnalysisResult {
+                histogram,
+                seconds,
+                suggested_threshold_db: histogram.suggest_threshold_db(seconds),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binning() {
+        let mut histogram = Histogram::default();
+        histogram.record(0.5);
+        histogram.record(f64::NAN);
+        histogram.record(1.0);
+        histogram.record(2.0);
+        histogram.record(f64::INFINITY);
+
+        assert_eq!(histogram.counts[0], 1);
+        assert_eq!(histogram.counts[6], 1);
+        assert_eq!(histogram.counts[NUM_BINS - 1], 1);
+        assert_eq!(histogram.total(), 3);
+    }
+
+    #[test]
+    fn suggestion_skips_dense_bins() {
+        let mut histogram = Histogram::default();
+        histogram.counts[3] = 500;
+        histogram.counts[4] = 20;
+        histogram.counts[18] = 2;
+
+        // With ten seconds of audio, ten detections are fine, so everything above bin 4 is fine
+        assert_eq!(histogram.suggest_threshold_db(10), 5.0);
+        assert_eq!(histogram.suggest_threshold_db(1000), 0.0);
+    }
+
+    #[test]
+    fn history_wraps_around() {
+        let mut history = DetectionHistory::default();
+        for i in 0..(MAX_HISTORY_SECONDS + 5) {
+            let mut slice = Histogram::default();
+            slice.counts[0] = i as u32;
+            history.push(slice);
+        }
+
+        let (histogram, seconds) = history.last_seconds(2);
+        assert_eq!(seconds, 2);
+        assert_eq!(
+            histogram.counts[0],
+            (MAX_HISTORY_SECONDS + 4 + MAX_HISTORY_SECONDS + 3) as u32
+        );
+        assert_eq!(history.last_seconds(1000).1, MAX_HISTORY_SECONDS);
+
+        let n = (MAX_HISTORY_SECONDS + 5) as u32;
+        assert_eq!(history.total().counts[0], n * (n - 1) / 2);
+        history.reset_total();
+        assert_eq!(history.total().total(), 0);
+    }
+}
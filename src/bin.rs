@@ -1,56 +1,2388 @@
 #![feature(iter_map_windows)]
 
-use clap::Parser;
-use flac::StreamReader;
-use robo_depop_plugin::clean_data;
-use std::{fs::File, io::Read, path::PathBuf};
+mod cli;
 
-/// Simple program to greet a person
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use cli::batch::Job;
+use cli::bit_depth::Dither;
+use cli::channels::{ChannelMap, MixPoint};
+use cli::clean::{difference, AlgorithmArg, Outcome, Repair, StreamCleaner};
+use cli::convert::Converter;
+use cli::decode::{Decoder, Spec};
+use cli::encode::{Encoder, Output, OutputFormat};
+use cli::error::Error;
+use cli::flac_encoder::{DEFAULT_COMPRESSION_LEVEL, MAX_COMPRESSION_LEVEL};
+use cli::inject::InjectConfig;
+#[cfg(feature = "live")]
+use cli::live::Live;
+use cli::loudness::{Loudness, LoudnessComparison, LoudnessMeter};
+use cli::metadata::Metadata;
+use cli::raw::{RawFormat, RawSpec};
+use cli::summary::Summary;
+use cli::template::OutputTemplate;
+use cli::time::format_timestamp;
+use cli::watch::Watcher;
+use indicatif::{MultiProgress, ProgressBar};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use robo_depop_plugin::CleanConfig;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How many samples per channel are decoded before they're cleaned.
+const BLOCK_LEN: usize = 1 << 16;
+/// How often `--watch` looks for new files.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Removes pops and clicks from recordings
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
-struct Args {
-    /// Input file
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Don't show a progress bar, and only print errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Print more details about each file: its format, the repairs per channel, and how long
+    /// cleaning it took. Given twice, this also lists every repair.
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Clean files, directories, or standard input
+    Clean(CleanArgs),
+    /// Only look for clicks and list the ones that would be repaired, without writing any audio
+    Analyze(AnalyzeArgs),
+    /// Render a stretch of a file before and after cleaning as two PNG images
+    Plot(PlotArgs),
+    /// Write a stretch of a file before and after cleaning as CSV, one row per sample
+    Dump(DumpArgs),
+    /// Decode files completely to check that they're intact. Exits with an error if any of them
+    /// aren't.
+    Verify(VerifyArgs),
+    /// Compare two versions of a recording sample by sample, lining them up first, and show how
+    /// much they differ
+    Compare(CompareArgs),
+    /// Add synthetic pops to a clean file, and list where they went. Cleaning the result shows
+    /// how many of them the settings catch.
+    Inject(InjectArgs),
+    /// Measure how fast each algorithm cleans a file, or generated noise, on this machine
+    Bench(BenchArgs),
+    /// Add known pops to a clean file, clean it with each algorithm, and score how many of the
+    /// pops were found and how close the result is to the original
+    Simulate(SimulateArgs),
+    /// Clean the audio from a sound card as it's recorded and play it back right away, to monitor
+    /// a transfer with the clicks already gone
+    #[cfg(feature = "live")]
+    Live(LiveArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct CleanArgs {
+    /// Input file, which can be FLAC, WAV, AIFF, MP3, Ogg Vorbis, or ALAC. Use `-` to read from
+    /// standard input. If this is a directory, every supported file in it and its subdirectories
+    /// gets cleaned. This can be given more than once to clean several files or directories, and
+    /// can be a pattern like `captures/*.flac` for shells that don't expand those.
+    #[arg(short, long, required_unless_present = "watch")]
+    input: Vec<PathBuf>,
+
+    #[command(flatten)]
+    raw: RawArgs,
+
+    /// Output file, or `-` to write to standard output. When cleaning a directory or more than one
+    /// input, this is the directory the cleaned files are written to. Directories keep their
+    /// structure. This can also be a template like `{dir}/{stem}_clean.{ext}`, filled in with
+    /// each input's directory, file name, name without its extension, and output extension.
+    #[arg(short, long, required_unless_present = "in_place")]
+    output: Option<PathBuf>,
+
+    /// The output format. By default files ending in `.flac` are written as FLAC, paths without an
+    /// extension in the input's format, and everything else including standard output as WAV with
+    /// integer samples.
+    #[arg(short, long)]
+    format: Option<OutputFormat>,
+
+    /// Write the output in the same format as the input: FLAC for FLAC, and WAV for everything
+    /// else. This is also what happens when the output path has no extension.
+    #[arg(long, conflicts_with = "format")]
+    same_format: bool,
+
+    /// Give WAV output a Broadcast WAV `bext` chunk even when the input doesn't have one. Chunks
+    /// in WAV inputs are always carried over, with a line for the cleaning added to their coding
+    /// history.
+    #[arg(long)]
+    bwf: bool,
+
+    /// The bit depth of the output. By default this is the same as the input's, or 24 bits for
+    /// lossy formats. Floating point WAV is always 32 bits, and integer WAV stores depths like 20
+    /// bits in the next whole number of bytes at the same level.
+    #[arg(
+        short,
+        long,
+        value_parser = PossibleValuesParser::new(["8", "16", "20", "24", "32"])
+            .map(|bits| bits.parse::<u16>().unwrap()),
+    )]
+    bit_depth: Option<u16>,
+
+    /// Add dither when reducing the bit depth, so quiet passages get a little hiss instead of the
+    /// distortion of rounding them. Without this, samples are only rounded.
+    #[arg(long, value_enum)]
+    dither: Option<Dither>,
+
+    /// Convert the output to this sample rate in Hz after cleaning. By default it keeps the input's
+    /// sample rate.
+    #[arg(
+        short,
+        long,
+        value_parser = clap::value_parser!(u32).range(1000..=768_000),
+    )]
+    sample_rate: Option<u32>,
+
+    /// How hard to compress FLAC output, from 0 (fastest) to 8 (smallest)
+    #[arg(
+        short,
+        long,
+        default_value_t = DEFAULT_COMPRESSION_LEVEL,
+        value_parser = clap::value_parser!(u8).range(0..=MAX_COMPRESSION_LEVEL as i64),
+    )]
+    compression_level: u8,
+
+    #[command(flatten)]
+    detection: DetectionArgs,
+
+    /// Look for pops in all channels together, and repair a sample in every channel when it's a
+    /// pop in any of them. By default each channel is cleaned on its own, which leaves the other
+    /// side of a click that's quieter there untouched.
+    #[arg(long)]
+    link_channels: bool,
+
+    /// Only keep these channels, counted from one and separated by commas, like `1` for the left
+    /// side of a dual-mono capture, or `1,1` to copy it to both sides. The others are dropped
+    /// before cleaning. Not to be confused with `--channels`, which is the number of channels in
+    /// raw input.
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u16).range(1..),
+        conflicts_with = "mono_mix",
+    )]
+    select_channels: Vec<u16>,
+
+    /// Mix all channels down to mono. By default each channel is cleaned first and the cleaned
+    /// channels are mixed, while `before` mixes them first and cleans the mix.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        default_missing_value = "after",
+    )]
+    mono_mix: Option<MixPoint>,
+
+    /// Only repair pops from this point on, given in seconds or as `h:mm:ss.sss`. The audio
+    /// before it is copied through untouched.
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    start: Option<f64>,
+
+    /// Only repair pops up to this point, given in seconds or as `h:mm:ss.sss`. The audio after it
+    /// is copied through untouched.
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    end: Option<f64>,
+
+    /// How many threads to clean with. Batches clean this many files at the same time, and single
+    /// files are split up between them. Defaults to the number of CPU cores.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Replace the input files with their cleaned versions, keeping the originals as `NAME.bak`
+    /// next to them. Only FLAC and WAV files can be cleaned in place.
+    #[arg(long, conflicts_with_all = ["output", "watch"])]
+    in_place: bool,
+
+    /// Keep the originals replaced by `--in-place` in this directory instead. Files found in a
+    /// directory keep their path relative to it.
+    #[arg(long, requires = "in_place")]
+    backup_dir: Option<PathBuf>,
+
+    /// Set for `analyze`, which only looks for clicks
+    #[arg(skip)]
+    dry_run: bool,
+
+    /// Taken from the global `--quiet`
+    #[arg(skip)]
+    quiet: bool,
+
+    /// Bring the cleaned audio's peak to this level in dBFS, like -1. This takes a second pass
+    /// over each file, as the peak is only known once all of it has been cleaned.
+    #[arg(
+        long,
+        value_name = "DBFS",
+        allow_negative_numbers = true,
+        value_parser = cli::normalize::parse_level,
+    )]
+    normalize: Option<f64>,
+
+    /// Measure the loudness of the input and the output, as the integrated and maximum short-term
+    /// loudness in LUFS and the true peak in dBTP. This is printed and added to JSON reports.
+    #[arg(long)]
+    loudness: bool,
+
+    /// Overwrite output files that already exist. Without this, files that would overwrite
+    /// something are skipped with an error.
+    #[arg(long)]
+    force: bool,
+
+    /// Also write what was removed from the audio (the original minus the cleaned audio) to this
+    /// file, as floating point WAV. When cleaning more than one file, this is a directory.
+    #[arg(long)]
+    diff: Option<PathBuf>,
+
+    /// Keep watching this directory, and clean every new file that shows up in it into the output
+    /// directory. Files are picked up once they stop growing, and marked as cleaned with a hidden
+    /// `.NAME.cleaned` file next to them.
+    #[arg(long, conflicts_with_all = ["input", "report"])]
+    watch: Option<PathBuf>,
+
+    /// Write a list of every repair to this file, with the sample, time, channel, and the values
+    /// before and after. This is CSV if the name ends in `.csv`, and JSON otherwise.
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Write the repairs to this file as an Audacity label track, to go through them one by one
+    /// after importing it with File > Import > Labels. Repairs less than 10 ms apart share a
+    /// label. When cleaning more than one file, this is a directory.
+    #[arg(long)]
+    labels: Option<PathBuf>,
+
+    /// Write the clicks to this file as regions for REAPER, to import with the Region/Marker
+    /// Manager and jump between them on the timeline. When cleaning more than one file, this is a
+    /// directory.
+    #[arg(long)]
+    markers: Option<PathBuf>,
+
+    /// Write a pair of short WAV files for every click into this directory, with the audio around
+    /// it before and after the repair, to audition each fix on its own. When cleaning more than
+    /// one file, each file gets a directory in it.
+    #[arg(long, conflicts_with = "in_place")]
+    snippets: Option<PathBuf>,
+
+    /// Write a JSON file next to every output, like `NAME.flac.json`, with the settings it was
+    /// cleaned with, the number of clicks, and the SHA-256 hashes of the input and the output. This
+    /// keeps a record of where each cleaned file came from that travels with it.
+    #[arg(long)]
+    provenance: bool,
+
+    /// Use the settings from this profile in the config file. Flags given on the command line
+    /// still take precedence over it.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// The config file holding the profiles. Defaults to `depop/config.toml` in the user's
+    /// configuration directory.
+    #[arg(long, requires = "profile")]
+    config: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct AnalyzeArgs {
+    /// The files or directories to look for clicks in, in any of the supported input formats. Use
+    /// `-` to read from standard input.
+    #[arg(short, long, required = true)]
+    input: Vec<PathBuf>,
+
+    #[command(flatten)]
+    raw: RawArgs,
+
+    #[command(flatten)]
+    detection: DetectionArgs,
+
+    /// Look for pops in all channels together, like for cleaning
+    #[arg(long)]
+    link_channels: bool,
+
+    /// Only look for pops in these channels, counted from one and separated by commas
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u16).range(1..),
+    )]
+    select_channels: Vec<u16>,
+
+    /// Only look for pops from this point on, given in seconds or as `h:mm:ss.sss`
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    start: Option<f64>,
+
+    /// Only look for pops up to this point, given in seconds or as `h:mm:ss.sss`
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    end: Option<f64>,
+
+    /// How many threads to analyze with. Defaults to the number of CPU cores.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// Also measure the loudness of the files and what it would be after cleaning
+    #[arg(long)]
+    loudness: bool,
+
+    /// Write a list of the clicks to this file, in the same format as for cleaning
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Write the clicks to this file as an Audacity label track, like for cleaning
+    #[arg(long)]
+    labels: Option<PathBuf>,
+
+    /// Write the clicks to this file as regions for REAPER, like for cleaning
+    #[arg(long)]
+    markers: Option<PathBuf>,
+
+    /// Write the audio around every click into this directory, before and after the repair it
+    /// would get, like for cleaning
+    #[arg(long)]
+    snippets: Option<PathBuf>,
+
+    /// Use the settings from this profile in the config file
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// The config file holding the profiles
+    #[arg(long, requires = "profile")]
+    config: Option<PathBuf>,
+}
+
+/// Analyzing is cleaning without an output.
+impl From<AnalyzeArgs> for CleanArgs {
+    fn from(args: AnalyzeArgs) -> Self {
+        CleanArgs {
+            input: args.input,
+            raw: args.raw,
+            output: None,
+            format: None,
+            same_format: false,
+            bwf: false,
+            bit_depth: None,
+            dither: None,
+            sample_rate: None,
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            detection: args.detection,
+            link_channels: args.link_channels,
+            select_channels: args.select_channels,
+            mono_mix: None,
+            start: args.start,
+            end: args.end,
+            jobs: args.jobs,
+            in_place: false,
+            backup_dir: None,
+            dry_run: true,
+            quiet: false,
+            normalize: None,
+            loudness: args.loudness,
+            force: false,
+            diff: None,
+            watch: None,
+            report: args.report,
+            labels: args.labels,
+            markers: args.markers,
+            snippets: args.snippets,
+            provenance: false,
+            profile: args.profile,
+            config: args.config,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct BenchArgs {
+    /// The file to clean. Without one, the algorithms clean stereo white noise at 48 kHz.
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+
+    /// How many seconds of audio to clean. Files are cut off after this.
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    seconds: u32,
+
+    /// How many times to clean the audio. The fastest run counts.
+    #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+
+    /// The algorithms to measure. Defaults to all of them.
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    algorithm: Vec<AlgorithmArg>,
+
+    /// The threshold to clean with, like when cleaning files
+    #[arg(short, long, default_value_t = 2.0, value_parser = cli::clean::parse_threshold)]
+    threshold: f64,
+
+    /// The window length to clean with, like when cleaning files
+    #[arg(short, long, default_value_t = 5, value_parser = cli::clean::parse_window)]
+    window: usize,
+
+    /// How many times to clean the audio in every run
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    passes: u32,
+
+    /// How many threads to clean with. Defaults to the number of CPU cores.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+}
+
+#[cfg(feature = "live")]
+#[derive(clap::Args, Debug)]
+struct LiveArgs {
+    /// The device to record from, by a name `--list-devices` shows. Defaults to the system's
+    /// default input.
+    #[arg(long)]
+    input_device: Option<String>,
+
+    /// The device to play the cleaned audio on, at the input's sample rate. Defaults to the
+    /// system's default output.
+    #[arg(long)]
+    output_device: Option<String>,
+
+    /// List the devices that can record and play, and exit
+    #[arg(long)]
+    list_devices: bool,
+
+    #[command(flatten)]
+    detection: DetectionArgs,
+
+    /// Look for pops in all channels together, like for cleaning
+    #[arg(long)]
+    link_channels: bool,
+
+    /// Stop after this long, given in seconds or as `h:mm:ss.sss`. Without it the cleaning runs
+    /// until it's interrupted.
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    duration: Option<f64>,
+}
+
+#[derive(clap::Args, Debug)]
+struct SimulateArgs {
+    /// The clean recording the pops are added to, in any of the supported input formats
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// How many seconds of the file to use. Files are cut off after this.
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    seconds: u32,
+
+    /// How many pops to add
+    #[arg(short = 'n', long, default_value_t = 100)]
+    count: usize,
+
+    /// How loud the pops are in dBFS, like for `inject`
+    #[arg(
+        long,
+        value_name = "DBFS",
+        default_value_t = -6.0,
+        allow_negative_numbers = true,
+        value_parser = cli::normalize::parse_level,
+    )]
+    amplitude: f64,
+
+    /// How many samples in a row each pop covers
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=64))]
+    width: u32,
+
+    /// The seed for placing the pops. The same seed always gives the same pops.
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+
+    /// The algorithms to score. Defaults to all of them.
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    algorithm: Vec<AlgorithmArg>,
+
+    /// The threshold to clean with, like when cleaning files
+    #[arg(short, long, default_value_t = 2.0, value_parser = cli::clean::parse_threshold)]
+    threshold: f64,
+
+    /// The window length to clean with, like when cleaning files
+    #[arg(short, long, default_value_t = 5, value_parser = cli::clean::parse_window)]
+    window: usize,
+
+    /// How many times to clean the audio
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    passes: u32,
+
+    /// Exit with an error if an algorithm finds less than this share of the pops, from 0 to 1
+    #[arg(long, value_parser = parse_share)]
+    min_recall: Option<f64>,
+
+    /// Exit with an error if less than this share of an algorithm's repairs are part of a pop,
+    /// from 0 to 1
+    #[arg(long, value_parser = parse_share)]
+    min_precision: Option<f64>,
+
+    /// How many threads to clean with. Defaults to the number of CPU cores.
+    #[arg(short, long)]
+    jobs: Option<usize>,
+}
+
+fn parse_share(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(share) if (0.0..=1.0).contains(&share) => Ok(share),
+        _ => Err("must be a number from 0 to 1".to_owned()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct InjectArgs {
+    /// The clean file, in any of the supported input formats
     #[arg(short, long)]
     input: PathBuf,
 
+    /// Where the file with the pops goes. Files ending in `.flac` are written as FLAC, everything
+    /// else as WAV.
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Where the list of pops goes, in the same format as `--report`, with the original and the
+    /// corrupted value of every sample. Defaults to the output's path ending in `.json`.
+    #[arg(long)]
+    truth: Option<PathBuf>,
+
+    /// How many pops to add
+    #[arg(short = 'n', long, default_value_t = 10)]
+    count: usize,
+
+    /// How loud the pops are in dBFS, relative to the audio they land on
+    #[arg(
+        short,
+        long,
+        value_name = "DBFS",
+        default_value_t = -6.0,
+        allow_negative_numbers = true,
+        value_parser = cli::normalize::parse_level,
+    )]
+    amplitude: f64,
+
+    /// How many samples in a row each pop covers
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=64))]
+    width: u32,
+
+    /// The seed for placing the pops. The same seed always gives the same pops.
+    #[arg(short, long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// The files to check
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// The file to compare against, like the output of an earlier version
+    a: PathBuf,
+
+    /// The file to compare, which has to have the same number of channels and sample rate
+    b: PathBuf,
+
+    /// How far apart in seconds the starts of the files may be. They're lined up by shifting them
+    /// against each other by up to this much.
+    #[arg(long, default_value_t = 0.1, value_parser = parse_max_offset)]
+    max_offset: f64,
+}
+
+fn parse_max_offset(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if (0.0..=1.0).contains(&seconds) => Ok(seconds),
+        _ => Err("must be a number of seconds from 0 to 1".to_owned()),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct PlotArgs {
+    /// The file to plot, in any of the supported input formats
+    #[arg(short, long)]
+    input: PathBuf,
+
+    /// Where the plot starts, given in seconds or as `h:mm:ss.sss`
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    start: f64,
+
+    /// How many samples to plot
+    #[arg(
+        short,
+        long,
+        default_value_t = 200,
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    length: u32,
+
+    /// The channel to plot, counting from 1
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    channel: u16,
+
+    /// The directory the images are written to. They are named after the input file, ending in
+    /// `-before.png` and `-after.png`.
+    #[arg(short, long, default_value = ".")]
+    output: PathBuf,
+
+    #[command(flatten)]
+    detection: DetectionArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct DumpArgs {
+    /// The file to dump, in any of the supported input formats
     #[arg(short, long)]
+    input: PathBuf,
+
+    /// Where the dump starts, given in seconds or as `h:mm:ss.sss`
+    #[arg(long, value_parser = cli::time::parse_timestamp)]
+    start: f64,
+
+    /// How many samples per channel to dump
+    #[arg(
+        short,
+        long,
+        default_value_t = 200,
+        value_parser = clap::value_parser!(u32).range(1..),
+    )]
+    length: u32,
+
+    /// The CSV file to write, with the sample, its time in seconds, and each channel before and
+    /// after cleaning. Defaults to standard output.
+    #[arg(short, long, default_value = "-")]
     output: PathBuf,
+
+    #[command(flatten)]
+    detection: DetectionArgs,
+}
+
+/// The format of headerless inputs, which can't be detected.
+#[derive(clap::Args, Debug)]
+struct RawArgs {
+    /// Read the inputs as headerless PCM with interleaved samples in this format, like the
+    /// captures of hardware loggers. `--rate` and `--channels` give the rest of the format.
+    #[arg(long, value_enum, requires_all = ["rate", "channels"])]
+    raw: Option<RawFormat>,
+
+    /// The sample rate of `--raw` inputs in Hz
+    #[arg(
+        long,
+        requires = "raw",
+        value_parser = clap::value_parser!(u32).range(1000..=768_000),
+    )]
+    rate: Option<u32>,
+
+    /// The number of channels in `--raw` inputs
+    #[arg(
+        long,
+        requires = "raw",
+        value_parser = clap::value_parser!(u16).range(1..),
+    )]
+    channels: Option<u16>,
+}
+
+impl RawArgs {
+    /// Open `input`, which is raw PCM if `--raw` was given.
+    fn open(&self, input: &Path) -> io::Result<Decoder> {
+        match (self.raw, self.rate, self.channels) {
+            (Some(format), Some(sample_rate), Some(num_channels)) => Decoder::open_raw(
+                input,
+                RawSpec {
+                    format,
+                    sample_rate,
+                    num_channels: num_channels as usize,
+                },
+            ),
+            _ => Decoder::open(input),
+        }
+    }
+}
+
+/// The settings that control which samples get repaired, and how.
+#[derive(clap::Args, Debug)]
+struct DetectionArgs {
+    /// How a sample's expected value is estimated, and thus what it's replaced with.
+    #[arg(short, long, value_enum, default_value_t = AlgorithmArg::WindowAvg)]
+    algorithm: AlgorithmArg,
+
+    /// How far a sample may stray from its expected value, as a multiple of its neighbours' range,
+    /// before it gets repaired. Lower values catch quieter pops, but also more of the music.
+    #[arg(short, long, default_value_t = 2.0, value_parser = cli::clean::parse_threshold)]
+    threshold: f64,
+
+    /// The number of samples each sample is compared against, including itself. Wider windows
+    /// help with material that has a lot of high frequency content.
+    #[arg(short, long, default_value_t = 5, value_parser = cli::clean::parse_window)]
+    window: usize,
+
+    /// How many times to clean the audio. Extra passes can repair pops that sit right next to each
+    /// other, which hide each other from a single pass.
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    passes: u32,
+}
+
+impl DetectionArgs {
+    fn config(&self) -> CleanConfig {
+        CleanConfig {
+            algorithm: self.algorithm.into(),
+            threshold: self.threshold,
+            radius: self.window / 2,
+            passes: self.passes as usize,
+        }
+    }
+}
+
+impl CleanArgs {
+    /// Fill in the settings from `--profile` that weren't given on the command line.
+    fn apply_profile(&mut self, matches: &ArgMatches) -> Result<(), Error> {
+        let Some(name) = &self.profile else {
+            return Ok(());
+        };
+        let path = self
+            .config
+            .clone()
+            .or_else(cli::profile::default_config_path)
+            .ok_or_else(|| {
+                Error::Usage("Could not find the config directory, use --config".to_owned())
+            })?;
+        let profile = cli::profile::load_profile(&path, name)
+            .map_err(|error| Error::Usage(format!("{}: {error}", path.display())))?;
+
+        let from_command_line = |id| matches.value_source(id) == Some(ValueSource::CommandLine);
+        let detection = &mut self.detection;
+        if let (Some(algorithm), false) = (profile.algorithm, from_command_line("algorithm")) {
+            detection.algorithm = algorithm;
+        }
+        if let (Some(threshold), false) = (profile.threshold, from_command_line("threshold")) {
+            detection.threshold = threshold;
+        }
+        if let (Some(window), false) = (profile.window, from_command_line("window")) {
+            detection.window = window;
+        }
+        if let (Some(passes), false) = (profile.passes, from_command_line("passes")) {
+            detection.passes = passes;
+        }
+        if !self.same_format {
+            self.format = self.format.or(profile.format);
+        }
+
+        Ok(())
+    }
+
+    /// Open `input` with its channels picked or mixed before cleaning, as `--select-channels` and
+    /// `--mono-mix before` ask for.
+    fn open(&self, input: &Path) -> io::Result<Decoder> {
+        let decoder = self.raw.open(input)?;
+        let channel_map = if !self.select_channels.is_empty() {
+            let channels = self
+                .select_channels
+                .iter()
+                .map(|&channel| channel as usize - 1);
+            ChannelMap::Select(channels.collect())
+        } else if self.mono_mix == Some(MixPoint::Before) {
+            ChannelMap::Mix
+        } else {
+            return Ok(decoder);
+        };
+        decoder.with_channel_map(channel_map)
+    }
+
+    /// The positions of the samples that may be repaired in a file with the given sample rate.
+    fn range(&self, sample_rate: u32) -> Range<u64> {
+        let position = |seconds: f64| (seconds * sample_rate as f64).round() as u64;
+        self.start.map_or(0, position)..self.end.map_or(u64::MAX, position)
+    }
+
+    /// The format a single file is written in: the `--format`, the input's format with
+    /// `--same-format` or an output path without an extension, or else the output's extension.
+    fn output_format(&self, input: &Path) -> OutputFormat {
+        let output = self.output();
+        match self.format {
+            Some(format) => format,
+            None if self.same_format
+                || (!cli::is_stdio(output) && output.extension().is_none()) =>
+            {
+                OutputFormat::from_path(input)
+            }
+            None => OutputFormat::from_path(output),
+        }
+    }
+
+    /// The output path. This is only empty when analyzing.
+    fn output(&self) -> &Path {
+        self.output.as_deref().unwrap_or(Path::new(""))
+    }
+}
+
+/// Errors are printed where they happen, so the exit code is all that's left to report here. See
+/// [`Error::exit_code()`] for what the codes mean.
+pub fn main() -> ExitCode {
+    let matches = Cli::command().get_matches();
+    let args = match Cli::from_arg_matches(&matches) {
+        Ok(args) => args,
+        Err(error) => error.exit(),
+    };
+    cli::logging::init(args.verbose, args.quiet);
+    // The profile needs to know which settings were given for the subcommand itself
+    let Some((_, command_matches)) = matches.subcommand() else {
+        unreachable!("clap requires a subcommand");
+    };
+    let mut clean_args = match args.command {
+        Command::Clean(clean_args) => clean_args,
+        Command::Analyze(analyze_args) => analyze_args.into(),
+        Command::Plot(plot_args) => return plot(&plot_args),
+        Command::Dump(dump_args) => return dump(&dump_args),
+        Command::Verify(verify_args) => return verify(&verify_args),
+        Command::Compare(compare_args) => return compare(&compare_args),
+        Command::Inject(inject_args) => return inject(&inject_args),
+        Command::Bench(bench_args) => return bench(&bench_args),
+        Command::Simulate(simulate_args) => return simulate(&simulate_args),
+        #[cfg(feature = "live")]
+        Command::Live(live_args) => return live(&live_args),
+    };
+    clean_args.quiet = args.quiet;
+
+    clean(&mut clean_args, command_matches)
 }
 
-pub fn main() {
-    let args = Args::parse();
+/// Clean or analyze everything `args` asks for.
+fn clean(args: &mut CleanArgs, matches: &ArgMatches) -> ExitCode {
+    if let Err(error) = args.apply_profile(matches) {
+        return report_error(&error);
+    }
 
-    let mut buf = vec![];
+    if let (Some(start), Some(end)) = (args.start, args.end) {
+        if start >= end {
+            return report_error(&Error::Usage(
+                "The start of the range has to come before its end".to_owned(),
+            ));
+        }
+    }
 
-    File::open(args.input)
-        .expect("Could not open input file")
-        .read_to_end(&mut buf)
-        .expect("Could not read whole file!");
+    let template = match args
+        .output
+        .as_deref()
+        .filter(|output| OutputTemplate::is_template(output))
+    {
+        Some(output) => match OutputTemplate::parse(&output.to_string_lossy()) {
+            Ok(template) => Some(template),
+            Err(error) => return report_error(&Error::Usage(error)),
+        },
+        None => None,
+    };
+
+    if let Some(watch_dir) = &args.watch {
+        if template.is_some() {
+            return report_error(&Error::Usage(
+                "--watch needs an output directory, not a template".to_owned(),
+            ));
+        }
+        return watch(args, watch_dir);
+    }
 
-    match StreamReader::<File>::from_buffer(&buf) {
-        Ok(mut stream) => {
-            if stream.info().channels != 1 {
-                eprintln!("Given FLAC file is more than one channel");
-                return;
+    // A pattern is cleaned like a directory, even if it only matches a single file
+    if let [input] = args.input.as_slice() {
+        if !input.is_dir() && !cli::batch::is_glob(input) {
+            let input = input.clone();
+            if let Some(template) = &template {
+                let mut job = cli::batch::file_job(&input, Path::new(""), args.format);
+                template.apply(&mut job, args.format);
+                args.output = Some(job.output);
             }
+            return clean_single_file(args, &input);
+        }
+    }
+
+    let mut inputs = Vec::new();
+    for input in &args.input {
+        match cli::batch::expand_glob(input) {
+            Ok(paths) => inputs.extend(paths),
+            Err(error) => return report_error(&Error::decode(input, error)),
+        }
+    }
+    args.input = inputs;
 
-            let spec = hound::WavSpec {
-                channels: 1,
-                sample_rate: stream.info().sample_rate,
-                bits_per_sample: stream.info().bits_per_sample as u16,
-                sample_format: hound::SampleFormat::Int,
+    clean_batch(args, template.as_ref())
+}
+
+/// Log `error`, and return the code to exit with.
+fn report_error(error: &Error) -> ExitCode {
+    log::error!("{error}");
+    error.into()
+}
+
+fn clean_single_file(args: &CleanArgs, input: &Path) -> ExitCode {
+    let format = args.output_format(input);
+    let name = input.file_name().unwrap_or(input.as_os_str());
+    let pool = match thread_pool(args.jobs) {
+        Ok(pool) => pool,
+        Err(error) => return report_error(&error),
+    };
+    let output = if args.in_place { input } else { args.output() };
+    if !args.in_place && !args.dry_run {
+        if let Err(error) = check_not_input(input, output) {
+            return report_error(&error);
+        }
+    }
+    let input_sha256 = match hash_input(args, input, output) {
+        Ok(input_sha256) => input_sha256,
+        Err(error) => return report_error(&error),
+    };
+    let progress = cli::progress::file_bar(args.quiet, name.to_string_lossy().into_owned());
+    let result = pool.install(|| {
+        if args.in_place {
+            clean_in_place(
+                args,
+                input,
+                Path::new(name),
+                args.diff.as_deref(),
+                &progress,
+            )
+        } else {
+            clean_file(
+                args,
+                input,
+                args.output(),
+                format,
+                args.diff.as_deref(),
+                &progress,
+            )
+        }
+    });
+    progress.finish_and_clear();
+    let result = result.and_then(|outcome| {
+        if let Some(labels) = &args.labels {
+            write_labels(labels, &outcome)?;
+        }
+        if let Some(markers) = &args.markers {
+            write_markers(markers, &outcome)?;
+        }
+        if let Some(snippets) = &args.snippets {
+            write_snippets(args, input, snippets, &outcome)?;
+        }
+        if let Some(input_sha256) = input_sha256 {
+            write_provenance(args, input, input_sha256, output, format, &outcome)?;
+        }
+        Ok(outcome)
+    });
+    match result {
+        Ok(outcome) => {
+            if args.dry_run {
+                print_repairs(input, &outcome);
+            }
+            write_report(args, &[(input.to_owned(), outcome)])
+        }
+        // Standard output may be carrying the audio
+        Err(error) => report_error(&error),
+    }
+}
+
+/// Clean several files or whole directories into the output directory, using a thread pool.
+/// If some of the files can't be cleaned, the rest still are, and the exit code is that of the
+/// first failure.
+fn clean_batch(args: &CleanArgs, template: Option<&OutputTemplate>) -> ExitCode {
+    let mut jobs = Vec::new();
+    for input in &args.input {
+        if cli::is_stdio(input) {
+            return report_error(&Error::Usage(
+                "Standard input can only be cleaned on its own".to_owned(),
+            ));
+        }
+
+        if input.is_dir() {
+            match cli::batch::collect_jobs(input, args.output(), args.format) {
+                Ok(found) => jobs.extend(found),
+                Err(error) => return report_error(&Error::decode(input, error)),
+            }
+        } else {
+            jobs.push(cli::batch::file_job(input, args.output(), args.format));
+        }
+    }
+    if let Some(template) = template {
+        for job in &mut jobs {
+            template.apply(job, args.format);
+        }
+    }
+    // Files with the same name from different directories, or a template without `{stem}`,
+    // would overwrite each other
+    if !args.dry_run && !args.in_place {
+        let mut outputs = HashMap::new();
+        for job in &jobs {
+            if let Err(error) = check_not_input(&job.input, &job.output) {
+                return report_error(&error);
+            }
+            if let Some(other) = outputs.insert(&job.output, &job.input) {
+                return report_error(&Error::Usage(format!(
+                    "Both {} and {} would be written to {}",
+                    other.display(),
+                    job.input.display(),
+                    job.output.display()
+                )));
+            }
+        }
+    }
+
+    // Outputs that are newer than their inputs are left over from an earlier run of the batch,
+    // which is picked up where it stopped
+    if !args.dry_run && !args.in_place && !args.force {
+        let num_jobs = jobs.len();
+        jobs.retain(|job| {
+            let done = job.is_done();
+            if done {
+                log::debug!("{}: already cleaned, skipping", job.name.display());
+            }
+            !done
+        });
+        let skipped = num_jobs - jobs.len();
+        if skipped > 0 {
+            log::info!(
+                "Skipping {skipped} of {num_jobs} files that were already cleaned, use --force to \
+                 clean them again"
+            );
+        }
+    }
+
+    let pool = match thread_pool(args.jobs) {
+        Ok(pool) => pool,
+        Err(error) => return report_error(&error),
+    };
+
+    let bars = MultiProgress::new();
+    let results: Vec<Result<(PathBuf, Outcome), ExitCode>> = pool.install(|| {
+        // One broken file shouldn't stop the rest of the batch
+        jobs.par_iter()
+            .enumerate()
+            .map(|(index, job)| {
+                let progress = bars.add(cli::progress::file_bar(
+                    args.quiet,
+                    format!("[{}/{}] {}", index + 1, jobs.len(), job.name.display()),
+                ));
+                let result = clean_job(args, job, &progress);
+                progress.finish_and_clear();
+                bars.remove(&progress);
+                match result {
+                    Ok(outcome) => {
+                        if args.dry_run {
+                            bars.suspend(|| print_repairs(&job.name, &outcome));
+                        }
+                        Ok((job.name.clone(), outcome))
+                    }
+                    Err(error) => Err(bars.suspend(|| report_error(&error))),
+                }
+            })
+            .collect()
+    });
+
+    let mut exit_code = ExitCode::SUCCESS;
+    let mut outcomes = Vec::new();
+    for result in results {
+        match result {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(code) if exit_code == ExitCode::SUCCESS => exit_code = code,
+            Err(_) => (),
+        }
+    }
+    let report_exit_code = write_report(args, &outcomes);
+
+    if exit_code == ExitCode::SUCCESS {
+        report_exit_code
+    } else {
+        exit_code
+    }
+}
+
+/// The threads for `--jobs`. Files are cleaned on them along with the segments of each file.
+fn thread_pool(jobs: Option<usize>) -> Result<ThreadPool, Error> {
+    // Zero threads leaves the choice to rayon, which uses one thread per core
+    ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()
+        .map_err(|error| {
+            Error::Other(io::Error::other(format!(
+                "Could not start the worker threads: {error}"
+            )))
+        })
+}
+
+/// Clean a file that's part of a batch, creating the directories it goes into.
+fn clean_job(args: &CleanArgs, job: &Job, progress: &ProgressBar) -> Result<Outcome, Error> {
+    // The differences, labels, and markers mirror the structure of the output directory. Outputs
+    // from a template only have their file name to go by.
+    let relative = job
+        .output
+        .strip_prefix(args.output())
+        .unwrap_or_else(|_| Path::new(job.output.file_name().unwrap_or_default()));
+    let diff = args
+        .diff
+        .as_ref()
+        .map(|diff_dir| diff_dir.join(relative).with_extension("wav"));
+    let labels = args
+        .labels
+        .as_ref()
+        .map(|labels_dir| labels_dir.join(relative).with_extension("txt"));
+    let markers = args
+        .markers
+        .as_ref()
+        .map(|markers_dir| markers_dir.join(relative).with_extension("csv"));
+    let snippets = args
+        .snippets
+        .as_ref()
+        .map(|snippets_dir| snippets_dir.join(relative).with_extension(""));
+    let output = Some(&job.output).filter(|_| !args.in_place && !args.dry_run);
+    let diff_path = diff.as_ref().filter(|_| !args.dry_run);
+    for path in [output, diff_path, labels.as_ref(), markers.as_ref()]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| Error::write(parent, error))?;
+        }
+    }
+
+    let input_sha256 = hash_input(
+        args,
+        &job.input,
+        if args.in_place {
+            &job.input
+        } else {
+            &job.output
+        },
+    )?;
+    let outcome = if args.in_place {
+        // Files that were given directly have no directory to be relative to
+        let name = if job.name == job.input {
+            Path::new(job.input.file_name().unwrap_or_default())
+        } else {
+            &job.name
+        };
+        clean_in_place(args, &job.input, name, diff.as_deref(), progress)?
+    } else if args.dry_run {
+        clean_file(
+            args,
+            &job.input,
+            &job.output,
+            job.format,
+            diff.as_deref(),
+            progress,
+        )?
+    } else {
+        clean_through_temp(args, job, diff.as_deref(), progress)?
+    };
+    if let Some(labels) = &labels {
+        write_labels(labels, &outcome)?;
+    }
+    if let Some(markers) = &markers {
+        write_markers(markers, &outcome)?;
+    }
+    if let Some(snippets) = &snippets {
+        write_snippets(args, &job.input, snippets, &outcome)?;
+    }
+    if let Some(input_sha256) = input_sha256 {
+        let output = if args.in_place {
+            &job.input
+        } else {
+            &job.output
+        };
+        write_provenance(args, &job.input, input_sha256, output, job.format, &outcome)?;
+    }
+
+    Ok(outcome)
+}
+
+/// Write the repairs in `outcome` to `path` as an Audacity label track.
+fn write_labels(path: &Path, outcome: &Outcome) -> Result<(), Error> {
+    cli::labels::write(path, &outcome.repairs, outcome.spec.sample_rate)
+        .map_err(|error| Error::write(path, error))
+}
+
+/// Write the repairs in `outcome` to `path` as REAPER regions.
+fn write_markers(path: &Path, outcome: &Outcome) -> Result<(), Error> {
+    cli::labels::write_reaper(path, &outcome.repairs, outcome.spec.sample_rate)
+        .map_err(|error| Error::write(path, error))
+}
+
+/// Write snippets of the clicks in `outcome` into `dir`, taking the audio from `input` again.
+fn write_snippets(
+    args: &CleanArgs,
+    input: &Path,
+    dir: &Path,
+    outcome: &Outcome,
+) -> Result<(), Error> {
+    let decoder = args
+        .open(input)
+        .map_err(|error| Error::decode(input, error))?;
+    let count = cli::snippets::write_snippets(decoder, dir, &outcome.repairs)
+        .map_err(|error| Error::write(dir, error))?;
+    log::debug!("{}: wrote snippets of {count} clicks", dir.display());
+    Ok(())
+}
+
+/// Clean a job into a temporary file next to its output, and move that into place once it's
+/// complete. A batch that's interrupted then never leaves a partial output behind, which would look
+/// like it's done when the batch is resumed.
+fn clean_through_temp(
+    args: &CleanArgs,
+    job: &Job,
+    diff: Option<&Path>,
+    progress: &ProgressBar,
+) -> Result<Outcome, Error> {
+    check_overwrite(args, &job.output)?;
+    let temp = cli::in_place::temp_path(&job.output);
+    // A temporary file that's still around can only be left over from an interrupted run
+    let _ = fs::remove_file(&temp);
+    let outcome =
+        clean_file(args, &job.input, &temp, job.format, diff, progress).inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })?;
+    fs::rename(&temp, &job.output).map_err(|error| {
+        let _ = fs::remove_file(&temp);
+        Error::write(&job.output, error)
+    })?;
+
+    Ok(outcome)
+}
+
+/// Refuse to overwrite `path` if it exists, unless `--force` was given.
+fn check_overwrite(args: &CleanArgs, path: &Path) -> Result<(), Error> {
+    if args.force || cli::is_stdio(path) || !path.exists() {
+        return Ok(());
+    }
+
+    Err(Error::Write {
+        path: path.to_owned(),
+        source: io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the file already exists, use --force to overwrite it",
+        ),
+    })
+}
+
+/// Fail if `output` is `input` itself, which would be cut off while it's still being read.
+fn check_not_input(input: &Path, output: &Path) -> Result<(), Error> {
+    if !cli::is_same_file(input, output) {
+        return Ok(());
+    }
+
+    Err(Error::Usage(format!(
+        "{} would be cleaned into itself, use --in-place to replace files with their cleaned \
+         versions",
+        input.display()
+    )))
+}
+
+/// Hash `input` before it's cleaned into `output`, if `--provenance` needs it.
+fn hash_input(args: &CleanArgs, input: &Path, output: &Path) -> Result<Option<String>, Error> {
+    if !args.provenance {
+        return Ok(None);
+    }
+    if cli::is_stdio(input) || cli::is_stdio(output) {
+        return Err(Error::Usage(
+            "--provenance hashes the input and the output, so it can't use standard input or output"
+                .to_owned(),
+        ));
+    }
+
+    cli::provenance::sha256(input)
+        .map(Some)
+        .map_err(|error| Error::decode(input, error))
+}
+
+/// Write the provenance of `output`, which was cleaned from `input` in `format`. `input_sha256` is
+/// the hash of the input from before it was cleaned, as it's gone after cleaning in place.
+fn write_provenance(
+    args: &CleanArgs,
+    input: &Path,
+    input_sha256: String,
+    output: &Path,
+    format: OutputFormat,
+    outcome: &Outcome,
+) -> Result<(), Error> {
+    let name = |value: Option<clap::builder::PossibleValue>| {
+        value.map_or_else(String::new, |value| value.get_name().to_owned())
+    };
+    let output_sha256 =
+        cli::provenance::sha256(output).map_err(|error| Error::decode(output, error))?;
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let (date, time) = cli::time::format_utc(now.as_secs());
+    let detection = &args.detection;
+    let provenance = cli::provenance::Provenance {
+        version: env!("CARGO_PKG_VERSION"),
+        cleaned_at: format!("{date}T{time}Z"),
+        input: cli::provenance::FileDigest {
+            path: input,
+            sha256: input_sha256,
+        },
+        output: cli::provenance::FileDigest {
+            path: output,
+            sha256: output_sha256,
+        },
+        settings: cli::provenance::Settings {
+            algorithm: name(detection.algorithm.to_possible_value()),
+            threshold: detection.threshold,
+            window: detection.window,
+            passes: detection.passes,
+            link_channels: args.link_channels,
+            select_channels: args.select_channels.clone(),
+            mono_mix: args.mono_mix.map(|point| name(point.to_possible_value())),
+            start: args.start,
+            end: args.end,
+            format: name(format.to_possible_value()),
+            bit_depth: args.bit_depth,
+            dither: args.dither.map(|dither| name(dither.to_possible_value())),
+            sample_rate: args.sample_rate,
+            normalize: args.normalize,
+        },
+        clicks: cli::labels::clicks(&outcome.repairs, outcome.spec.sample_rate).len(),
+        repaired_samples: outcome.repairs.len(),
+    };
+
+    let path = cli::provenance::path_for(output);
+    cli::provenance::write(&path, &provenance).map_err(|error| Error::write(&path, error))
+}
+
+/// Clean `input` into a temporary file next to it, and then replace it with that, keeping the
+/// original as a backup. `name` is where the backup goes in `--backup-dir`.
+fn clean_in_place(
+    args: &CleanArgs,
+    input: &Path,
+    name: &Path,
+    diff: Option<&Path>,
+    progress: &ProgressBar,
+) -> Result<Outcome, Error> {
+    if cli::is_stdio(input) {
+        return Err(Error::Usage(
+            "Standard input can't be cleaned in place".to_owned(),
+        ));
+    }
+    let format =
+        cli::in_place::format(input, args.format).map_err(|error| Error::decode(input, error))?;
+    let temp = cli::in_place::temp_path(input);
+    let backup = cli::in_place::backup_path(input, name, args.backup_dir.as_deref());
+    // Checked up front as well, so a long file isn't cleaned for nothing
+    if backup.exists() {
+        return Err(Error::Write {
+            path: input.to_owned(),
+            source: io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("the backup {} already exists", backup.display()),
+            ),
+        });
+    }
+
+    // A temporary file that's still around can only be left over from an interrupted run
+    let _ = fs::remove_file(&temp);
+    let outcome = clean_file(args, input, &temp, format, diff, progress).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })?;
+    cli::in_place::replace(input, &temp, &backup).map_err(|error| {
+        let _ = fs::remove_file(&temp);
+        Error::write(input, error)
+    })?;
+
+    Ok(outcome)
+}
+
+/// Clean new files in `dir` as they show up, until the process is stopped or the directory can't
+/// be read anymore.
+fn watch(args: &CleanArgs, dir: &Path) -> ExitCode {
+    let pool = match thread_pool(args.jobs) {
+        Ok(pool) => pool,
+        Err(error) => return report_error(&error),
+    };
+    let mut watcher = Watcher::new(dir, args.output(), args.format);
+    loop {
+        let jobs = match watcher.scan() {
+            Ok(jobs) => jobs,
+            Err(error) => return report_error(&Error::decode(dir, error)),
+        };
+
+        for job in jobs {
+            let progress = cli::progress::file_bar(args.quiet, job.name.display().to_string());
+            let result = pool.install(|| clean_job(args, &job, &progress));
+            progress.finish_and_clear();
+            match result {
+                Ok(outcome) => {
+                    log::info!(
+                        "{}: cleaned, {} samples repaired",
+                        job.name.display(),
+                        outcome.repairs.len()
+                    );
+                    if let Err(error) = cli::watch::mark_done(&job) {
+                        report_error(&Error::write(&cli::watch::marker_path(&job.input), error));
+                    }
+                }
+                // Files that failed are tried again once they change
+                Err(error) => {
+                    report_error(&error);
+                }
+            }
+        }
+
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Clean a file while it's being decoded, so only a small part of it is ever held in memory. Dry
+/// runs only decode the file and look for clicks. With `--normalize`, the file is cleaned once to
+/// find the peak, and again to write it out.
+fn clean_file(
+    args: &CleanArgs,
+    input: &Path,
+    output: &Path,
+    format: OutputFormat,
+    diff: Option<&Path>,
+    progress: &ProgressBar,
+) -> Result<Outcome, Error> {
+    if args.snippets.is_some() && cli::is_stdio(input) {
+        return Err(Error::Usage(
+            "--snippets reads the input again after cleaning, so it can't read standard input"
+                .to_owned(),
+        ));
+    }
+    if !args.dry_run {
+        for path in [Some(output), diff].into_iter().flatten() {
+            check_overwrite(args, path)?;
+        }
+    }
+
+    if args.dry_run {
+        return clean_stream(args, input, None, format, None, 1.0, progress);
+    }
+    let Some(level) = args.normalize else {
+        return clean_stream(args, input, Some(output), format, diff, 1.0, progress);
+    };
+    if cli::is_stdio(input) {
+        return Err(Error::Usage(
+            "--normalize reads the input twice, so it can't read standard input".to_owned(),
+        ));
+    }
+
+    let measured = clean_stream(args, input, None, format, None, 1.0, progress)?;
+    let gain = cli::normalize::gain(measured.peak, measured.spec.bits_per_sample, level);
+    progress.suspend(|| {
+        log::debug!(
+            "{}: normalizing to {level} dBFS with a gain of {:.2} dB",
+            input.display(),
+            20.0 * gain.log10()
+        )
+    });
+    progress.set_position(0);
+
+    clean_stream(args, input, Some(output), format, diff, gain, progress)
+}
+
+/// The metadata to carry over from `input`. WAV output gets a `bext` chunk if the input has one or
+/// `--bwf` asks for it, with the cleaning added to its coding history.
+fn read_metadata(
+    args: &CleanArgs,
+    input: &Path,
+    format: OutputFormat,
+    spec: Spec,
+    output_spec: Spec,
+    progress: &ProgressBar,
+) -> io::Result<Metadata> {
+    let mut flac_blocks = cli::metadata::read_flac_metadata(input)?;
+    if output_spec.sample_rate != spec.sample_rate {
+        cli::metadata::resample_blocks(&mut flac_blocks, spec.sample_rate, output_spec.sample_rate);
+    }
+    let mut bext = match format {
+        OutputFormat::Flac => None,
+        OutputFormat::Wav | OutputFormat::FloatWav => cli::bwf::read_bext(input)?,
+    };
+    if let Some(bext) = &bext {
+        progress.suspend(|| {
+            log::debug!(
+                "{}: originated {} {} by {}, time reference {}",
+                input.display(),
+                bext.origination_date,
+                bext.origination_time,
+                bext.originator,
+                bext.time_reference
+            )
+        });
+    } else if args.bwf && format != OutputFormat::Flac {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        let (date, time) = cli::time::format_utc(now.as_secs());
+        bext = Some(cli::bwf::Bext::new(date, time));
+    }
+
+    if let Some(bext) = &mut bext {
+        let detection = &args.detection;
+        let algorithm = detection
+            .algorithm
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_owned());
+        // Commas separate the fields of a coding history line, so the settings can't use them
+        let mut process = format!(
+            "depop {}; {algorithm}; threshold {}; window {}; passes {}",
+            env!("CARGO_PKG_VERSION"),
+            detection.threshold,
+            detection.window,
+            detection.passes
+        );
+        if args.link_channels {
+            process.push_str("; linked channels");
+        }
+        bext.add_step(
+            spec.sample_rate,
+            output_spec,
+            format == OutputFormat::FloatWav,
+            &process,
+        );
+    }
+
+    Ok(Metadata { flac_blocks, bext })
+}
+
+/// Clean a file into `output` with `gain` applied, or only look for clicks without an output.
+fn clean_stream(
+    args: &CleanArgs,
+    input: &Path,
+    output: Option<&Path>,
+    format: OutputFormat,
+    diff: Option<&Path>,
+    gain: f64,
+    progress: &ProgressBar,
+) -> Result<Outcome, Error> {
+    let read_error = |error| Error::decode(input, error);
+    let write_error = |error| Error::write(output.unwrap_or(input), error);
+    let diff_error = |error| Error::write(diff.unwrap_or(input), error);
+    // A pass that only measures the peak would repeat the messages of the pass after it
+    let measuring = output.is_none() && !args.dry_run;
+
+    let started = Instant::now();
+    let mut decoder = args.open(input).map_err(read_error)?;
+    let spec = decoder.spec();
+    if !measuring {
+        progress.suspend(|| {
+            log::debug!(
+                "{}: {} channels, {} Hz, {} bits, {} samples",
+                input.display(),
+                spec.num_channels,
+                spec.sample_rate,
+                spec.bits_per_sample,
+                decoder.num_frames().map_or_else(
+                    || "unknown number of".to_owned(),
+                    |frames| frames.to_string()
+                )
+            )
+        });
+    }
+    if let Some(num_frames) = decoder.num_frames() {
+        progress.set_length(num_frames);
+    }
+
+    // Floating point samples are converted from the input's own depth
+    let bits_per_sample = match format {
+        OutputFormat::FloatWav => spec.bits_per_sample,
+        _ => args.bit_depth.unwrap_or(spec.bits_per_sample),
+    };
+    let output_spec = Spec {
+        sample_rate: args.sample_rate.unwrap_or(spec.sample_rate),
+        bits_per_sample,
+        num_channels: match args.mono_mix {
+            Some(MixPoint::After) => 1,
+            _ => spec.num_channels,
+        },
+    };
+    let (mut output, metadata) = match output {
+        Some(output) => (
+            Some(Output::create(output).map_err(write_error)?),
+            read_metadata(args, input, format, spec, output_spec, progress).map_err(read_error)?,
+        ),
+        None => (None, Metadata::default()),
+    };
+    let mut encoder = output
+        .as_mut()
+        .map(|output| {
+            Encoder::new(
+                output,
+                format,
+                output_spec,
+                args.compression_level,
+                &metadata,
+            )
+        })
+        .transpose()
+        .map_err(write_error)?;
+    let mut converter = Converter::new(spec, output_spec)
+        .with_gain(gain)
+        .with_dither(args.dither);
+    // The input is measured as it was decoded, and the output as it's written
+    let mut meters = (args.loudness && !measuring).then(|| {
+        (
+            LoudnessMeter::new(spec.num_channels, spec.sample_rate, spec.bits_per_sample),
+            LoudnessMeter::new(
+                output_spec.num_channels,
+                output_spec.sample_rate,
+                output_spec.bits_per_sample,
+            ),
+        )
+    });
+
+    // The difference keeps the input's format, as floating point so it can hold pops that went
+    // past full scale
+    let mut diff_output = diff.map(Output::create).transpose().map_err(diff_error)?;
+    let mut diff_encoder = diff_output
+        .as_mut()
+        .map(|output| {
+            Encoder::new(
+                output,
+                OutputFormat::FloatWav,
+                spec,
+                0,
+                &Metadata::default(),
+            )
+        })
+        .transpose()
+        .map_err(diff_error)?;
+    let mut diff_position = 0;
+    let mut write_diff = |repairs: &[Repair], len: usize| -> io::Result<()> {
+        if let Some(encoder) = &mut diff_encoder {
+            let channels = difference(repairs, diff_position, len, spec.num_channels);
+            encoder.write(&channels)?;
+        }
+        diff_position += len as u64;
+        Ok(())
+    };
+
+    // Pops are found in each channel on its own unless the channels are linked
+    let config = args.detection.config();
+    let range = args.range(spec.sample_rate);
+    let mut cleaners: Vec<StreamCleaner> = if args.link_channels {
+        vec![StreamCleaner::linked(spec.num_channels, config).with_range(range)]
+    } else {
+        (0..spec.num_channels)
+            .map(|channel| StreamCleaner::new(channel, config).with_range(range.clone()))
+            .collect()
+    };
+    let mut repairs = Vec::new();
+    let mut input_peak = 0;
+    let mut peak = 0;
+    let mut decoded_frames = 0;
+    // The blocks are split up between the threads by the cleaners, and need to be long enough
+    // for that to pay off
+    while let Some(chunk) = decoder.next_block(BLOCK_LEN).map_err(read_error)? {
+        let num_repairs = repairs.len();
+        let (cleaned, channel_repairs): (Vec<Vec<Vec<i32>>>, Vec<Vec<Repair>>) = cleaners
+            .par_iter_mut()
+            .map(|cleaner| {
+                let mut repairs = Vec::new();
+                let cleaned = cleaner.push(&chunk[cleaner.channels()], &mut repairs);
+                (cleaned, repairs)
+            })
+            .unzip();
+        let cleaned: Vec<Vec<i32>> = cleaned.into_iter().flatten().collect();
+        repairs.extend(channel_repairs.into_iter().flatten());
+        input_peak = input_peak.max(channels_peak(&chunk));
+        peak = peak.max(channels_peak(&cleaned));
+        write_diff(&repairs[num_repairs..], cleaned.first().map_or(0, Vec::len))
+            .map_err(diff_error)?;
+        if let Some((input_meter, _)) = &mut meters {
+            input_meter.push(&chunk);
+        }
+        if encoder.is_some() || meters.is_some() {
+            let converted = converter.push(cleaned);
+            if let Some((_, output_meter)) = &mut meters {
+                output_meter.push(&converted);
+            }
+            if let Some(encoder) = &mut encoder {
+                encoder.write(&converted).map_err(write_error)?;
+            }
+        }
+        let len = chunk.first().map_or(0, Vec::len) as u64;
+        decoded_frames += len;
+        progress.inc(len);
+    }
+
+    // Leaving out audio that couldn't be decoded would shift everything after it, so the output
+    // wouldn't line up with the input anymore. Some damage is only noticed as missing samples,
+    // when the decoder skips ahead to the next intact frame.
+    let damage = if decoder.damaged_packets() > 0 {
+        Some(format!(
+            "{} packets could not be decoded",
+            decoder.damaged_packets()
+        ))
+    } else {
+        decoder
+            .num_frames()
+            .filter(|&expected| decoded_frames < expected)
+            .map(|expected| {
+                format!(
+                    "the file says it has {expected} samples, but {decoded_frames} could be \
+                     decoded"
+                )
+            })
+    };
+    if let Some(damage) = damage {
+        return Err(read_error(io::Error::new(
+            io::ErrorKind::InvalidData,
+            damage,
+        )));
+    }
+
+    // The signature was made from the audio as it was encoded, so a mismatch means the file was
+    // already damaged before it got here, and not by the cleaning
+    if !measuring && decoder.md5_matches() == Some(false) {
+        progress.suspend(|| {
+            log::warn!(
+                "{}: the decoded audio does not match the file's MD5 signature, so the file itself \
+                 is damaged",
+                input.display()
+            )
+        });
+    }
+
+    let num_repairs = repairs.len();
+    let rest: Vec<Vec<i32>> = cleaners
+        .into_iter()
+        .flat_map(|cleaner| cleaner.finish(&mut repairs))
+        .collect();
+    peak = peak.max(channels_peak(&rest));
+    write_diff(&repairs[num_repairs..], rest.first().map_or(0, Vec::len)).map_err(diff_error)?;
+    diff_encoder
+        .map(Encoder::finish)
+        .transpose()
+        .map_err(diff_error)?;
+    diff_output
+        .map(Output::finish)
+        .transpose()
+        .map_err(diff_error)?;
+    if encoder.is_some() || meters.is_some() {
+        for converted in [converter.push(rest), converter.finish()] {
+            if let Some((_, output_meter)) = &mut meters {
+                output_meter.push(&converted);
+            }
+            if let Some(encoder) = &mut encoder {
+                encoder.write(&converted).map_err(write_error)?;
+            }
+        }
+    }
+    encoder
+        .map(Encoder::finish)
+        .transpose()
+        .map_err(write_error)?;
+    output
+        .map(Output::finish)
+        .transpose()
+        .map_err(write_error)?;
+
+    repairs.sort_by_key(|repair| (repair.position, repair.channel));
+    let loudness = meters.map(|(input_meter, output_meter)| LoudnessComparison {
+        input: input_meter.finish(),
+        output: output_meter.finish(),
+    });
+    if !measuring {
+        let summary = Summary::new(
+            spec,
+            &repairs,
+            diff_position,
+            input_peak,
+            peak as f64 * gain,
+            started.elapsed(),
+        );
+        log_statistics(input, spec, &repairs, &summary);
+    }
+    if let Some(loudness) = &loudness {
+        log::info!(
+            "{}: before {}; after {}",
+            input.display(),
+            format_loudness(&loudness.input),
+            format_loudness(&loudness.output)
+        );
+    }
+    Ok(Outcome {
+        spec,
+        repairs,
+        peak,
+        loudness,
+    })
+}
+
+/// Describe a loudness measurement in a line.
+fn format_loudness(loudness: &Loudness) -> String {
+    let value = |value: Option<f64>, unit| {
+        value.map_or_else(
+            || format!("silent {unit}"),
+            |value| format!("{value:.1} {unit}"),
+        )
+    };
+    format!(
+        "{} integrated, {} short-term max, {}",
+        value(loudness.integrated, "LUFS"),
+        value(loudness.max_short_term, "LUFS"),
+        value(loudness.true_peak, "dBTP")
+    )
+}
+
+/// The largest absolute value of any of the samples.
+fn channels_peak(channels: &[Vec<i32>]) -> u32 {
+    channels
+        .iter()
+        .flatten()
+        .map(|sample| sample.unsigned_abs())
+        .max()
+        .unwrap_or(0)
+}
+
+fn plot(args: &PlotArgs) -> ExitCode {
+    let result = cli::plot::plot_region(
+        &args.input,
+        args.channel as usize - 1,
+        args.start,
+        args.length as usize,
+        &args.detection.config(),
+        &args.output,
+    );
+    match result {
+        Ok((before, after)) => {
+            println!("{}\n{}", before.display(), after.display());
+            ExitCode::SUCCESS
+        }
+        Err(error) => report_error(&Error::decode(&args.input, error)),
+    }
+}
+
+fn dump(args: &DumpArgs) -> ExitCode {
+    let write_error = |error| Error::write(&args.output, error);
+    let result = Output::create(&args.output)
+        .map_err(write_error)
+        .and_then(|mut output| {
+            cli::dump::dump_region(
+                &args.input,
+                args.start,
+                args.length as usize,
+                &args.detection.config(),
+                &mut output,
+            )
+            .map_err(|error| Error::decode(&args.input, error))?;
+            output.finish().map_err(write_error)
+        });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report_error(&error),
+    }
+}
+
+fn inject(args: &InjectArgs) -> ExitCode {
+    let truth = args
+        .truth
+        .clone()
+        .unwrap_or_else(|| args.output.with_extension("json"));
+    let result = inject_pops(args).and_then(|outcome| {
+        cli::report::write(&truth, &[(args.input.clone(), outcome)])
+            .map_err(|error| Error::write(&truth, error))
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report_error(&error),
+    }
+}
+
+/// Write `args.input` with the pops added to `args.output`. The outcome lists the samples that
+/// were changed as its repairs.
+fn inject_pops(args: &InjectArgs) -> Result<Outcome, Error> {
+    let read_error = |error| Error::decode(&args.input, error);
+    let write_error = |error| Error::write(&args.output, error);
+
+    let mut decoder = Decoder::open(&args.input).map_err(read_error)?;
+    let spec = decoder.spec();
+    let num_frames = decoder.num_frames().ok_or_else(|| {
+        read_error(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the file doesn't say how long it is, so the pops can't be spread over it",
+        ))
+    })?;
+    let config = InjectConfig {
+        count: args.count,
+        amplitude: 10f64.powf(args.amplitude / 20.0),
+        width: args.width as usize,
+        seed: args.seed,
+    };
+    let pops = cli::inject::plan_pops(num_frames, spec.num_channels, spec.bits_per_sample, &config);
+    if pops.len() < args.count {
+        log::warn!(
+            "{}: only {} of the {} pops fit into the file",
+            args.input.display(),
+            pops.len(),
+            args.count
+        );
+    }
+
+    let mut output = Output::create(&args.output).map_err(write_error)?;
+    let mut encoder = Encoder::new(
+        &mut output,
+        OutputFormat::from_path(&args.output),
+        spec,
+        DEFAULT_COMPRESSION_LEVEL,
+        &Metadata::default(),
+    )
+    .map_err(write_error)?;
+    let mut changes = Vec::new();
+    let mut peak = 0;
+    let mut position = 0;
+    while let Some(mut chunk) = decoder.next_chunk().map_err(read_error)? {
+        cli::inject::apply_pops(
+            &pops,
+            position,
+            &mut chunk,
+            spec.bits_per_sample,
+            &mut changes,
+        );
+        peak = peak.max(channels_peak(&chunk));
+        position += chunk.first().map_or(0, Vec::len) as u64;
+        encoder.write(&chunk).map_err(write_error)?;
+    }
+    encoder.finish().map_err(write_error)?;
+    output.finish().map_err(write_error)?;
+
+    changes.sort_by_key(|change| (change.position, change.channel));
+    Ok(Outcome {
+        spec,
+        repairs: changes,
+        peak,
+        loudness: None,
+    })
+}
+
+fn bench(args: &BenchArgs) -> ExitCode {
+    let (spec, channels) = match &args.input {
+        Some(input) => match read_start(input, args.seconds) {
+            Ok(audio) => audio,
+            Err(error) => return report_error(&error),
+        },
+        None => {
+            let spec = Spec {
+                sample_rate: 48_000,
+                bits_per_sample: 24,
+                num_channels: 2,
             };
+            let len = args.seconds as usize * spec.sample_rate as usize;
+            let channels = (0..spec.num_channels as u64)
+                .map(|seed| cli::bench::noise(len, spec.bits_per_sample, seed))
+                .collect();
+            (spec, channels)
+        }
+    };
+    let pool = match thread_pool(args.jobs) {
+        Ok(pool) => pool,
+        Err(error) => return report_error(&error),
+    };
+
+    let algorithms = if args.algorithm.is_empty() {
+        AlgorithmArg::value_variants().to_vec()
+    } else {
+        args.algorithm.clone()
+    };
+    let len = channels.first().map_or(0, Vec::len);
+    println!(
+        "{:.1} s of {} channel {} Hz audio, {} threads, best of {} runs:",
+        len as f64 / spec.sample_rate as f64,
+        spec.num_channels,
+        spec.sample_rate,
+        pool.current_num_threads(),
+        args.runs
+    );
+    println!(
+        "{:<12} {:>10} {:>14} {:>10}",
+        "algorithm", "time", "samples/s", "realtime"
+    );
+    for algorithm in algorithms {
+        let config = CleanConfig {
+            algorithm: algorithm.into(),
+            threshold: args.threshold,
+            radius: args.window / 2,
+            passes: args.passes as usize,
+        };
+        let measurement = pool.install(|| {
+            cli::bench::measure(&channels, spec.sample_rate, &config, args.runs as usize)
+        });
+        let name = algorithm
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_owned());
+        println!(
+            "{:<12} {:>8.3} s {:>12.1} M {:>9.0}x",
+            name,
+            measurement.best.as_secs_f64(),
+            measurement.samples_per_second / 1e6,
+            measurement.realtime_factor
+        );
+    }
+
+    ExitCode::SUCCESS
+}
 
-            let all_data: Vec<i32> = stream.iter::<i32>().collect();
-            let cleaned = clean_data(&all_data);
+fn simulate(args: &SimulateArgs) -> ExitCode {
+    let (spec, original) = match read_start(&args.input, args.seconds) {
+        Ok(audio) => audio,
+        Err(error) => return report_error(&error),
+    };
+    let pool = match thread_pool(args.jobs) {
+        Ok(pool) => pool,
+        Err(error) => return report_error(&error),
+    };
+
+    let len = original.first().map_or(0, Vec::len);
+    let config = InjectConfig {
+        count: args.count,
+        amplitude: 10f64.powf(args.amplitude / 20.0),
+        width: args.width as usize,
+        seed: args.seed,
+    };
+    let pops = cli::inject::plan_pops(len as u64, spec.num_channels, spec.bits_per_sample, &config);
+    let mut corrupted = original.clone();
+    cli::inject::apply_pops(
+        &pops,
+        0,
+        &mut corrupted,
+        spec.bits_per_sample,
+        &mut Vec::new(),
+    );
+
+    let algorithms = if args.algorithm.is_empty() {
+        AlgorithmArg::value_variants().to_vec()
+    } else {
+        args.algorithm.clone()
+    };
+    println!(
+        "{:.1} s of {} channel {} Hz audio with {} pops of {} samples at {:.1} dBFS:",
+        len as f64 / spec.sample_rate as f64,
+        spec.num_channels,
+        spec.sample_rate,
+        pops.len(),
+        args.width,
+        args.amplitude
+    );
+    println!(
+        "{:<12} {:>10} {:>10} {:>16} {:>16}",
+        "algorithm", "precision", "recall", "pop error", "collateral"
+    );
+    let dbfs = |level: f64| {
+        if level > 0.0 {
+            format!("{:.1} dBFS", 20.0 * level.log10())
+        } else {
+            "none".to_owned()
+        }
+    };
+    let mut exit_code = ExitCode::SUCCESS;
+    for algorithm in algorithms {
+        let config = CleanConfig {
+            algorithm: algorithm.into(),
+            threshold: args.threshold,
+            radius: args.window / 2,
+            passes: args.passes as usize,
+        };
+        let cleaned: Vec<Vec<i32>> = pool.install(|| {
+            corrupted
+                .iter()
+                .map(|samples| {
+                    cli::clean::clean_in_parallel(samples, &config, cli::clean::SEGMENT_LEN)
+                })
+                .collect()
+        });
+        let score =
+            cli::simulate::score(&original, &corrupted, &cleaned, &pops, spec.bits_per_sample);
+        let name = algorithm
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_owned());
+        println!(
+            "{:<12} {:>8.1} % {:>8.1} % {:>16} {:>16}",
+            name,
+            score.precision() * 100.0,
+            score.recall() * 100.0,
+            dbfs(score.pop_error),
+            dbfs(score.collateral_error)
+        );
 
-            let mut writer = hound::WavWriter::create(args.output, spec).unwrap();
-            for sample in cleaned {
-                writer
-                    .write_sample(sample)
-                    .expect("Should be able to write sample!");
+        if args.min_recall.is_some_and(|min| score.recall() < min) {
+            log::error!("{name} found fewer of the pops than --min-recall");
+            exit_code = ExitCode::FAILURE;
+        }
+        if args
+            .min_precision
+            .is_some_and(|min| score.precision() < min)
+        {
+            log::error!("{name} made more repairs outside the pops than --min-precision allows");
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+
+    exit_code
+}
+
+/// Clean a sound card's input live, or list the devices.
+#[cfg(feature = "live")]
+fn live(args: &LiveArgs) -> ExitCode {
+    match clean_live(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => report_error(&error),
+    }
+}
+
+/// Clean the audio from the input device onto the output device until `--duration` is up, and
+/// log the clicks as they're repaired.
+#[cfg(feature = "live")]
+fn clean_live(args: &LiveArgs) -> Result<(), Error> {
+    if args.list_devices {
+        let (inputs, outputs) = cli::live::device_names().map_err(Error::Other)?;
+        println!("Input devices:");
+        for name in inputs {
+            println!("  {name}");
+        }
+        println!("Output devices:");
+        for name in outputs {
+            println!("  {name}");
+        }
+        return Ok(());
+    }
+
+    // Either way, other devices need to be picked
+    let device_error = |error: io::Error| match error.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::Unsupported => Error::Usage(error.to_string()),
+        _ => Error::Other(error),
+    };
+    let input = cli::live::input_device(args.input_device.as_deref()).map_err(device_error)?;
+    let output = cli::live::output_device(args.output_device.as_deref()).map_err(device_error)?;
+    let live = Live::start(&input, &output).map_err(device_error)?;
+    let spec = live.spec();
+    log::info!(
+        "Cleaning {} channels at {} Hz, press Ctrl+C to stop",
+        spec.num_channels,
+        spec.sample_rate
+    );
+
+    let config = args.detection.config();
+    let mut cleaners: Vec<StreamCleaner> = if args.link_channels {
+        vec![StreamCleaner::linked(spec.num_channels, config)]
+    } else {
+        (0..spec.num_channels)
+            .map(|channel| StreamCleaner::new(channel, config))
+            .collect()
+    };
+    let end = args
+        .duration
+        .map(|seconds| (seconds * spec.sample_rate as f64).round() as u64);
+    let mut position = 0;
+    let mut num_clicks = 0;
+    while let Some(chunk) = live.next_chunk() {
+        let mut repairs = Vec::new();
+        let cleaned: Vec<Vec<i32>> = cleaners
+            .iter_mut()
+            .flat_map(|cleaner| cleaner.push(&chunk[cleaner.channels()], &mut repairs))
+            .collect();
+        repairs.sort_by_key(|repair| repair.position);
+        for click in cli::labels::clicks(&repairs, spec.sample_rate) {
+            log::info!(
+                "{} click in channel {}",
+                format_timestamp(click[0].position as f64 / spec.sample_rate as f64),
+                click[0].channel + 1
+            );
+            num_clicks += 1;
+        }
+        live.play(&cleaned);
+
+        position += chunk.first().map_or(0, Vec::len) as u64;
+        if end.is_some_and(|end| position >= end) {
+            break;
+        }
+    }
+
+    log::info!(
+        "{num_clicks} clicks repaired in {} of audio",
+        format_timestamp(position as f64 / spec.sample_rate as f64)
+    );
+    Ok(())
+}
+
+/// Decode up to the first `seconds` of `input` into memory.
+fn read_start(input: &Path, seconds: u32) -> Result<(Spec, Vec<Vec<i32>>), Error> {
+    let mut decoder = Decoder::open(input).map_err(|error| Error::decode(input, error))?;
+    let spec = decoder.spec();
+    let len = seconds as usize * spec.sample_rate as usize;
+    let mut channels = vec![Vec::new(); spec.num_channels];
+    while channels[0].len() < len {
+        let Some(chunk) = decoder
+            .next_chunk()
+            .map_err(|error| Error::decode(input, error))?
+        else {
+            break;
+        };
+        for (channel, samples) in channels.iter_mut().zip(chunk) {
+            channel.extend(samples);
+        }
+    }
+    for channel in &mut channels {
+        channel.truncate(len);
+    }
+
+    Ok((spec, channels))
+}
+
+/// Check every file. Damaged files exit with the same code as files that fail to decode while
+/// cleaning them.
+fn verify(args: &VerifyArgs) -> ExitCode {
+    let mut exit_code = ExitCode::SUCCESS;
+    for file in &args.files {
+        match cli::verify::verify(file) {
+            Ok(verification) => {
+                let problems = verification.problems();
+                if problems.is_empty() {
+                    println!(
+                        "{}: OK, {} samples of {} channel {} Hz {}-bit audio",
+                        file.display(),
+                        verification.decoded_frames,
+                        verification.spec.num_channels,
+                        verification.spec.sample_rate,
+                        verification.spec.bits_per_sample
+                    );
+                } else {
+                    for problem in problems {
+                        let error = Error::Decode {
+                            path: file.clone(),
+                            source: io::Error::new(io::ErrorKind::InvalidData, problem),
+                        };
+                        exit_code = report_error(&error);
+                    }
+                }
             }
+            Err(error) => exit_code = report_error(&Error::decode(file, error)),
+        }
+    }
+
+    exit_code
+}
+
+fn compare(args: &CompareArgs) -> ExitCode {
+    let comparison = match cli::compare::compare(&args.a, &args.b, args.max_offset) {
+        Ok(comparison) => comparison,
+        // Either file could be the one that's broken
+        Err(error) => {
+            return report_error(&Error::Decode {
+                path: PathBuf::from(format!("{} and {}", args.a.display(), args.b.display())),
+                source: error,
+            })
+        }
+    };
+
+    let spec = comparison.spec;
+    let seconds = |samples: u64| format_timestamp(samples as f64 / spec.sample_rate as f64);
+    let dbfs = |level: f64| 20.0 * level.log10();
+    match comparison.offset {
+        0 => println!("Offset: none"),
+        offset => println!(
+            "Offset: {} starts {} samples {} than {}",
+            args.b.display(),
+            offset.abs(),
+            if offset > 0 { "later" } else { "earlier" },
+            args.a.display()
+        ),
+    }
+    println!(
+        "Compared: {} samples per channel ({})",
+        comparison.compared,
+        seconds(comparison.compared)
+    );
+    if comparison.unmatched != (0, 0) {
+        println!(
+            "Unmatched: {} samples in {}, {} in {}",
+            comparison.unmatched.0,
+            args.a.display(),
+            comparison.unmatched.1,
+            args.b.display()
+        );
+    }
+    println!("Differing samples: {}", comparison.differing);
+    if comparison.differing > 0 {
+        let (position, channel) = comparison.max_difference_at;
+        println!(
+            "Max difference: {:.1} dBFS at {} (sample {position}) in channel {}",
+            dbfs(comparison.max_difference),
+            seconds(position),
+            channel + 1
+        );
+        println!(
+            "RMS difference: {:.1} dBFS",
+            dbfs(comparison.rms_difference)
+        );
+        println!("SNR: {:.1} dB", comparison.snr);
+    } else {
+        println!("The files are identical");
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Write the `--report`, if one was asked for.
+fn write_report(args: &CleanArgs, outcomes: &[(PathBuf, Outcome)]) -> ExitCode {
+    if let Some(path) = &args.report {
+        if let Err(error) = cli::report::write(path, outcomes) {
+            return report_error(&Error::write(path, error));
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Show the summary of a file, with the repairs per channel for `-v` and every repair for `-vv`.
+fn log_statistics(input: &Path, spec: Spec, repairs: &[Repair], summary: &Summary) {
+    log::info!("{}: {summary}", input.display());
+    if log::log_enabled!(log::Level::Debug) {
+        let mut per_channel = vec![0; spec.num_channels];
+        for repair in repairs {
+            per_channel[repair.channel] += 1;
         }
-        Err(error) => println!("{:?}", error),
+        log::debug!(
+            "{}: {} samples repaired per channel, {:.0}x real time",
+            input.display(),
+            per_channel
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join("/"),
+            summary.duration / summary.elapsed.as_secs_f64().max(1e-9)
+        );
+    }
+    for repair in repairs {
+        log::trace!(
+            "{}: {} (sample {}) in channel {}: {} -> {}",
+            input.display(),
+            format_timestamp(repair.position as f64 / spec.sample_rate as f64),
+            repair.position,
+            repair.channel + 1,
+            repair.original,
+            repair.replacement
+        );
+    }
+}
+
+/// List the repairs found by `analyze`.
+fn print_repairs(name: &Path, outcome: &Outcome) {
+    println!(
+        "{}: {} samples would be repaired",
+        name.display(),
+        outcome.repairs.len()
+    );
+    for repair in &outcome.repairs {
+        println!(
+            "  {} (sample {}) in channel {}",
+            format_timestamp(repair.position as f64 / outcome.spec.sample_rate as f64),
+            repair.position,
+            repair.channel + 1
+        );
     }
 }
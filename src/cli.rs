@@ -0,0 +1,60 @@
+//! The parts of the `depop` command line tool that don't deal with its arguments.
+
+use std::io;
+use std::path::Path;
+
+pub mod batch;
+pub mod bench;
+pub mod bit_depth;
+pub mod bwf;
+pub mod channels;
+pub mod clean;
+pub mod compare;
+pub mod convert;
+pub mod decode;
+pub mod dump;
+pub mod encode;
+pub mod error;
+pub mod flac_encoder;
+pub mod in_place;
+pub mod inject;
+pub mod labels;
+#[cfg(feature = "live")]
+pub mod live;
+pub mod logging;
+pub mod loudness;
+pub mod metadata;
+pub mod normalize;
+pub mod plot;
+pub mod profile;
+pub mod progress;
+pub mod provenance;
+pub mod raw;
+pub mod report;
+pub mod resample;
+pub mod simulate;
+pub mod snippets;
+pub mod summary;
+pub mod template;
+pub mod time;
+pub mod verify;
+pub mod watch;
+pub mod wav_writer;
+
+/// Whether `path` stands for standard input or output.
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Whether `a` and `b` are the same file, even when they're written differently, like `x.wav` and
+/// `./x.wav`. Paths that don't exist are never the same file.
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
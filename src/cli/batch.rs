@@ -0,0 +1,223 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::encode::OutputFormat;
+
+/// The extensions of the files that are picked up when cleaning a whole directory.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "flac", "wav", "wave", "aif", "aiff", "aifc", "mp3", "ogg", "oga", "m4a",
+];
+
+/// A single file to clean as part of a batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub input: PathBuf,
+    /// How the file is referred to in messages. For files found in a directory, this is their
+    /// path relative to that directory.
+    pub name: PathBuf,
+    pub output: PathBuf,
+    pub format: OutputFormat,
+}
+
+/// Whether `path` has the extension of a format the command line tool can read.
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            SUPPORTED_EXTENSIONS
+                .iter()
+                .any(|supported| extension.eq_ignore_ascii_case(supported))
+        })
+}
+
+/// Whether `input` is a glob pattern like `captures/*.flac` rather than a path. Paths that exist
+/// are never patterns, even if they look like one.
+pub fn is_glob(input: &Path) -> bool {
+    !input.exists() && input.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// The paths matching `input` if it's a glob pattern, for shells that don't expand them, in
+/// alphabetical order. Other paths are returned as they are.
+pub fn expand_glob(input: &Path) -> io::Result<Vec<PathBuf>> {
+    if !is_glob(input) {
+        return Ok(vec![input.to_owned()]);
+    }
+
+    let pattern = input.to_string_lossy();
+    let paths = glob::glob(&pattern)
+        .map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not a valid pattern: {error}"),
+            )
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io::Error::from)?;
+    if paths.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no files match the pattern",
+        ));
+    }
+
+    Ok(paths)
+}
+
+/// Clean a single file into `output_dir`, keeping its name. Without a `format`, FLAC files stay
+/// FLAC and everything else becomes WAV.
+pub fn file_job(input: &Path, output_dir: &Path, format: Option<OutputFormat>) -> Job {
+    let format = format.unwrap_or_else(|| OutputFormat::from_path(input));
+    let file_name = input.file_name().unwrap_or(input.as_os_str());
+
+    Job {
+        input: input.to_owned(),
+        name: input.to_owned(),
+        output: output_dir
+            .join(file_name)
+            .with_extension(format.extension()),
+        format,
+    }
+}
+
+/// Find every supported file under `input_dir`, and mirror the directory structure under
+/// `output_dir`. Without a `format`, FLAC files stay FLAC and everything else becomes WAV. The
+/// jobs are sorted by their input path.
+pub fn collect_jobs(
+    input_dir: &Path,
+    output_dir: &Path,
+    format: Option<OutputFormat>,
+) -> io::Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    for entry in WalkDir::new(input_dir).sort_by_file_name() {
+        let entry = entry.map_err(io::Error::from)?;
+        if !entry.file_type().is_file() || !is_supported(entry.path()) {
+            continue;
+        }
+
+        let input = entry.into_path();
+        let format = format.unwrap_or_else(|| OutputFormat::from_path(&input));
+        let name = input
+            .strip_prefix(input_dir)
+            .expect("walkdir only yields paths under its root")
+            .to_owned();
+        let output = output_dir.join(&name).with_extension(format.extension());
+
+        jobs.push(Job {
+            input,
+            name,
+            output,
+            format,
+        });
+    }
+
+    Ok(jobs)
+}
+
+impl Job {
+    /// Whether the job was already done by an earlier run: its output exists and was written after
+    /// the input last changed. A file that is its own output never counts as done.
+    pub fn is_done(&self) -> bool {
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+        match (modified(&self.input), modified(&self.output)) {
+            (Ok(input), Ok(output)) => {
+                output >= input && !super::is_same_file(&self.input, &self.output)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn mirrors_the_directory_structure() {
+        let root = std::env::temp_dir().join(format!("depop-batch-{}", std::process::id()));
+        let input_dir = root.join("in");
+        fs::create_dir_all(input_dir.join("side a")).unwrap();
+        for file in ["side a/track 1.flac", "side a/notes.txt", "track 2.MP3"] {
+            fs::write(input_dir.join(file), b"").unwrap();
+        }
+
+        let output_dir = root.join("out");
+        let jobs = collect_jobs(&input_dir, &output_dir, None).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            jobs,
+            [
+                Job {
+                    input: input_dir.join("side a/track 1.flac"),
+                    name: PathBuf::from("side a/track 1.flac"),
+                    output: output_dir.join("side a/track 1.flac"),
+                    format: OutputFormat::Flac,
+                },
+                Job {
+                    input: input_dir.join("track 2.MP3"),
+                    name: PathBuf::from("track 2.MP3"),
+                    output: output_dir.join("track 2.wav"),
+                    format: OutputFormat::Wav,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_globs() {
+        let root = std::env::temp_dir().join(format!("depop-glob-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        for file in ["b.flac", "a.flac", "c.wav"] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+
+        let flac = expand_glob(&root.join("*.flac"));
+        let wave = expand_glob(&root.join("*.wave"));
+        let plain = expand_glob(&root.join("c.wav"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(flac.unwrap(), [root.join("a.flac"), root.join("b.flac")]);
+        assert_eq!(wave.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(plain.unwrap(), [root.join("c.wav")]);
+    }
+
+    #[test]
+    fn outputs_newer_than_their_inputs_are_done() {
+        let root = std::env::temp_dir().join(format!("depop-done-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let job = file_job(&root.join("in.flac"), &root.join("out"), None);
+        fs::write(&job.input, b"").unwrap();
+        let not_cleaned = job.is_done();
+
+        fs::create_dir_all(root.join("out")).unwrap();
+        let output = fs::File::create(&job.output).unwrap();
+        let now = SystemTime::now();
+        output.set_modified(now).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&job.input)
+            .unwrap()
+            .set_modified(now - Duration::from_secs(60))
+            .unwrap();
+        let cleaned = job.is_done();
+
+        // The input was changed after it was cleaned
+        output.set_modified(now - Duration::from_secs(120)).unwrap();
+        let outdated = job.is_done();
+
+        let into_itself = Job {
+            output: root.join(".").join("in.flac"),
+            ..job.clone()
+        };
+        let into_itself = into_itself.is_done();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(!not_cleaned);
+        assert!(cleaned);
+        assert!(!outdated);
+        assert!(!into_itself);
+    }
+}
@@ -0,0 +1,76 @@
+//! Throughput measurements for the `bench` subcommand, to judge how long a batch will take before
+//! starting it.
+
+use robo_depop_plugin::CleanConfig;
+use std::time::{Duration, Instant};
+
+use super::clean::{clean_in_parallel, SEGMENT_LEN};
+use super::inject::SplitMix64;
+
+/// How fast one configuration cleaned the audio.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    /// The fastest of the runs.
+    pub best: Duration,
+    /// The number of samples cleaned per second, counting every channel.
+    pub samples_per_second: f64,
+    /// How many times faster than real time the audio was cleaned.
+    pub realtime_factor: f64,
+}
+
+/// White noise up to a quarter of full scale with the odd pop at half scale in it, so the cleaner
+/// has some repairs to make. The same seed gives the same noise.
+pub fn noise(len: usize, bits_per_sample: u16, seed: u64) -> Vec<i32> {
+    let mut rng = SplitMix64(seed);
+    let quarter_scale = 1u64 << (bits_per_sample - 3);
+    let pop = 1i32 << (bits_per_sample - 2);
+    (0..len)
+        .map(|_| {
+            if rng.below(10_000) == 0 {
+                pop
+            } else {
+                rng.below(2 * quarter_scale) as i32 - quarter_scale as i32
+            }
+        })
+        .collect()
+}
+
+/// Clean `channels` the way the command line tool does `runs` times, and time it.
+pub fn measure(
+    channels: &[Vec<i32>],
+    sample_rate: u32,
+    config: &CleanConfig,
+    runs: usize,
+) -> Measurement {
+    let mut best = Duration::MAX;
+    for _ in 0..runs.max(1) {
+        let started = Instant::now();
+        for samples in channels {
+            std::hint::black_box(clean_in_parallel(samples, config, SEGMENT_LEN));
+        }
+        best = best.min(started.elapsed());
+    }
+
+    let seconds = best.as_secs_f64().max(1e-9);
+    let len = channels.first().map_or(0, Vec::len);
+    Measurement {
+        best,
+        samples_per_second: (len * channels.len()) as f64 / seconds,
+        realtime_factor: len as f64 / sample_rate as f64 / seconds,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_stays_in_range() {
+        let samples = noise(100_000, 16, 1);
+        assert_eq!(samples, noise(100_000, 16, 1));
+        assert!(samples
+            .iter()
+            .all(|&sample| (-8192..=16_384).contains(&sample)));
+        assert!(samples.contains(&16_384));
+    }
+}
@@ -0,0 +1,117 @@
+use super::inject::SplitMix64;
+
+/// How the noise of reducing the bit depth is spread out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dither {
+    /// Triangular noise of up to one step of the new depth, which turns the distortion of
+    /// rounding quiet passages into a steady hiss
+    Tpdf,
+    /// TPDF dither with the rounding error fed back into the next sample, which moves the hiss up
+    /// towards the frequencies the ear is least sensitive to
+    Shaped,
+}
+
+/// Dithers one channel while reducing its bit depth.
+pub struct Ditherer {
+    dither: Dither,
+    rng: SplitMix64,
+    /// The rounding error of the previous sample, in steps of the new depth.
+    error: f64,
+}
+
+impl Ditherer {
+    /// Each channel gets its own `seed`, so their noise isn't correlated.
+    pub fn new(dither: Dither, seed: u64) -> Self {
+        Self {
+            dither,
+            rng: SplitMix64(seed),
+            error: 0.0,
+        }
+    }
+
+    /// Like [`rescale()`], but with dither added before rounding. Only going down in depth adds
+    /// dither.
+    pub fn rescale(&mut self, samples: &mut [i32], from_bits: u16, to_bits: u16) {
+        if to_bits >= from_bits {
+            rescale(samples, from_bits, to_bits);
+            return;
+        }
+
+        let step = (1u64 << (from_bits - to_bits)) as f64;
+        let max = ((1i64 << (to_bits - 1)) - 1) as f64;
+        let min = -(1i64 << (to_bits - 1)) as f64;
+        for sample in samples {
+            let value = match self.dither {
+                Dither::Tpdf => *sample as f64 / step,
+                Dither::Shaped => *sample as f64 / step - self.error,
+            };
+            let noise = self.rng.unit() - self.rng.unit();
+            let rounded = (value + noise).round().clamp(min, max);
+            self.error = rounded - value;
+            *sample = rounded as i32;
+        }
+    }
+}
+
+/// The sample size a file format with whole-byte samples, like WAV, stores `bits` in.
+pub fn container_bits(bits: u16) -> u16 {
+    bits.div_ceil(8) * 8
+}
+
+/// Convert `samples` from `from_bits` to `to_bits` per sample, keeping their level. Going down
+/// rounds to the nearest value the new depth can hold.
+pub fn rescale(samples: &mut [i32], from_bits: u16, to_bits: u16) {
+    if to_bits > from_bits {
+        let shift = to_bits - from_bits;
+        for sample in samples {
+            *sample <<= shift;
+        }
+    } else if to_bits < from_bits {
+        let shift = from_bits - to_bits;
+        let max = (1i64 << (to_bits - 1)) - 1;
+        for sample in samples {
+            let rounded = (*sample as i64 + (1 << (shift - 1))) >> shift;
+            // Rounding up can go past the largest value of the new depth
+            *sample = rounded.min(max) as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_level() {
+        let mut samples = [-8_388_608, -129, -128, 127, 128, 8_388_607];
+        rescale(&mut samples, 24, 16);
+        assert_eq!(samples, [-32_768, -1, 0, 0, 1, 32_767]);
+
+        rescale(&mut samples, 16, 32);
+        assert_eq!(samples, [i32::MIN, -65_536, 0, 0, 65_536, 32_767 << 16]);
+    }
+
+    #[test]
+    fn dither_keeps_quiet_signals() {
+        // A quarter of a step of the new depth disappears when it's only rounded
+        let mut samples = vec![64; 100_000];
+        rescale(&mut samples, 24, 16);
+        assert!(samples.iter().all(|&sample| sample == 0));
+
+        for dither in [Dither::Tpdf, Dither::Shaped] {
+            let mut samples = vec![64; 100_000];
+            Ditherer::new(dither, 1).rescale(&mut samples, 24, 16);
+            let mean = samples.iter().sum::<i32>() as f64 / samples.len() as f64;
+            assert!((0.2..0.3).contains(&mean), "{dither:?}: {mean}");
+            assert!(samples.iter().all(|&sample| (-2..=2).contains(&sample)));
+        }
+    }
+
+    #[test]
+    fn containers() {
+        assert_eq!(container_bits(8), 8);
+        assert_eq!(container_bits(12), 16);
+        assert_eq!(container_bits(20), 24);
+        assert_eq!(container_bits(24), 24);
+    }
+}
@@ -0,0 +1,201 @@
+//! Broadcast WAV `bext` chunks (EBU Tech 3285), which archives use to record where a file came from
+//! and everything that was done to it. The chunk is carried over to cleaned WAV files with a line
+//! for the cleaning added to its coding history.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::decode::Spec;
+use super::{invalid_data, is_stdio};
+
+/// The length of everything in the chunk before the coding history.
+const FIXED_LEN: usize = 602;
+
+/// The fields of a `bext` chunk that the command line tool reads or changes. The rest is kept as
+/// it was.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bext {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    /// `yyyy-mm-dd`
+    pub origination_date: String,
+    /// `hh:mm:ss`
+    pub origination_time: String,
+    /// The position of the first sample in samples since midnight.
+    pub time_reference: u64,
+    /// The version, UMID, loudness values, and reserved bytes.
+    rest: Vec<u8>,
+    /// One line per step the audio went through, following EBU R98.
+    pub coding_history: String,
+}
+
+impl Bext {
+    /// A chunk for a file that didn't have one, originating now.
+    pub fn new(origination_date: String, origination_time: String) -> Self {
+        let mut rest = vec![0; FIXED_LEN - 346];
+        // Version 1, which doesn't have the loudness values
+        rest[0] = 1;
+        Self {
+            description: String::new(),
+            originator: "depop".to_owned(),
+            originator_reference: String::new(),
+            origination_date,
+            origination_time,
+            time_reference: 0,
+            rest,
+            coding_history: String::new(),
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if data.len() < FIXED_LEN {
+            return Err(invalid_data(format!(
+                "the bext chunk is only {} bytes long",
+                data.len()
+            )));
+        }
+
+        Ok(Self {
+            description: text(&data[0..256]),
+            originator: text(&data[256..288]),
+            originator_reference: text(&data[288..320]),
+            origination_date: text(&data[320..330]),
+            origination_time: text(&data[330..338]),
+            time_reference: u64::from_le_bytes(data[338..346].try_into().unwrap()),
+            rest: data[346..FIXED_LEN].to_vec(),
+            coding_history: text(&data[FIXED_LEN..]),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(FIXED_LEN + self.coding_history.len());
+        for (field, len) in [
+            (&self.description, 256),
+            (&self.originator, 32),
+            (&self.originator_reference, 32),
+            (&self.origination_date, 10),
+            (&self.origination_time, 8),
+        ] {
+            let bytes = field.as_bytes();
+            let bytes = &bytes[..bytes.len().min(len)];
+            data.extend_from_slice(bytes);
+            data.resize(data.len() + len - bytes.len(), 0);
+        }
+        data.extend_from_slice(&self.time_reference.to_le_bytes());
+        data.extend_from_slice(&self.rest);
+        data.extend_from_slice(self.coding_history.as_bytes());
+
+        data
+    }
+
+    /// Add a line to the coding history for audio that was written in `spec` by `process`, and
+    /// move the time reference to `spec`'s sample rate if it was resampled from `input_rate`.
+    pub fn add_step(&mut self, input_rate: u32, spec: Spec, float: bool, process: &str) {
+        self.time_reference =
+            (self.time_reference as u128 * spec.sample_rate as u128 / input_rate as u128) as u64;
+
+        let mode = match spec.num_channels {
+            1 => "mono",
+            2 => "stereo",
+            _ => "multichannel",
+        };
+        let bits = if float { 32 } else { spec.bits_per_sample };
+        // The lines are separated by CR LF, and some writers leave the last one off
+        if !self.coding_history.is_empty() && !self.coding_history.ends_with("\r\n") {
+            self.coding_history.push_str("\r\n");
+        }
+        self.coding_history.push_str(&format!(
+            "A=PCM,F={},W={bits},M={mode},T={process}\r\n",
+            spec.sample_rate
+        ));
+    }
+}
+
+/// Fixed-length text fields are padded with NUL bytes.
+fn text(bytes: &[u8]) -> String {
+    let len = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// Read the `bext` chunk of the WAV file at `path`, if it has one. Other formats and standard input
+/// don't have one.
+pub fn read_bext(path: &Path) -> io::Result<Option<Bext>> {
+    if is_stdio(path) {
+        return Ok(None);
+    }
+
+    find_bext(BufReader::new(File::open(path)?))
+}
+
+fn find_bext<R: Read + Seek>(mut reader: R) -> io::Result<Option<Bext>> {
+    let mut header = [0; 12];
+    if reader.read_exact(&mut header).is_err() || &header[0..4] != b"RIFF" {
+        return Ok(None);
+    }
+    if &header[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+
+    loop {
+        let mut chunk_header = [0; 8];
+        if reader.read_exact(&mut chunk_header).is_err() {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap());
+        match &chunk_header[0..4] {
+            b"bext" => {
+                let mut data = vec![0; len as usize];
+                reader.read_exact(&mut data)?;
+                return Bext::parse(&data).map(Some);
+            }
+            // Everything that matters comes before the audio
+            b"data" => return Ok(None),
+            // Chunks are padded to an even length
+            _ => {
+                reader.seek(SeekFrom::Current(len as i64 + (len % 2) as i64))?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn chunks_survive_a_round_trip() {
+        let mut bext = Bext::new("2024-03-01".to_owned(), "10:20:30".to_owned());
+        bext.description = "Side A".to_owned();
+        bext.time_reference = 96_000 * 3600;
+        bext.coding_history = "A=ANALOGUE,M=stereo,T=Technics SL-1200".to_owned();
+        let spec = Spec {
+            sample_rate: 48_000,
+            bits_per_sample: 24,
+            num_channels: 2,
+        };
+        bext.add_step(96_000, spec, false, "depop");
+        assert_eq!(bext.time_reference, 48_000 * 3600);
+        assert_eq!(
+            bext.coding_history,
+            "A=ANALOGUE,M=stereo,T=Technics SL-1200\r\nA=PCM,F=48000,W=24,M=stereo,T=depop\r\n"
+        );
+
+        let data = bext.to_bytes();
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF\0\0\0\0WAVE");
+        wav.extend_from_slice(b"JUNK\x03\0\0\0abc\0");
+        wav.extend_from_slice(b"bext");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        assert_eq!(find_bext(Cursor::new(wav)).unwrap(), Some(bext));
+
+        let plain = b"RIFF\0\0\0\0WAVEdata\0\0\0\0".to_vec();
+        assert_eq!(find_bext(Cursor::new(plain)).unwrap(), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! Picking and mixing channels, for captures where not every channel is worth keeping, like stereo
+//! transfers of mono records where only one side needs work.
+
+use std::io;
+
+/// When `--mono-mix` mixes the channels down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MixPoint {
+    /// Mix the channels first, and look for pops in the mix
+    Before,
+    /// Clean every channel on its own, and mix the cleaned channels
+    After,
+}
+
+/// What happens to the channels of a file as it's decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelMap {
+    /// Keep only these channels, counted from zero, in this order.
+    Select(Vec<usize>),
+    /// Mix all channels into one.
+    Mix,
+}
+
+impl ChannelMap {
+    /// The number of channels left of `num_channels`, or an error if a selected channel doesn't
+    /// exist.
+    pub fn num_channels(&self, num_channels: usize) -> io::Result<usize> {
+        match self {
+            ChannelMap::Select(channels) => {
+                if let Some(&channel) = channels.iter().find(|&&channel| channel >= num_channels) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "channel {} was selected, but the file only has {num_channels}",
+                            channel + 1
+                        ),
+                    ));
+                }
+                Ok(channels.len())
+            }
+            ChannelMap::Mix => Ok(1),
+        }
+    }
+
+    /// Pick or mix the channels of a chunk, which has a `Vec` per channel. A channel that's
+    /// selected more than once is copied, like `1,1` to put the left side on both sides.
+    pub fn apply(&self, chunk: &[Vec<i32>]) -> Vec<Vec<i32>> {
+        match self {
+            ChannelMap::Select(channels) => channels
+                .iter()
+                .map(|&channel| chunk[channel].clone())
+                .collect(),
+            ChannelMap::Mix => vec![mix(chunk)],
+        }
+    }
+}
+
+/// The average of all `channels`, rounded to the nearest sample.
+pub fn mix(channels: &[Vec<i32>]) -> Vec<i32> {
+    let len = channels.first().map_or(0, Vec::len);
+    (0..len)
+        .map(|index| {
+            let sum: i64 = channels.iter().map(|channel| channel[index] as i64).sum();
+            (sum as f64 / channels.len() as f64).round() as i32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_and_mixes_channels() {
+        let chunk = vec![vec![1, 2, -3], vec![4, 5, -6], vec![7, 8, 9]];
+
+        let select = ChannelMap::Select(vec![2, 0]);
+        assert_eq!(select.num_channels(3).unwrap(), 2);
+        assert_eq!(select.apply(&chunk), [vec![7, 8, 9], vec![1, 2, -3]]);
+        assert!(ChannelMap::Select(vec![3]).num_channels(3).is_err());
+
+        let twice = ChannelMap::Select(vec![0, 0]);
+        assert_eq!(twice.num_channels(3).unwrap(), 2);
+        assert_eq!(twice.apply(&chunk), [vec![1, 2, -3], vec![1, 2, -3]]);
+
+        assert_eq!(ChannelMap::Mix.num_channels(3).unwrap(), 1);
+        assert_eq!(ChannelMap::Mix.apply(&chunk), [vec![4, 5, 0]]);
+    }
+}
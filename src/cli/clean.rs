@@ -0,0 +1,534 @@
+use std::ops::Range;
+
+use rayon::prelude::*;
+use robo_depop_plugin::{clean_data_with, clean_linked_with, Algorithm, CleanConfig, MAX_RADIUS};
+use serde::Deserialize;
+
+use super::decode::Spec;
+use super::loudness::LoudnessComparison;
+
+/// The longest window that can be passed to `--window`.
+const MAX_WINDOW: usize = 2 * MAX_RADIUS + 1;
+
+/// How many samples are cleaned on a thread at a time. Long enough that the context the segments
+/// share is a rounding error, and short enough that a block of a few seconds keeps every core busy.
+pub const SEGMENT_LEN: usize = 8192;
+
+/// The names the library's [`Algorithm`]s go by on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlgorithmArg {
+    /// The midpoint between the lowest and highest surrounding sample, as described in the readme
+    WindowAvg,
+    /// The median of the surrounding samples, which copes better with two pops close together
+    Median,
+    /// Linear prediction from both sides, which follows the waveform best with wider windows
+    Lpc,
+    /// Band-limited interpolation, which keeps the upper half of the window's spectrum quietest
+    Spectral,
+}
+
+impl From<AlgorithmArg> for Algorithm {
+    fn from(algorithm: AlgorithmArg) -> Self {
+        match algorithm {
+            AlgorithmArg::WindowAvg => Algorithm::WindowAverage,
+            AlgorithmArg::Median => Algorithm::Median,
+            AlgorithmArg::Lpc => Algorithm::Lpc,
+            AlgorithmArg::Spectral => Algorithm::Spectral,
+        }
+    }
+}
+
+/// What happened to a file that was cleaned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub spec: Spec,
+    pub repairs: Vec<Repair>,
+    /// The largest absolute value of the cleaned samples, before any conversion.
+    pub peak: u32,
+    /// The loudness before and after cleaning, if it was measured.
+    pub loudness: Option<LoudnessComparison>,
+}
+
+/// A sample that was replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    /// The sample's position in its channel.
+    pub position: u64,
+    pub channel: usize,
+    pub original: i32,
+    pub replacement: i32,
+}
+
+/// Parse the `--window` length, which is the number of samples a sample is compared against
+/// including itself. It has to be odd so the sample is in the middle.
+pub fn parse_window(value: &str) -> Result<usize, String> {
+    check_window(value.parse().map_err(|error| format!("{error}"))?)
+}
+
+/// Check a window length that didn't come from the command line.
+pub fn check_window(window: usize) -> Result<usize, String> {
+    if !(3..=MAX_WINDOW).contains(&window) || window.is_multiple_of(2) {
+        return Err(format!("must be an odd number from 3 to {MAX_WINDOW}"));
+    }
+
+    Ok(window)
+}
+
+/// Parse the `--threshold` multiplier, which has to be positive.
+pub fn parse_threshold(value: &str) -> Result<f64, String> {
+    check_threshold(value.parse().map_err(|error| format!("{error}"))?)
+}
+
+/// Check a threshold that didn't come from the command line.
+pub fn check_threshold(threshold: f64) -> Result<f64, String> {
+    if !(threshold > 0.0 && threshold.is_finite()) {
+        return Err("must be a positive number".to_owned());
+    }
+
+    Ok(threshold)
+}
+
+/// The original minus the cleaned audio for the `len` samples per channel starting at `position`,
+/// which is silent everywhere except for the `repairs`.
+pub fn difference(
+    repairs: &[Repair],
+    position: u64,
+    len: usize,
+    num_channels: usize,
+) -> Vec<Vec<i32>> {
+    let mut channels = vec![vec![0; len]; num_channels];
+    for repair in repairs {
+        if let Some(offset) = repair
+            .position
+            .checked_sub(position)
+            .filter(|&offset| offset < len as u64)
+        {
+            channels[repair.channel][offset as usize] = repair.original - repair.replacement;
+        }
+    }
+
+    channels
+}
+
+/// Clean `samples` with the same result as [`clean_data_with()`], but split up into segments of
+/// `segment_len` samples that are cleaned in parallel. Each segment is cleaned along with the
+/// samples around it it depends on, so the segments stitch back together seamlessly.
+pub fn clean_in_parallel(samples: &[i32], config: &CleanConfig, segment_len: usize) -> Vec<i32> {
+    if samples.len() <= segment_len {
+        return clean_data_with(samples, config);
+    }
+
+    let reach = reach(config);
+    let segments: Vec<Vec<i32>> = (0..samples.len())
+        .step_by(segment_len)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|start| {
+            let end = (start + segment_len).min(samples.len());
+            let context_start = start.saturating_sub(reach);
+            let context_end = (end + reach).min(samples.len());
+            let cleaned = clean_data_with(&samples[context_start..context_end], config);
+            cleaned[start - context_start..end - context_start].to_vec()
+        })
+        .collect();
+
+    segments.concat()
+}
+
+/// Clean `channels` with the same result as [`clean_linked_with()`], in parallel segments like
+/// [`clean_in_parallel()`].
+pub fn clean_linked_in_parallel(
+    channels: &[Vec<i32>],
+    config: &CleanConfig,
+    segment_len: usize,
+) -> Vec<Vec<i32>> {
+    let len = channels.first().map_or(0, Vec::len);
+    if len <= segment_len {
+        return clean_linked_with(channels, config);
+    }
+
+    let reach = reach(config);
+    let segments: Vec<Vec<Vec<i32>>> = (0..len)
+        .step_by(segment_len)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|start| {
+            let end = (start + segment_len).min(len);
+            let context_start = start.saturating_sub(reach);
+            let context_end = (end + reach).min(len);
+            let context: Vec<Vec<i32>> = channels
+                .iter()
+                .map(|samples| samples[context_start..context_end].to_vec())
+                .collect();
+            clean_linked_with(&context, config)
+                .into_iter()
+                .map(|cleaned| cleaned[start - context_start..end - context_start].to_vec())
+                .collect()
+        })
+        .collect();
+
+    (0..channels.len())
+        .map(|channel| {
+            segments
+                .iter()
+                .flat_map(|segment| &segment[channel])
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+/// How far cleaning a sample can depend on the samples around it.
+fn reach(config: &CleanConfig) -> usize {
+    config.radius.clamp(1, MAX_RADIUS) * config.passes.max(1)
+}
+
+/// Cleans a channel a chunk at a time with the same result as cleaning it in one go. Each
+/// sample's window reaches `config.radius` samples into the next chunk, and every extra pass
+/// reaches that much further, so the end of every chunk is held back until the next one arrives.
+///
+/// A linked cleaner cleans several channels together like [`clean_linked_with()`] instead.
+#[derive(Debug, Clone)]
+pub struct StreamCleaner {
+    /// The channels this cleans.
+    channels: Range<usize>,
+    config: CleanConfig,
+    /// The positions of the samples that may be repaired. Everything else is passed through.
+    range: Range<u64>,
+    /// The samples that haven't been cleaned yet, preceded by the samples they need as context
+    /// once anything has been cleaned. There's a `Vec` for each channel.
+    pending: Vec<Vec<i32>>,
+    started: bool,
+    /// The position of the next cleaned sample in the channels.
+    position: u64,
+}
+
+impl StreamCleaner {
+    pub fn new(channel: usize, config: CleanConfig) -> Self {
+        Self::with_channels(channel..channel + 1, config)
+    }
+
+    /// Clean the first `num_channels` channels together, repairing a sample in all of them when
+    /// it's a pop in any of them.
+    pub fn linked(num_channels: usize, config: CleanConfig) -> Self {
+        Self::with_channels(0..num_channels, config)
+    }
+
+    fn with_channels(channels: Range<usize>, config: CleanConfig) -> Self {
+        Self {
+            pending: vec![Vec::new(); channels.len()],
+            channels,
+            config,
+            range: 0..u64::MAX,
+            started: false,
+            position: 0,
+        }
+    }
+
+    /// The channels this cleans, as indices into the file's channels.
+    pub fn channels(&self) -> Range<usize> {
+        self.channels.clone()
+    }
+
+    /// Only repair the samples at `range`. The samples around it are still used as context.
+    pub fn with_range(mut self, range: Range<u64>) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Add the next chunk with a slice for each of the channels, and return the samples that could
+    /// be cleaned so far. Samples that were replaced are added to `repairs`.
+    pub fn push(
+        &mut self,
+        chunk: &[impl AsRef<[i32]>],
+        repairs: &mut Vec<Repair>,
+    ) -> Vec<Vec<i32>> {
+        let reach = self.reach();
+        for (pending, samples) in self.pending.iter_mut().zip(chunk) {
+            pending.extend_from_slice(samples.as_ref());
+        }
+        let len = self.pending_len();
+        if len <= 2 * reach {
+            return vec![Vec::new(); self.pending.len()];
+        }
+
+        // `clean_data_with()` leaves the samples at the edges of its input alone, which is only
+        // right at the start and the end of the stream, so the samples that could be affected by
+        // that are left for later
+        let skip = if self.started { reach } else { 0 };
+        let output = self.clean(skip..len - reach, repairs);
+        self.position += (len - reach - skip) as u64;
+
+        for pending in &mut self.pending {
+            pending.drain(..len - 2 * reach);
+        }
+        self.started = true;
+
+        output
+    }
+
+    /// Clean the samples that were held back at the end of the stream, with a `Vec` per channel.
+    pub fn finish(self, repairs: &mut Vec<Repair>) -> Vec<Vec<i32>> {
+        let len = self.pending_len();
+        if len == 0 {
+            return vec![Vec::new(); self.pending.len()];
+        }
+
+        let skip = if self.started { self.reach() } else { 0 };
+        self.clean(skip..len, repairs)
+    }
+
+    fn reach(&self) -> usize {
+        reach(&self.config)
+    }
+
+    fn pending_len(&self) -> usize {
+        self.pending.first().map_or(0, Vec::len)
+    }
+
+    /// Clean the pending samples, and return the ones at `output`, which start at the current
+    /// position.
+    fn clean(&self, output: Range<usize>, repairs: &mut Vec<Repair>) -> Vec<Vec<i32>> {
+        let cleaned = if self.pending.len() == 1 {
+            vec![clean_in_parallel(
+                &self.pending[0],
+                &self.config,
+                SEGMENT_LEN,
+            )]
+        } else {
+            clean_linked_in_parallel(&self.pending, &self.config, SEGMENT_LEN)
+        };
+
+        self.channels
+            .clone()
+            .zip(&self.pending)
+            .zip(cleaned)
+            .map(|((channel, original), mut cleaned)| {
+                let original = &original[output.clone()];
+                let cleaned = &mut cleaned[output.clone()];
+                self.restore_outside_range(original, cleaned);
+                self.record_repairs(channel, original, cleaned, repairs);
+                cleaned.to_vec()
+            })
+            .collect()
+    }
+
+    /// Undo the repairs outside of the range, with `original` starting at the current position.
+    fn restore_outside_range(&self, original: &[i32], cleaned: &mut [i32]) {
+        for (offset, (&original, cleaned)) in original.iter().zip(cleaned).enumerate() {
+            if !self.range.contains(&(self.position + offset as u64)) {
+                *cleaned = original;
+            }
+        }
+    }
+
+    /// Note down which of the cleaned samples in `channel` differ from the originals, with
+    /// `original` starting at the current position.
+    fn record_repairs(
+        &self,
+        channel: usize,
+        original: &[i32],
+        cleaned: &[i32],
+        repairs: &mut Vec<Repair>,
+    ) {
+        for (offset, (&original, &replacement)) in original.iter().zip(cleaned).enumerate() {
+            if original != replacement {
+                repairs.push(Repair {
+                    position: self.position + offset as u64,
+                    channel,
+                    original,
+                    replacement,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_cleaning_in_one_go() {
+        for config in [
+            CleanConfig::default(),
+            CleanConfig {
+                algorithm: Algorithm::Lpc,
+                threshold: 1.5,
+                radius: 5,
+                passes: 2,
+            },
+            CleanConfig {
+                algorithm: Algorithm::Median,
+                passes: 3,
+                ..CleanConfig::default()
+            },
+        ] {
+            check_chunked_cleaning(config);
+        }
+    }
+
+    #[test]
+    fn parallel_cleaning_matches_cleaning_in_one_go() {
+        let samples: Vec<i32> = (0..5000)
+            .map(|i| {
+                if i % 97 == 0 {
+                    30_000
+                } else {
+                    (i * 37 % 200) - 100
+                }
+            })
+            .collect();
+        for config in [
+            CleanConfig::default(),
+            CleanConfig {
+                algorithm: Algorithm::Lpc,
+                radius: 6,
+                passes: 3,
+                ..CleanConfig::default()
+            },
+        ] {
+            let expected = clean_data_with(&samples, &config);
+            for segment_len in [1, 7, 100, 4999, 5000] {
+                assert_eq!(
+                    clean_in_parallel(&samples, &config, segment_len),
+                    expected,
+                    "{config:?}, segment length {segment_len}"
+                );
+            }
+        }
+    }
+
+    fn check_chunked_cleaning(config: CleanConfig) {
+        let samples: Vec<i32> = (0..1000)
+            .map(|i| match i {
+                0 | 333 | 500 | 501 | 998 => 30_000,
+                _ => (i * 37 % 200) - 100,
+            })
+            .collect();
+
+        let expected = clean_data_with(&samples, &config);
+        let expected_repairs: Vec<Repair> = (0..samples.len())
+            .filter(|&i| samples[i] != expected[i])
+            .map(|i| Repair {
+                position: i as u64,
+                channel: 1,
+                original: samples[i],
+                replacement: expected[i],
+            })
+            .collect();
+        assert!(!expected_repairs.is_empty());
+
+        for chunk_size in [1, 3, 4, 5, 64, 1000] {
+            let mut cleaner = StreamCleaner::new(1, config);
+            let mut cleaned = Vec::new();
+            let mut repairs = Vec::new();
+            for chunk in samples.chunks(chunk_size) {
+                cleaned.extend(cleaner.push(&[chunk], &mut repairs).remove(0));
+            }
+            cleaned.extend(cleaner.finish(&mut repairs).remove(0));
+
+            assert_eq!(cleaned, expected, "{config:?}, chunk size {chunk_size}");
+            assert_eq!(
+                repairs, expected_repairs,
+                "{config:?}, chunk size {chunk_size}"
+            );
+        }
+    }
+
+    #[test]
+    fn linked_channels_are_repaired_together() {
+        let left: Vec<i32> = (0..3000)
+            .map(|i| {
+                if i % 101 == 0 {
+                    30_000
+                } else {
+                    i * 37 % 200 - 100
+                }
+            })
+            .collect();
+        let right: Vec<i32> = (0..3000).map(|i| i * 7919 % 300 - 150).collect();
+        let channels = vec![left, right];
+        let config = CleanConfig {
+            passes: 2,
+            ..CleanConfig::default()
+        };
+
+        let expected = clean_linked_with(&channels, &config);
+        assert_eq!(clean_data_with(&channels[1], &config), channels[1]);
+        assert_ne!(expected[1][1010], channels[1][1010]);
+        for segment_len in [1, 100, 2999] {
+            assert_eq!(
+                clean_linked_in_parallel(&channels, &config, segment_len),
+                expected
+            );
+        }
+
+        let mut cleaner = StreamCleaner::linked(2, config);
+        let mut cleaned = vec![Vec::new(); 2];
+        let mut repairs = Vec::new();
+        for start in (0..3000).step_by(7) {
+            let chunk: Vec<&[i32]> = channels
+                .iter()
+                .map(|samples| &samples[start..(start + 7).min(3000)])
+                .collect();
+            for (cleaned, samples) in cleaned.iter_mut().zip(cleaner.push(&chunk, &mut repairs)) {
+                cleaned.extend(samples);
+            }
+        }
+        for (cleaned, samples) in cleaned.iter_mut().zip(cleaner.finish(&mut repairs)) {
+            cleaned.extend(samples);
+        }
+
+        assert_eq!(cleaned, expected);
+        assert!(repairs
+            .iter()
+            .any(|repair| repair.channel == 1 && repair.position == 1010));
+    }
+
+    #[test]
+    fn only_repairs_the_range() {
+        let mut samples = vec![0; 100];
+        for position in [10, 50, 90] {
+            samples[position] = 30_000;
+        }
+
+        let mut cleaner = StreamCleaner::new(0, CleanConfig::default()).with_range(40..91);
+        let mut cleaned = Vec::new();
+        let mut repairs = Vec::new();
+        for chunk in samples.chunks(7) {
+            cleaned.extend(cleaner.push(&[chunk], &mut repairs).remove(0));
+        }
+        cleaned.extend(cleaner.finish(&mut repairs).remove(0));
+
+        let positions: Vec<u64> = repairs.iter().map(|repair| repair.position).collect();
+        assert_eq!(positions, [50, 90]);
+        assert_eq!(cleaned[10], 30_000);
+        assert_eq!(cleaned.iter().filter(|&&sample| sample != 0).count(), 1);
+    }
+
+    #[test]
+    fn difference_holds_the_repairs() {
+        let repairs = [
+            Repair {
+                position: 4,
+                channel: 1,
+                original: 100,
+                replacement: -20,
+            },
+            Repair {
+                position: 9,
+                channel: 0,
+                original: -5,
+                replacement: 3,
+            },
+        ];
+
+        assert_eq!(
+            difference(&repairs, 3, 4, 2),
+            [vec![0, 0, 0, 0], vec![0, 120, 0, 0]]
+        );
+        assert_eq!(difference(&repairs, 8, 2, 2), [vec![0, -8], vec![0, 0]]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Compares two versions of a recording sample by sample, to check that a change to the cleaning
+//! only touched what it should have. Files with different bit depths are compared at their level
+//! relative to full scale, and a file that starts a little later than the other is lined up first.
+
+use std::io;
+use std::path::Path;
+
+use super::decode::{Decoder, Spec};
+use super::invalid_data;
+
+/// The number of samples from the start of the files that are used to line them up.
+const ALIGN_LEN: usize = 32_768;
+
+/// The number of samples per channel compared at a time.
+const BLOCK_LEN: usize = 65_536;
+
+/// How two files differ.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub spec: Spec,
+    /// How many samples later the audio starts in the second file than in the first. This is
+    /// negative if it starts earlier.
+    pub offset: i64,
+    /// The number of samples per channel that were compared, which is the length of the part the
+    /// files have in common.
+    pub compared: u64,
+    /// The number of samples per channel in either file that had no counterpart in the other,
+    /// before and after the part they have in common.
+    pub unmatched: (u64, u64),
+    /// The number of samples, counting every channel, that aren't exactly the same.
+    pub differing: u64,
+    /// The largest difference between two samples relative to full scale, and where it was as the
+    /// position in the first file and the channel.
+    pub max_difference: f64,
+    pub max_difference_at: (u64, usize),
+    /// The root mean square of the differences, relative to full scale.
+    pub rms_difference: f64,
+    /// The ratio of the first file's power to the power of the differences, in dB. This is
+    /// infinite for identical files.
+    pub snr: f64,
+}
+
+/// Running totals over the part the files have in common.
+#[derive(Debug, Default)]
+struct Totals {
+    compared: u64,
+    differing: u64,
+    max_difference: f64,
+    max_difference_at: (u64, usize),
+    signal_power: f64,
+    difference_power: f64,
+}
+
+impl Totals {
+    /// Add the next blocks of both files, whose samples are scaled to full scale.
+    fn push(&mut self, a: &[Vec<f64>], b: &[Vec<f64>]) {
+        for (channel, (a, b)) in a.iter().zip(b).enumerate() {
+            for (index, (&a, &b)) in a.iter().zip(b).enumerate() {
+                let difference = (a - b).abs();
+                if difference > 0.0 {
+                    self.differing += 1;
+                }
+                if difference > self.max_difference {
+                    self.max_difference = difference;
+                    self.max_difference_at = (self.compared + index as u64, channel);
+                }
+                self.signal_power += a * a;
+                self.difference_power += difference * difference;
+            }
+        }
+        self.compared += a.first().map_or(0, Vec::len) as u64;
+    }
+
+    fn rms_difference(&self, num_channels: usize) -> f64 {
+        let count = self.compared * num_channels as u64;
+        if count == 0 {
+            0.0
+        } else {
+            (self.difference_power / count as f64).sqrt()
+        }
+    }
+
+    fn snr(&self) -> f64 {
+        if self.difference_power == 0.0 {
+            f64::INFINITY
+        } else {
+            10.0 * (self.signal_power / self.difference_power).log10()
+        }
+    }
+}
+
+/// A decoder whose samples are scaled to full scale, and can be taken in any amount.
+struct Samples {
+    decoder: Decoder,
+    scale: f64,
+    channels: Vec<Vec<f64>>,
+    ended: bool,
+}
+
+impl Samples {
+    fn open(path: &Path) -> io::Result<Self> {
+        let decoder = Decoder::open(path)?;
+        let spec = decoder.spec();
+        Ok(Self {
+            scale: 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f64,
+            channels: vec![Vec::new(); spec.num_channels],
+            decoder,
+            ended: false,
+        })
+    }
+
+    /// Decode until at least `len` samples per channel are waiting, or the file ends. Returns the
+    /// number that are.
+    fn fill(&mut self, len: usize) -> io::Result<usize> {
+        while !self.ended && self.channels[0].len() < len {
+            match self.decoder.next_chunk()? {
+                Some(chunk) => {
+                    for (channel, samples) in self.channels.iter_mut().zip(chunk) {
+                        channel.extend(samples.iter().map(|&sample| sample as f64 * self.scale));
+                    }
+                }
+                None => self.ended = true,
+            }
+        }
+
+        Ok(self.channels[0].len())
+    }
+
+    fn take(&mut self, len: usize) -> Vec<Vec<f64>> {
+        self.channels
+            .iter_mut()
+            .map(|channel| channel.drain(..len).collect())
+            .collect()
+    }
+
+    /// Throw away up to `len` samples per channel, and return how many there were.
+    fn skip(&mut self, len: usize) -> io::Result<usize> {
+        let len = self.fill(len)?.min(len);
+        self.take(len);
+        Ok(len)
+    }
+
+    /// Throw away the rest of the file, and return how many samples per channel that was.
+    fn skip_rest(&mut self) -> io::Result<u64> {
+        let mut len = 0;
+        while self.fill(BLOCK_LEN)? > 0 {
+            let available = self.channels[0].len();
+            self.take(available);
+            len += available as u64;
+        }
+
+        Ok(len)
+    }
+
+    /// The waiting samples of all channels mixed together.
+    fn mono(&self) -> Vec<f64> {
+        (0..self.channels[0].len())
+            .map(|index| self.channels.iter().map(|channel| channel[index]).sum())
+            .collect()
+    }
+}
+
+/// Compare the files at `a` and `b`, after lining them up by shifting them up to `max_offset`
+/// seconds against each other.
+pub fn compare(a: &Path, b: &Path, max_offset: f64) -> io::Result<Comparison> {
+    let mut a_samples = Samples::open(a)?;
+    let mut b_samples = Samples::open(b)?;
+    let spec = a_samples.decoder.spec();
+    let b_spec = b_samples.decoder.spec();
+    if spec.num_channels != b_spec.num_channels || spec.sample_rate != b_spec.sample_rate {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} channels at {} Hz can't be compared with {} channels at {} Hz",
+                spec.num_channels, spec.sample_rate, b_spec.num_channels, b_spec.sample_rate
+            ),
+        ));
+    }
+
+    let max_offset = (max_offset * spec.sample_rate as f64).round() as usize;
+    let align_len = ALIGN_LEN + 2 * max_offset;
+    a_samples.fill(align_len)?;
+    b_samples.fill(align_len)?;
+    let offset = find_offset(&a_samples.mono(), &b_samples.mono(), max_offset);
+    let skipped_a = a_samples.skip((-offset).max(0) as usize)?;
+    let skipped_b = b_samples.skip(offset.max(0) as usize)?;
+
+    let mut totals = Totals::default();
+    loop {
+        let len = a_samples.fill(BLOCK_LEN)?.min(b_samples.fill(BLOCK_LEN)?);
+        if len == 0 {
+            break;
+        }
+        totals.push(&a_samples.take(len), &b_samples.take(len));
+    }
+    if totals.compared == 0 {
+        return Err(invalid_data("the files have no audio in common".to_owned()));
+    }
+    let unmatched = (
+        skipped_a as u64 + a_samples.skip_rest()?,
+        skipped_b as u64 + b_samples.skip_rest()?,
+    );
+
+    Ok(Comparison {
+        spec,
+        offset,
+        compared: totals.compared,
+        unmatched,
+        differing: totals.differing,
+        max_difference: totals.max_difference,
+        max_difference_at: (
+            totals.max_difference_at.0 + skipped_a as u64,
+            totals.max_difference_at.1,
+        ),
+        rms_difference: totals.rms_difference(spec.num_channels),
+        snr: totals.snr(),
+    })
+}
+
+/// How many samples later `b` starts than `a`, from -`max_offset` to `max_offset`. This is where
+/// the two correlate best, which is the offset with no difference at all for files that only
+/// differ in a few repaired samples.
+fn find_offset(a: &[f64], b: &[f64], max_offset: usize) -> i64 {
+    // Both sides need room to shift, so short files are searched less far
+    let max_offset = max_offset.min(a.len().min(b.len()) / 4) as i64;
+    let len = a.len().min(b.len()) - 2 * max_offset as usize;
+    let a = &a[max_offset as usize..max_offset as usize + len];
+
+    let mut best: (i64, f64) = (0, f64::NEG_INFINITY);
+    for offset in -max_offset..=max_offset {
+        let start = (max_offset + offset) as usize;
+        let correlation: f64 = a
+            .iter()
+            .zip(&b[start..start + len])
+            .map(|(a, b)| a * b)
+            .sum();
+        // Ties go to the offset closest to zero
+        if correlation > best.1 || (correlation == best.1 && offset.abs() < best.0.abs()) {
+            best = (offset, correlation);
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_offset() {
+        let signal: Vec<f64> = (0..5000)
+            .map(|i| ((i * 7919) % 1000) as f64 / 1000.0 - 0.5)
+            .collect();
+        assert_eq!(find_offset(&signal, &signal, 100), 0);
+        let later: Vec<f64> = [vec![0.0; 37], signal.clone()].concat();
+        assert_eq!(find_offset(&signal, &later, 100), 37);
+        assert_eq!(find_offset(&later, &signal, 100), -37);
+    }
+
+    #[test]
+    fn measures_the_differences() {
+        let a = vec![vec![0.5, -0.5, 0.5, -0.5]];
+        let mut b = a.clone();
+        b[0][2] = 0.0;
+        let mut totals = Totals::default();
+        totals.push(&a, &b);
+
+        assert_eq!(totals.differing, 1);
+        assert_eq!(totals.max_difference, 0.5);
+        assert_eq!(totals.max_difference_at, (2, 0));
+        assert_eq!(totals.rms_difference(1), 0.25);
+        assert_eq!(totals.snr(), 10.0 * 4f64.log10());
+    }
+}
@@ -0,0 +1,104 @@
+use super::bit_depth::rescale;
+use super::bit_depth::{Dither, Ditherer};
+use super::channels::mix;
+use super::decode::Spec;
+use super::normalize::apply_gain;
+use super::resample::Resampler;
+
+/// Turns the cleaned audio into the output's format, changing its channels, level, sample rate,
+/// and bit depth where needed. The channels are mixed down to mono first when the output has only
+/// one, then the gain and the sample rate are applied, at the input's bit depth.
+pub struct Converter {
+    mix: bool,
+    gain: f64,
+    resamplers: Option<Vec<Resampler>>,
+    /// One per channel, only when dithering.
+    ditherers: Option<Vec<Ditherer>>,
+    num_channels: usize,
+    input_bits: u16,
+    output_bits: u16,
+}
+
+impl Converter {
+    pub fn new(input: Spec, output: Spec) -> Self {
+        let resamplers = (input.sample_rate != output.sample_rate).then(|| {
+            (0..output.num_channels)
+                .map(|_| {
+                    Resampler::new(input.sample_rate, output.sample_rate, input.bits_per_sample)
+                })
+                .collect()
+        });
+
+        Self {
+            mix: output.num_channels == 1 && input.num_channels > 1,
+            gain: 1.0,
+            resamplers,
+            ditherers: None,
+            num_channels: output.num_channels,
+            input_bits: input.bits_per_sample,
+            output_bits: output.bits_per_sample,
+        }
+    }
+
+    /// Multiply the samples by `gain` before anything else.
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    /// Dither the samples when their bit depth is reduced.
+    pub fn with_dither(mut self, dither: Option<Dither>) -> Self {
+        self.ditherers = dither.map(|dither| {
+            (0..self.num_channels as u64)
+                .map(|channel| Ditherer::new(dither, channel))
+                .collect()
+        });
+        self
+    }
+
+    /// Convert the next chunk, with one `Vec` per channel.
+    pub fn push(&mut self, mut channels: Vec<Vec<i32>>) -> Vec<Vec<i32>> {
+        if self.mix {
+            channels = vec![mix(&channels)];
+        }
+        if self.gain != 1.0 {
+            for samples in &mut channels {
+                apply_gain(samples, self.gain, self.input_bits);
+            }
+        }
+        let channels = match &mut self.resamplers {
+            Some(resamplers) => resamplers
+                .iter_mut()
+                .zip(&channels)
+                .map(|(resampler, samples)| resampler.push(samples))
+                .collect(),
+            None => channels,
+        };
+
+        self.rescale(channels)
+    }
+
+    /// Convert whatever is still held back at the end of the stream.
+    pub fn finish(mut self) -> Vec<Vec<i32>> {
+        match self.resamplers.take() {
+            Some(resamplers) => {
+                let channels = resamplers.into_iter().map(Resampler::finish).collect();
+                self.rescale(channels)
+            }
+            None => vec![Vec::new(); self.num_channels],
+        }
+    }
+
+    fn rescale(&mut self, mut channels: Vec<Vec<i32>>) -> Vec<Vec<i32>> {
+        for (channel, samples) in channels.iter_mut().enumerate() {
+            match &mut self.ditherers {
+                Some(ditherers) => {
+                    ditherers[channel].rescale(samples, self.input_bits, self.output_bits)
+                }
+                None => rescale(samples, self.input_bits, self.output_bits),
+            }
+        }
+
+        channels
+    }
+}
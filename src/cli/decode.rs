@@ -0,0 +1,487 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{self, DecoderOptions, VerificationCheck, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSource, MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use super::channels::ChannelMap;
+use super::raw::{RawReader, RawSpec};
+use super::{invalid_data, is_stdio};
+
+/// Lossy formats don't have a bit depth of their own. Their decoded samples are stored with this
+/// many bits, which is more than enough to not add any audible quantization noise.
+const LOSSY_BITS_PER_SAMPLE: u32 = 24;
+
+/// The format of the audio going through the command line tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spec {
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub num_channels: usize,
+}
+
+/// Decodes an audio file a packet at a time, so files of any length can be cleaned without
+/// holding them in memory. This handles FLAC, WAV, AIFF, MP3, Ogg Vorbis, and ALAC, as well as
+/// headerless PCM in a format given up front.
+pub struct Decoder {
+    source: Source,
+    spec: Spec,
+    /// The number of samples per channel, if the file says so up front.
+    num_frames: Option<u64>,
+    /// A chunk that had to be decoded early to find out the number of channels.
+    first_chunk: Option<Vec<Vec<i32>>>,
+    /// Which channels are kept or mixed, if not all of them are kept as they are.
+    channel_map: Option<ChannelMap>,
+    /// The number of packets that were skipped because they couldn't be decoded.
+    damaged_packets: usize,
+}
+
+/// Where the samples come from.
+enum Source {
+    Symphonia {
+        format: Box<dyn FormatReader>,
+        decoder: Box<dyn codecs::Decoder>,
+        track_id: u32,
+        sample_buffer: Option<SampleBuffer<i32>>,
+    },
+    Raw(RawReader),
+}
+
+impl Decoder {
+    /// Open an audio file, or standard input if `path` is `-`. The format is recognized from the
+    /// file's contents, with its extension as a hint.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        if is_stdio(path) {
+            return Self::new(Box::new(ReadOnlySource::new(io::stdin())), &Hint::new());
+        }
+
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        Self::new(Box::new(File::open(path)?), &hint)
+    }
+
+    /// Open headerless PCM in the format given by `spec`, or read it from standard input if `path`
+    /// is `-`.
+    pub fn open_raw(path: &Path, spec: RawSpec) -> io::Result<Self> {
+        let (reader, num_frames): (Box<dyn io::Read>, _) = if is_stdio(path) {
+            (Box::new(io::stdin()), None)
+        } else {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len();
+            (
+                Box::new(io::BufReader::new(file)),
+                Some(len / spec.frame_len() as u64),
+            )
+        };
+
+        Ok(Self {
+            source: Source::Raw(RawReader::new(reader, spec)),
+            spec: Spec {
+                sample_rate: spec.sample_rate,
+                bits_per_sample: spec.format.bits_per_sample(),
+                num_channels: spec.num_channels,
+            },
+            num_frames,
+            first_chunk: None,
+            channel_map: None,
+            damaged_packets: 0,
+        })
+    }
+
+    fn new(source: Box<dyn MediaSource>, hint: &Hint) -> io::Result<Self> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let format = symphonia::default::get_probe()
+            .format(
+                hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(symphonia_error)?
+            .format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| invalid_data("the file does not contain any audio".to_owned()))?;
+        let params = &track.codec_params;
+        let sample_rate = params
+            .sample_rate
+            .ok_or_else(|| invalid_data("the file does not specify a sample rate".to_owned()))?;
+        // Samples are decoded into 32 bits, so 64-bit floating point files are cleaned at 32 bits
+        let bits_per_sample = params
+            .bits_per_sample
+            .unwrap_or(LOSSY_BITS_PER_SAMPLE)
+            .min(32);
+        if bits_per_sample == 0 {
+            return Err(invalid_data(
+                "the file claims to have 0 bits per sample".to_owned(),
+            ));
+        }
+        // FLAC files can carry an MD5 signature of their audio, which is checked once all of it
+        // has been decoded
+        let options = DecoderOptions {
+            verify: matches!(params.verification_check, Some(VerificationCheck::Md5(_))),
+        };
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &options)
+            .map_err(symphonia_error)?;
+
+        let num_channels = params.channels.map_or(0, |channels| channels.count());
+        // WAV files that were streamed, like the ones written to standard output, have the largest
+        // possible size in their header rather than their length
+        let frame_len =
+            params.bits_per_sample.unwrap_or(0).div_ceil(8) as u64 * num_channels as u64;
+        let num_frames = params
+            .n_frames
+            .filter(|&num_frames| frame_len == 0 || num_frames != u32::MAX as u64 / frame_len);
+
+        let mut decoder = Self {
+            spec: Spec {
+                sample_rate,
+                bits_per_sample: bits_per_sample as u16,
+                num_channels,
+            },
+            num_frames,
+            source: Source::Symphonia {
+                track_id: track.id,
+                format,
+                decoder,
+                sample_buffer: None,
+            },
+            first_chunk: None,
+            channel_map: None,
+            damaged_packets: 0,
+        };
+
+        // Some formats only reveal their channel layout in the audio itself
+        if decoder.spec.num_channels == 0 {
+            let first_chunk = decoder
+                .decode_chunk()?
+                .ok_or_else(|| invalid_data("the file does not contain any audio".to_owned()))?;
+            decoder.spec.num_channels = first_chunk.len();
+            decoder.first_chunk = Some(first_chunk);
+        }
+
+        Ok(decoder)
+    }
+
+    /// Pick or mix the channels as they're decoded, which changes the spec to match. Fails if a
+    /// selected channel isn't in the file.
+    pub fn with_channel_map(mut self, channel_map: ChannelMap) -> io::Result<Self> {
+        self.spec.num_channels = channel_map.num_channels(self.spec.num_channels)?;
+        self.channel_map = Some(channel_map);
+        Ok(self)
+    }
+
+    pub fn spec(&self) -> Spec {
+        self.spec
+    }
+
+    /// The number of samples per channel, if the file says so up front.
+    pub fn num_frames(&self) -> Option<u64> {
+        self.num_frames
+    }
+
+    /// The number of packets that were skipped so far because they couldn't be decoded.
+    pub fn damaged_packets(&self) -> usize {
+        self.damaged_packets
+    }
+
+    /// Check the decoded audio against the MD5 signature in a FLAC file's STREAMINFO block. This
+    /// only means something once the whole file has been decoded. Returns `None` for files without
+    /// a signature, which includes FLAC files whose encoder left it out.
+    pub fn md5_matches(&mut self) -> Option<bool> {
+        match &mut self.source {
+            Source::Symphonia { decoder, .. } => decoder.finalize().verify_ok,
+            Source::Raw(_) => None,
+        }
+    }
+
+    /// Decode the next stretch of audio, with one `Vec` per channel. Returns `None` at the end of
+    /// the file.
+    pub fn next_chunk(&mut self) -> io::Result<Option<Vec<Vec<i32>>>> {
+        let chunk = match self.first_chunk.take() {
+            Some(chunk) => Some(chunk),
+            None => self.decode_chunk()?,
+        };
+
+        Ok(match &self.channel_map {
+            Some(channel_map) => chunk.map(|chunk| channel_map.apply(&chunk)),
+            None => chunk,
+        })
+    }
+
+    /// Decode at least `min_len` samples per channel, or whatever is left of the file. Returns
+    /// `None` at the end of the file.
+    pub fn next_block(&mut self, min_len: usize) -> io::Result<Option<Vec<Vec<i32>>>> {
+        let Some(mut block) = self.next_chunk()? else {
+            return Ok(None);
+        };
+        while block[0].len() < min_len {
+            let Some(chunk) = self.next_chunk()? else {
+                break;
+            };
+            for (channel, samples) in block.iter_mut().zip(chunk) {
+                channel.extend(samples);
+            }
+        }
+
+        Ok(Some(block))
+    }
+
+    /// Decode the samples from `start` up to `end`, with one `Vec` per channel. They're cut short if
+    /// the file ends before `end`.
+    pub fn read_range(&mut self, start: u64, end: u64) -> io::Result<Vec<Vec<i32>>> {
+        let mut channels = vec![Vec::new(); self.spec.num_channels];
+        let mut position = 0;
+        while position < end {
+            let Some(chunk) = self.next_chunk()? else {
+                break;
+            };
+            let chunk_start = position;
+            position += chunk.first().map_or(0, Vec::len) as u64;
+            if position > start {
+                for (channel, samples) in channels.iter_mut().zip(&chunk) {
+                    let from = start.saturating_sub(chunk_start) as usize;
+                    let to = (end - chunk_start).min(samples.len() as u64) as usize;
+                    channel.extend_from_slice(&samples[from..to]);
+                }
+            }
+        }
+
+        Ok(channels)
+    }
+
+    fn decode_chunk(&mut self) -> io::Result<Option<Vec<Vec<i32>>>> {
+        let (format, decoder, track_id, sample_buffer) = match &mut self.source {
+            Source::Symphonia {
+                format,
+                decoder,
+                track_id,
+                sample_buffer,
+            } => (format, decoder, *track_id, sample_buffer),
+            Source::Raw(reader) => return reader.next_chunk(),
+        };
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(error))
+                    if error.kind() == io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None)
+                }
+                Err(error) => return Err(symphonia_error(error)),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A damaged packet only loses that packet's audio, like in any other player
+                Err(SymphoniaError::DecodeError(_)) => {
+                    self.damaged_packets += 1;
+                    continue;
+                }
+                Err(error) => return Err(symphonia_error(error)),
+            };
+            let num_frames = decoded.frames();
+            if num_frames == 0 {
+                continue;
+            }
+
+            let spec = *decoded.spec();
+            let num_channels = spec.channels.count();
+            if sample_buffer
+                .as_ref()
+                .is_none_or(|buffer| buffer.capacity() < decoded.capacity() * num_channels)
+            {
+                *sample_buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+            }
+            let sample_buffer = sample_buffer.as_mut().unwrap();
+            sample_buffer.copy_planar_ref(decoded);
+
+            // Everything is converted to 32-bit samples, which are scaled back to the original
+            // depth
+            let shift = 32 - self.spec.bits_per_sample as u32;
+            let chunk = sample_buffer
+                .samples()
+                .chunks(num_frames)
+                .map(|samples| samples.iter().map(|&sample| sample >> shift).collect())
+                .collect();
+            return Ok(Some(chunk));
+        }
+    }
+}
+
+fn symphonia_error(error: SymphoniaError) -> io::Error {
+    match error {
+        SymphoniaError::IoError(error) => error,
+        SymphoniaError::Unsupported(what) => io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported format or codec: {what}"),
+        ),
+        error => invalid_data(format!("could not decode the file: {error}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Seek, Write};
+
+    use crate::cli::encode::{Encoder, Output, OutputFormat};
+    use crate::cli::flac_encoder::FlacWriter;
+    use crate::cli::metadata::Metadata;
+    use crate::cli::wav_writer::{SampleFormat, WavWriter};
+
+    /// Decode a whole file from memory.
+    fn decode_bytes(bytes: Vec<u8>) -> (Spec, Vec<Vec<i32>>) {
+        let mut decoder = Decoder::new(Box::new(Cursor::new(bytes)), &Hint::new()).unwrap();
+        let mut channels = vec![Vec::new(); decoder.spec().num_channels];
+        while let Some(chunk) = decoder.next_chunk().unwrap() {
+            for (channel, samples) in channels.iter_mut().zip(chunk) {
+                channel.extend(samples);
+            }
+        }
+
+        (decoder.spec(), channels)
+    }
+
+    #[test]
+    fn samples_keep_their_original_bit_depth() {
+        let left: Vec<i32> = (0..5000)
+            .map(|i| (i * 3001 % 16_000_000) - 8_000_000)
+            .collect();
+        let right: Vec<i32> = left.iter().map(|sample| -sample / 3).collect();
+
+        let mut wav = Cursor::new(Vec::new());
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 96_000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for (&l, &r) in left.iter().zip(&right) {
+            writer.write_sample(l).unwrap();
+            writer.write_sample(r).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (spec, channels) = decode_bytes(wav.into_inner());
+        assert_eq!(
+            spec,
+            Spec {
+                sample_rate: 96_000,
+                bits_per_sample: 24,
+                num_channels: 2,
+            }
+        );
+        assert_eq!(channels, [left.clone(), right.clone()]);
+
+        let mut flac = FlacWriter::new(Cursor::new(Vec::new()), 96_000, 24, 2, 5).unwrap();
+        flac.write(&[&left, &right]).unwrap();
+        let (spec, channels) = decode_bytes(flac.finish().unwrap().into_inner());
+        assert_eq!(spec.bits_per_sample, 24);
+        assert_eq!(channels, [left, right]);
+    }
+
+    #[test]
+    fn uncommon_bit_depths_keep_their_level() {
+        let samples: Vec<i32> = (0..1000)
+            .map(|i| (i * 1049 % 1_000_000) - 500_000)
+            .collect();
+
+        // WAV has no 20-bit samples, so they're stored as 24 bits
+        let path = std::env::temp_dir().join(format!("depop-20-bit-{}.wav", std::process::id()));
+        let mut wav = Output::create(&path).unwrap();
+        let spec = Spec {
+            sample_rate: 48_000,
+            bits_per_sample: 20,
+            num_channels: 1,
+        };
+        let mut encoder =
+            Encoder::new(&mut wav, OutputFormat::Wav, spec, 0, &Metadata::default()).unwrap();
+        encoder.write(std::slice::from_ref(&samples)).unwrap();
+        encoder.finish().unwrap();
+        wav.finish().unwrap();
+        let (spec, channels) = decode_bytes(std::fs::read(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(spec.bits_per_sample, 24);
+        let shifted: Vec<i32> = samples.iter().map(|sample| sample << 4).collect();
+        assert_eq!(channels, [shifted]);
+
+        for bits in [8, 12, 20] {
+            let samples: Vec<i32> = samples.iter().map(|sample| sample >> (20 - bits)).collect();
+            let mut flac = FlacWriter::new(Cursor::new(Vec::new()), 48_000, bits, 1, 5).unwrap();
+            flac.write(&[&samples]).unwrap();
+            let (spec, channels) = decode_bytes(flac.finish().unwrap().into_inner());
+            assert_eq!(spec.bits_per_sample, bits);
+            assert_eq!(channels, [samples]);
+        }
+    }
+
+    /// Standard output, as far as the writers can tell.
+    struct Unseekable(Vec<u8>);
+
+    impl Write for Unseekable {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Unseekable {
+        fn seek(&mut self, _: io::SeekFrom) -> io::Result<u64> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn streams_of_unknown_length_can_be_read() {
+        let samples: Vec<i32> = (0..10_000).map(|i| (i * 7919 % 20_000) - 10_000).collect();
+
+        let mut wav = WavWriter::streaming(
+            Unseekable(Vec::new()),
+            1,
+            44_100,
+            SampleFormat::Int(16),
+            &[],
+        )
+        .unwrap();
+        wav.write_int(std::slice::from_ref(&samples)).unwrap();
+        let wav = wav.finish().unwrap().0;
+        let decoder = Decoder::new(Box::new(Cursor::new(wav.clone())), &Hint::new()).unwrap();
+        assert_eq!(decoder.num_frames(), None);
+        let (_, channels) = decode_bytes(wav);
+        assert_eq!(channels, std::slice::from_ref(&samples));
+
+        let mut flac = FlacWriter::streaming(Unseekable(Vec::new()), 44_100, 16, 1, 5).unwrap();
+        flac.write(&[&samples]).unwrap();
+        let (_, channels) = decode_bytes(flac.finish().unwrap().0);
+        assert_eq!(channels, [samples]);
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        let result = Decoder::new(
+            Box::new(Cursor::new(b"ID3 but not really".to_vec())),
+            &Hint::new(),
+        );
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,98 @@
+//! Dumps a stretch of a file before and after cleaning as CSV, to see exactly what happened to
+//! each sample in a spreadsheet or plotting tool.
+
+use robo_depop_plugin::{clean_data_with, CleanConfig};
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::decode::Decoder;
+use super::invalid_data;
+
+/// Write `len` samples per channel starting at `start_seconds` to `writer`, with a row per sample:
+/// its position, its time in seconds, and every channel before and after cleaning it with
+/// `config`.
+pub fn dump_region(
+    input: &Path,
+    start_seconds: f64,
+    len: usize,
+    config: &CleanConfig,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut decoder = Decoder::open(input)?;
+    let spec = decoder.spec();
+
+    // Like for plots, the samples around the region are cleaned along with it
+    let start = (start_seconds * spec.sample_rate as f64).round() as u64;
+    let context = (config.radius * config.passes.max(1)) as u64;
+    let first = start.saturating_sub(context);
+    let end = start + len as u64 + context;
+    let before = decoder.read_range(first, end)?;
+    let region_start = (start - first) as usize;
+    let available = before.first().map_or(0, Vec::len);
+    if available <= region_start {
+        return Err(invalid_data(
+            "the region starts after the end of the file".to_owned(),
+        ));
+    }
+    let region_end = (region_start + len).min(available);
+    let after: Vec<Vec<i32>> = before
+        .iter()
+        .map(|samples| clean_data_with(samples, config))
+        .collect();
+
+    write!(writer, "sample,seconds")?;
+    for channel in 1..=spec.num_channels {
+        write!(writer, ",before_{channel},after_{channel}")?;
+    }
+    writeln!(writer)?;
+    for index in region_start..region_end {
+        let position = first + index as u64;
+        write!(
+            writer,
+            "{position},{:.6}",
+            position as f64 / spec.sample_rate as f64
+        )?;
+        for (before, after) in before.iter().zip(&after) {
+            write!(writer, ",{},{}", before[index], after[index])?;
+        }
+        writeln!(writer)?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::flac_encoder::FlacWriter;
+    use std::fs;
+
+    #[test]
+    fn dumps_both_versions() {
+        let path = std::env::temp_dir().join(format!("depop-dump-{}.flac", std::process::id()));
+        let mut samples: Vec<i32> = (0..2000).map(|i| (i % 20) * 10).collect();
+        samples[1005] = 30_000;
+        let mut writer = FlacWriter::new(
+            io::BufWriter::new(fs::File::create(&path).unwrap()),
+            1000,
+            16,
+            1,
+            0,
+        )
+        .unwrap();
+        writer.write(&[&samples]).unwrap();
+        writer.finish().unwrap();
+
+        let mut csv = Vec::new();
+        dump_region(&path, 1.004, 3, &CleanConfig::default(), &mut csv).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "sample,seconds,before_1,after_1");
+        assert_eq!(lines[1], "1004,1.004000,40,40");
+        assert!(lines[2].starts_with("1005,1.005000,30000,"));
+        assert!(!lines[2].ends_with(",30000"));
+    }
+}
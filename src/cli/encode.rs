@@ -0,0 +1,241 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Stdout, Write};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::bit_depth::container_bits;
+use super::decode::Spec;
+use super::flac_encoder::FlacWriter;
+use super::is_stdio;
+use super::metadata::Metadata;
+use super::wav_writer::{SampleFormat, WavWriter};
+
+/// The output formats the command line tool can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Losslessly compressed FLAC
+    Flac,
+    /// WAV with integer samples
+    Wav,
+    /// WAV with 32-bit floating point samples, which DAWs can take in without requantizing
+    FloatWav,
+}
+
+impl OutputFormat {
+    /// Files ending in `.flac` are written as FLAC, everything else as WAV. This includes standard
+    /// output.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("flac") => OutputFormat::Flac,
+            _ => OutputFormat::Wav,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Flac => "flac",
+            OutputFormat::Wav | OutputFormat::FloatWav => "wav",
+        }
+    }
+}
+
+/// Where the encoded audio goes. Both formats go back to their header in files once the length is
+/// known. Standard output can't seek, so it's streamed with the length left unknown instead.
+pub enum Output {
+    File(BufWriter<File>),
+    Stdout(BufWriter<Stdout>),
+}
+
+impl Output {
+    /// Create `path`, or write to standard output if `path` is `-`.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if is_stdio(path) {
+            Ok(Output::Stdout(BufWriter::new(io::stdout())))
+        } else {
+            Ok(Output::File(BufWriter::new(File::create(path)?)))
+        }
+    }
+
+    /// Whether the encoders can go back to fill in the header.
+    pub fn is_seekable(&self) -> bool {
+        matches!(self, Output::File(_))
+    }
+
+    /// Flush everything to its destination.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Output::File(mut file) => file.flush(),
+            Output::Stdout(mut stdout) => stdout.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::File(file) => file.write(buf),
+            Output::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(file) => file.flush(),
+            Output::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
+
+impl Seek for Output {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Output::File(file) => file.seek(pos),
+            Output::Stdout(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "standard output can't seek",
+            )),
+        }
+    }
+}
+
+/// Encodes audio a chunk at a time into an [`Output`].
+pub enum Encoder<'a> {
+    Flac(FlacWriter<&'a mut Output>),
+    /// WAV only has whole-byte samples, so depths like 20 bits are shifted up by `shift` into
+    /// the next size, which keeps their level.
+    Wav {
+        writer: WavWriter<&'a mut Output>,
+        shift: u16,
+    },
+    /// Integer samples are divided by `scale` to bring them into the -1 to 1 range.
+    FloatWav {
+        writer: WavWriter<&'a mut Output>,
+        scale: f32,
+    },
+}
+
+impl<'a> Encoder<'a> {
+    /// Start writing audio with the given `spec`. `compression_level` only applies to FLAC, and
+    /// each format only takes the parts of `metadata` it can hold. Floating point output is always
+    /// 32 bits, `spec.bits_per_sample` is the depth of the integer samples that get converted.
+    pub fn new(
+        output: &'a mut Output,
+        format: OutputFormat,
+        spec: Spec,
+        compression_level: u8,
+        metadata: &Metadata,
+    ) -> io::Result<Self> {
+        let seekable = output.is_seekable();
+        let bext = metadata.bext.as_ref().map(|bext| bext.to_bytes());
+        let chunks: Vec<(&[u8; 4], &[u8])> =
+            bext.iter().map(|bext| (b"bext", bext.as_slice())).collect();
+        match format {
+            // 32-bit FLAC only became part of the format recently, and most decoders including
+            // the one used here still reject it
+            OutputFormat::Flac if spec.bits_per_sample > 24 => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "FLAC output is limited to 24 bits per sample, use WAV or --bit-depth 24",
+            )),
+            OutputFormat::Flac => {
+                let start = if seekable {
+                    FlacWriter::new
+                } else {
+                    FlacWriter::streaming
+                };
+                let mut writer = start(
+                    output,
+                    spec.sample_rate,
+                    spec.bits_per_sample,
+                    spec.num_channels,
+                    compression_level,
+                )?;
+                writer.write_metadata(&metadata.flac_blocks)?;
+                Ok(Encoder::Flac(writer))
+            }
+            OutputFormat::Wav if !(1..=32).contains(&spec.bits_per_sample) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "WAV supports up to 32 bits per sample, not {}",
+                    spec.bits_per_sample
+                ),
+            )),
+            OutputFormat::Wav => {
+                let bits_per_sample = container_bits(spec.bits_per_sample);
+                let start = if seekable {
+                    WavWriter::new
+                } else {
+                    WavWriter::streaming
+                };
+                let writer = start(
+                    output,
+                    spec.num_channels as u16,
+                    spec.sample_rate,
+                    SampleFormat::Int(bits_per_sample),
+                    &chunks,
+                )?;
+                Ok(Encoder::Wav {
+                    writer,
+                    shift: bits_per_sample - spec.bits_per_sample,
+                })
+            }
+            OutputFormat::FloatWav => {
+                let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+                let start = if seekable {
+                    WavWriter::new
+                } else {
+                    WavWriter::streaming
+                };
+                let writer = start(
+                    output,
+                    spec.num_channels as u16,
+                    spec.sample_rate,
+                    SampleFormat::Float,
+                    &chunks,
+                )?;
+                Ok(Encoder::FloatWav { writer, scale })
+            }
+        }
+    }
+
+    /// Append samples, with one `Vec` per channel.
+    pub fn write(&mut self, channels: &[Vec<i32>]) -> io::Result<()> {
+        match self {
+            Encoder::Flac(writer) => {
+                let channels: Vec<&[i32]> = channels.iter().map(Vec::as_slice).collect();
+                writer.write(&channels)
+            }
+            Encoder::Wav { writer, shift: 0 } => writer.write_int(channels),
+            Encoder::Wav { writer, shift } => {
+                let shifted: Vec<Vec<i32>> = channels
+                    .iter()
+                    .map(|samples| samples.iter().map(|sample| sample << *shift).collect())
+                    .collect();
+                writer.write_int(&shifted)
+            }
+            Encoder::FloatWav { writer, scale } => {
+                let converted: Vec<Vec<f32>> = channels
+                    .iter()
+                    .map(|samples| {
+                        samples
+                            .iter()
+                            .map(|&sample| sample as f32 / *scale)
+                            .collect()
+                    })
+                    .collect();
+                writer.write_float(&converted)
+            }
+        }
+    }
+
+    /// Fill in the header now that the length is known.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Encoder::Flac(writer) => writer.finish().map(|_| ()),
+            Encoder::Wav { writer, .. } | Encoder::FloatWav { writer, .. } => {
+                writer.finish().map(|_| ())
+            }
+        }
+    }
+}
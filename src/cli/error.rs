@@ -0,0 +1,75 @@
+//! The ways a run of the command line tool can fail, and the exit codes they're reported with, so
+//! scripts can tell a broken input from a full disk.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The arguments, or the settings they point to, don't make sense.
+    #[error("{0}")]
+    Usage(String),
+    /// The input is in a format or uses a codec that can't be decoded, or the output can't be
+    /// written in the format that was asked for.
+    #[error("{}: {source}", path.display())]
+    Unsupported { path: PathBuf, source: io::Error },
+    /// The input could not be read or decoded, or turned out to be damaged.
+    #[error("{}: {source}", path.display())]
+    Decode { path: PathBuf, source: io::Error },
+    /// An output file could not be written.
+    #[error("{}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+    /// Anything else, like the worker threads not starting.
+    #[error("{0}")]
+    Other(io::Error),
+}
+
+impl Error {
+    /// An error that came up while reading `path`.
+    pub fn decode(path: &Path, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::Unsupported => Self::Unsupported {
+                path: path.to_owned(),
+                source,
+            },
+            _ => Self::Decode {
+                path: path.to_owned(),
+                source,
+            },
+        }
+    }
+
+    /// An error that came up while writing `path`.
+    pub fn write(path: &Path, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::Unsupported => Self::Unsupported {
+                path: path.to_owned(),
+                source,
+            },
+            _ => Self::Write {
+                path: path.to_owned(),
+                source,
+            },
+        }
+    }
+
+    /// The code the process exits with. 2 matches the code of clap's own usage errors.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Other(_) => 1,
+            Error::Usage(_) => 2,
+            Error::Decode { .. } => 3,
+            Error::Unsupported { .. } => 4,
+            Error::Write { .. } => 5,
+        }
+    }
+}
+
+impl From<&Error> for ExitCode {
+    fn from(error: &Error) -> Self {
+        ExitCode::from(error.exit_code())
+    }
+}
@@ -0,0 +1,121 @@
+//! Replacing files with their cleaned versions for `--in-place`. The cleaned audio is written to a
+//! temporary file next to the original first, so a failure halfway through never leaves a file
+//! half cleaned, and the original is kept as a backup.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::encode::OutputFormat;
+
+/// The format a file cleaned in place is written in. It has to match the file's extension, so only
+/// FLAC and WAV files can be cleaned in place. `requested` is the `--format`, if one was given.
+pub fn format(input: &Path, requested: Option<OutputFormat>) -> io::Result<OutputFormat> {
+    let extension = input
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match (extension.as_str(), requested) {
+        ("flac", None | Some(OutputFormat::Flac)) => Ok(OutputFormat::Flac),
+        ("wav" | "wave", None) => Ok(OutputFormat::Wav),
+        ("wav" | "wave", Some(format @ (OutputFormat::Wav | OutputFormat::FloatWav))) => Ok(format),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only FLAC and WAV files can be cleaned in place, and they have to keep their format",
+        )),
+    }
+}
+
+/// The hidden file the cleaned audio is written to before it replaces `path`. Batches write their
+/// outputs this way too.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.depop-tmp"))
+}
+
+/// Where the original of `input` is kept: `NAME.bak` next to it, or `name` under `backup_dir`.
+pub fn backup_path(input: &Path, name: &Path, backup_dir: Option<&Path>) -> PathBuf {
+    match backup_dir {
+        Some(backup_dir) => backup_dir.join(name),
+        None => {
+            let name = input.file_name().unwrap_or_default().to_string_lossy();
+            input.with_file_name(format!("{name}.bak"))
+        }
+    }
+}
+
+/// Move `input` to `backup`, and the cleaned audio in `temp` to where `input` was. An existing
+/// backup is never overwritten, as it may be the only untouched copy left from an earlier run.
+pub fn replace(input: &Path, temp: &Path, backup: &Path) -> io::Result<()> {
+    if backup.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("the backup {} already exists", backup.display()),
+        ));
+    }
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    move_file(input, backup)?;
+    if let Err(error) = fs::rename(temp, input) {
+        // Put the original back rather than leaving nothing at all
+        move_file(backup, input)?;
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+/// Rename a file, falling back to copying it for backup directories on other file systems.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_original_as_a_backup() {
+        let root = std::env::temp_dir().join(format!("depop-in-place-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let input = root.join("capture.flac");
+        let temp = temp_path(&input);
+        let backup = backup_path(&input, Path::new("capture.flac"), None);
+        fs::write(&input, b"original").unwrap();
+        fs::write(&temp, b"cleaned").unwrap();
+
+        replace(&input, &temp, &backup).unwrap();
+        let cleaned = fs::read(&input).unwrap();
+        let original = fs::read(&backup).unwrap();
+        fs::write(&temp, b"cleaned again").unwrap();
+        let second_run = replace(&input, &temp, &backup);
+        let after_second_run = fs::read(&backup).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(backup, root.join("capture.flac.bak"));
+        assert_eq!(cleaned, b"cleaned");
+        assert_eq!(original, b"original");
+        assert_eq!(second_run.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(after_second_run, b"original");
+    }
+
+    #[test]
+    fn formats() {
+        let path = Path::new;
+        assert_eq!(format(path("a.FLAC"), None).unwrap(), OutputFormat::Flac);
+        assert_eq!(
+            format(path("a.wav"), Some(OutputFormat::FloatWav)).unwrap(),
+            OutputFormat::FloatWav
+        );
+        assert!(format(path("a.wav"), Some(OutputFormat::Flac)).is_err());
+        assert!(format(path("a.mp3"), None).is_err());
+    }
+}
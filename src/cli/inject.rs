@@ -0,0 +1,189 @@
+//! Synthetic pops for the `inject` subcommand. Adding pops to a clean recording and checking how
+//! many of them come back out is the quickest way to compare settings, and the pops are placed by a
+//! seeded generator so the same seed always corrupts a file the same way.
+
+use super::clean::Repair;
+
+/// Where and how hard to hit a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InjectConfig {
+    pub count: usize,
+    /// The size of each pop relative to full scale.
+    pub amplitude: f64,
+    /// How many samples in a row each pop covers.
+    pub width: usize,
+    pub seed: u64,
+}
+
+/// A pop that will be added to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pop {
+    /// The position of the first sample of the pop in its channel.
+    pub position: u64,
+    pub channel: usize,
+    pub width: usize,
+    /// What gets added to each of the samples, which is clipped at full scale.
+    pub offset: i32,
+}
+
+/// Pick the places for `config.count` pops in a file with `num_frames` samples per channel, sorted
+/// by their position. Pops never touch or overlap each other, and stay clear of the very start and
+/// end of the file where there's nothing to compare them with. Returns fewer pops if the file is
+/// too short to fit them all.
+pub fn plan_pops(
+    num_frames: u64,
+    num_channels: usize,
+    bits_per_sample: u16,
+    config: &InjectConfig,
+) -> Vec<Pop> {
+    let mut rng = SplitMix64(config.seed);
+    let width = config.width.max(1) as u64;
+    let margin = width + 2;
+    let magnitude = (config.amplitude * (1u64 << (bits_per_sample - 1)) as f64).round() as i32;
+
+    let mut pops: Vec<Pop> = Vec::new();
+    if num_frames <= 2 * margin || num_channels == 0 {
+        return pops;
+    }
+    // Crowded files give up on the last few pops rather than looking for space forever
+    for _ in 0..config.count * 100 {
+        if pops.len() == config.count {
+            break;
+        }
+
+        let position = margin + rng.below(num_frames - 2 * margin);
+        let channel = rng.below(num_channels as u64) as usize;
+        let clear = pops
+            .iter()
+            .all(|pop| pop.channel != channel || position.abs_diff(pop.position) > width + margin);
+        if clear {
+            let offset = if rng.below(2) == 0 {
+                magnitude
+            } else {
+                -magnitude
+            };
+            pops.push(Pop {
+                position,
+                channel,
+                width: width as usize,
+                offset,
+            });
+        }
+    }
+
+    pops.sort_by_key(|pop| (pop.position, pop.channel));
+    pops
+}
+
+/// Add the pops to `channels`, which start at `position`, and note down every sample that was
+/// changed. Pops can span more than one chunk.
+pub fn apply_pops(
+    pops: &[Pop],
+    position: u64,
+    channels: &mut [Vec<i32>],
+    bits_per_sample: u16,
+    changes: &mut Vec<Repair>,
+) {
+    let min = -(1i64 << (bits_per_sample - 1));
+    let max = (1i64 << (bits_per_sample - 1)) - 1;
+    let len = channels.first().map_or(0, Vec::len) as u64;
+    for pop in pops {
+        let pop_end = pop.position + pop.width as u64;
+        if pop_end <= position || pop.position >= position + len {
+            continue;
+        }
+
+        for sample_position in pop.position.max(position)..pop_end.min(position + len) {
+            let sample = &mut channels[pop.channel][(sample_position - position) as usize];
+            let original = *sample;
+            *sample = (original as i64 + pop.offset as i64).clamp(min, max) as i32;
+            changes.push(Repair {
+                position: sample_position,
+                channel: pop.channel,
+                original,
+                replacement: *sample,
+            });
+        }
+    }
+}
+
+/// A small and fast generator that's good enough for placing pops and making test noise. See
+/// <https://prng.di.unimi.it/splitmix64.c>.
+pub struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `bound`. The slight bias towards small numbers doesn't matter here.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    /// A number from 0 up to but not including 1.
+    pub fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: InjectConfig = InjectConfig {
+        count: 20,
+        amplitude: 0.5,
+        width: 2,
+        seed: 7,
+    };
+
+    #[test]
+    fn pops_are_reproducible_and_apart() {
+        let pops = plan_pops(10_000, 2, 16, &CONFIG);
+        assert_eq!(pops, plan_pops(10_000, 2, 16, &CONFIG));
+        assert_ne!(
+            pops,
+            plan_pops(10_000, 2, 16, &InjectConfig { seed: 8, ..CONFIG })
+        );
+
+        assert_eq!(pops.len(), 20);
+        for (index, pop) in pops.iter().enumerate() {
+            assert_eq!(pop.offset.abs(), 16_384);
+            assert!(pop.position >= 4 && pop.position + 2 <= 10_000 - 4);
+            for other in &pops[index + 1..] {
+                assert!(pop.channel != other.channel || other.position > pop.position + 2);
+            }
+        }
+    }
+
+    #[test]
+    fn pops_span_chunks() {
+        let pops = [Pop {
+            position: 9,
+            channel: 1,
+            width: 3,
+            offset: -20_000,
+        }];
+        let mut changes = Vec::new();
+        let mut first = vec![vec![0; 10], vec![-20_000; 10]];
+        let mut second = vec![vec![0; 10], vec![100; 10]];
+        apply_pops(&pops, 0, &mut first, 16, &mut changes);
+        apply_pops(&pops, 10, &mut second, 16, &mut changes);
+
+        assert_eq!(first[1][9], -32_768);
+        assert_eq!(&second[1][..3], [-19_900, -19_900, 100]);
+        assert_eq!(first[0], vec![0; 10]);
+        assert_eq!(
+            changes
+                .iter()
+                .map(|change| change.position)
+                .collect::<Vec<_>>(),
+            [9, 10, 11]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Audacity label tracks and REAPER regions marking the repairs, so they can be checked one by one
+//! by ear.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::clean::Repair;
+
+/// Repairs closer together than this are marked with a single label, as they're heard as one
+/// click.
+const MERGE_SECONDS: f64 = 0.01;
+
+/// A region of the file with repairs in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Group `repairs`, which are sorted by position, into the clicks they'd be heard as.
+pub fn clicks(repairs: &[Repair], sample_rate: u32) -> Vec<&[Repair]> {
+    let max_gap = (MERGE_SECONDS * sample_rate as f64).round() as u64;
+    let mut clicks = Vec::new();
+    let mut start = 0;
+    while start < repairs.len() {
+        let mut end = start + 1;
+        while end < repairs.len() && repairs[end].position - repairs[end - 1].position <= max_gap {
+            end += 1;
+        }
+        clicks.push(&repairs[start..end]);
+        start = end;
+    }
+
+    clicks
+}
+
+/// Turn `repairs`, which are sorted by position, into one label per click. Each label starts at
+/// its first repaired sample and ends after its last one.
+pub fn labels(repairs: &[Repair], sample_rate: u32) -> Vec<Label> {
+    clicks(repairs, sample_rate)
+        .into_iter()
+        .map(|click| label(click, sample_rate))
+        .collect()
+}
+
+fn label(repairs: &[Repair], sample_rate: u32) -> Label {
+    let mut channels: Vec<usize> = repairs.iter().map(|repair| repair.channel + 1).collect();
+    channels.sort_unstable();
+    channels.dedup();
+    let channels = channels
+        .iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let samples = if repairs.len() == 1 {
+        "1 sample".to_owned()
+    } else {
+        format!("{} samples", repairs.len())
+    };
+    let channel_word = if channels.contains(',') {
+        "channels"
+    } else {
+        "channel"
+    };
+
+    Label {
+        start: repairs[0].position as f64 / sample_rate as f64,
+        end: (repairs[repairs.len() - 1].position + 1) as f64 / sample_rate as f64,
+        text: format!("{samples} in {channel_word} {channels}"),
+    }
+}
+
+/// Write the repairs to `path` as a label track that Audacity can import: one label per line,
+/// with its start and end in seconds and its text separated by tabs.
+pub fn write(path: &Path, repairs: &[Repair], sample_rate: u32) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for label in labels(repairs, sample_rate) {
+        writeln!(
+            writer,
+            "{:.6}\t{:.6}\t{}",
+            label.start, label.end, label.text
+        )?;
+    }
+
+    writer.flush()
+}
+
+/// Write the repairs to `path` as a CSV file that REAPER's Region/Marker Manager can import, with
+/// one region per click.
+pub fn write_reaper(path: &Path, repairs: &[Repair], sample_rate: u32) -> io::Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["#", "Name", "Start", "End", "Length"])?;
+    for (index, label) in labels(repairs, sample_rate).into_iter().enumerate() {
+        // Rounded first so the length adds up
+        let start = (label.start * 1_000_000.0).round() as u64;
+        let end = (label.end * 1_000_000.0).round() as u64;
+        writer.write_record([
+            format!("R{}", index + 1),
+            label.text,
+            reaper_time(start),
+            reaper_time(end),
+            reaper_time(end - start),
+        ])?;
+    }
+
+    writer.flush()
+}
+
+/// Format a time in microseconds as `h:mm:ss.ssssss`. REAPER reads plain numbers in the project's
+/// ruler units, which may be beats, and clicks need more precision than milliseconds.
+fn reaper_time(micros: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.{:06}",
+        micros / 3_600_000_000,
+        micros / 60_000_000 % 60,
+        micros / 1_000_000 % 60,
+        micros % 1_000_000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repair(position: u64, channel: usize) -> Repair {
+        Repair {
+            position,
+            channel,
+            original: 0,
+            replacement: 0,
+        }
+    }
+
+    #[test]
+    fn merges_nearby_repairs() {
+        let repairs = [
+            repair(100, 0),
+            repair(101, 1),
+            repair(500, 0),
+            repair(1000, 1),
+        ];
+        let labels = labels(&repairs, 1000);
+        assert_eq!(
+            labels,
+            [
+                Label {
+                    start: 0.1,
+                    end: 0.102,
+                    text: "2 samples in channels 1, 2".to_owned(),
+                },
+                Label {
+                    start: 0.5,
+                    end: 0.501,
+                    text: "1 sample in channel 1".to_owned(),
+                },
+                Label {
+                    start: 1.0,
+                    end: 1.001,
+                    text: "1 sample in channel 2".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_reaper_regions() {
+        let path = std::env::temp_dir().join(format!("depop-regions-{}.csv", std::process::id()));
+        write_reaper(
+            &path,
+            &[repair(100, 0), repair(101, 1), repair(3_726_000, 0)],
+            1000,
+        )
+        .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            csv,
+            "#,Name,Start,End,Length\n\
+             R1,\"2 samples in channels 1, 2\",0:00:00.100000,0:00:00.102000,0:00:00.002000\n\
+             R2,1 sample in channel 1,1:02:06.000000,1:02:06.001000,0:00:00.001000\n"
+        );
+    }
+}
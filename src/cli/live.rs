@@ -0,0 +1,481 @@
+//! Cleaning the audio from a sound card as it's recorded, and playing the result back right away.
+//! This lets a transfer be monitored, or recorded through another program, with the clicks already
+//! gone, without having to load the plugin in a DAW.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, FromSample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::decode::Spec;
+
+/// The bit depth the devices' samples are cleaned at, whatever their own format is.
+pub const BITS_PER_SAMPLE: u16 = 24;
+
+/// How much recorded audio may wait to be cleaned, in seconds. Anything recorded while the queue
+/// is full is dropped.
+const MAX_RECORDED_SECONDS: f64 = 1.0;
+/// How much audio may queue up for playback, in seconds. The two devices' clocks never run at
+/// exactly the same speed, so when the output falls this far behind the cleaned audio is dropped.
+const MAX_QUEUED_SECONDS: f64 = 0.5;
+/// How often the recording is checked for new audio. This is far shorter than a device's buffer.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The names of the devices that can record, and of those that can play.
+pub fn device_names() -> io::Result<(Vec<String>, Vec<String>)> {
+    let host = cpal::default_host();
+    let inputs = host.input_devices().map_err(other)?;
+    let outputs = host.output_devices().map_err(other)?;
+
+    Ok((
+        inputs.filter_map(|device| device.name().ok()).collect(),
+        outputs.filter_map(|device| device.name().ok()).collect(),
+    ))
+}
+
+/// The input device called `name`, or the system's default one.
+pub fn input_device(name: Option<&str>) -> io::Result<Device> {
+    let host = cpal::default_host();
+    match name {
+        Some(name) => find_device(host.input_devices().map_err(other)?, name),
+        None => host
+            .default_input_device()
+            .ok_or_else(|| not_found("There is no default input device".to_owned())),
+    }
+}
+
+/// The output device called `name`, or the system's default one.
+pub fn output_device(name: Option<&str>) -> io::Result<Device> {
+    let host = cpal::default_host();
+    match name {
+        Some(name) => find_device(host.output_devices().map_err(other)?, name),
+        None => host
+            .default_output_device()
+            .ok_or_else(|| not_found("There is no default output device".to_owned())),
+    }
+}
+
+fn find_device(mut devices: impl Iterator<Item = Device>, name: &str) -> io::Result<Device> {
+    devices
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+        .ok_or_else(|| not_found(format!("There is no device called '{name}'")))
+}
+
+/// A fixed size queue of samples from one thread to another, like from a device's callback to the
+/// cleaning. Neither side ever blocks or allocates: the writer drops what doesn't fit, and the
+/// reader takes whatever is there.
+///
+/// There must only ever be a single writer and a single reader.
+///
+/// This isn't the plugin's `SampleRing` because that makes the opposite trade-off. The ring's
+/// writer overwrites samples the reader hasn't picked up yet and the reader skips over them, which
+/// is fine for drawing the editor's waveform but would leave holes in the audio here. Cleaning and
+/// playback need every sample in order, so the writer has to know how much room is left and drop
+/// the newest samples instead, which the ring can't tell it.
+#[derive(Debug)]
+struct Fifo {
+    /// Each sample as [`f32::to_bits()`], since the queue is shared without a lock.
+    samples: Box<[AtomicU32]>,
+    /// The total number of samples written so far.
+    written: AtomicU64,
+    /// The total number of samples read so far.
+    read: AtomicU64,
+}
+
+impl Fifo {
+    fn new(capacity: usize) -> Self {
+        Self {
+            samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicU64::new(0),
+            read: AtomicU64::new(0),
+        }
+    }
+
+    /// The number of samples waiting to be read.
+    fn len(&self) -> usize {
+        let read = self.read.load(Ordering::Acquire);
+        (self.written.load(Ordering::Acquire) - read) as usize
+    }
+
+    /// Append as many whole frames of `frame_len` samples as fit, and return the number of samples
+    /// that were written. The rest are dropped.
+    fn push(&self, samples: impl ExactSizeIterator<Item = f32>, frame_len: usize) -> usize {
+        let written = self.written.load(Ordering::Relaxed);
+        let free = self.samples.len() - (written - self.read.load(Ordering::Acquire)) as usize;
+        let len = samples.len().min(free - free % frame_len);
+        for (position, sample) in (written..).zip(samples.take(len)) {
+            self.slot(position)
+                .store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.written.store(written + len as u64, Ordering::Release);
+
+        len
+    }
+
+    /// Pass up to `max_len` of the waiting samples to `f` in order, and return how many there were.
+    fn pop(&self, max_len: usize, mut f: impl FnMut(f32)) -> usize {
+        let read = self.read.load(Ordering::Relaxed);
+        let len = ((self.written.load(Ordering::Acquire) - read) as usize).min(max_len);
+        for position in read..read + len as u64 {
+            f(f32::from_bits(self.slot(position).load(Ordering::Relaxed)));
+        }
+        self.read.store(read + len as u64, Ordering::Release);
+
+        len
+    }
+
+    fn slot(&self, position: u64) -> &AtomicU32 {
+        &self.samples[(position % self.samples.len() as u64) as usize]
+    }
+}
+
+/// What the device callbacks share with the cleaning.
+#[derive(Debug)]
+struct Shared {
+    /// Interleaved samples with the input device's channel count, waiting to be cleaned.
+    recorded: Fifo,
+    /// Interleaved samples with the output device's channel count, waiting to be played.
+    playback: Fifo,
+    /// The number of recorded samples that were dropped because the cleaning fell behind.
+    dropped: AtomicU64,
+    /// Set when the input device went away.
+    stopped: AtomicBool,
+}
+
+/// A recording and a playback stream that run until this is dropped. The recorded audio comes out
+/// of [`next_chunk()`][Self::next_chunk()], and whatever is passed to [`play()`][Self::play()]
+/// goes to the output device.
+pub struct Live {
+    spec: Spec,
+    shared: Arc<Shared>,
+    output_channels: usize,
+    // The streams stop when they're dropped
+    _input: Stream,
+    _output: Stream,
+}
+
+impl Live {
+    /// Start recording from `input` in its default configuration, and playing on `output` at the
+    /// same sample rate. Fails if the output can't play at that rate.
+    pub fn start(input: &Device, output: &Device) -> io::Result<Self> {
+        let input_config = input.default_input_config().map_err(other)?;
+        let sample_rate = input_config.sample_rate();
+        let output_config = output_config(output, sample_rate)?;
+        let spec = Spec {
+            sample_rate: sample_rate.0,
+            bits_per_sample: BITS_PER_SAMPLE,
+            num_channels: input_config.channels() as usize,
+        };
+        let output_channels = output_config.channels() as usize;
+
+        let capacity = |seconds: f64, num_channels: usize| {
+            (seconds * spec.sample_rate as f64) as usize * num_channels
+        };
+        let shared = Arc::new(Shared {
+            recorded: Fifo::new(capacity(MAX_RECORDED_SECONDS, spec.num_channels)),
+            playback: Fifo::new(capacity(MAX_QUEUED_SECONDS, output_channels)),
+            dropped: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+        });
+        let input_stream = build_input(
+            input,
+            &input_config.config(),
+            input_config.sample_format(),
+            shared.clone(),
+        )?;
+        let output_stream = build_output(
+            output,
+            &output_config.config(),
+            output_config.sample_format(),
+            shared.clone(),
+        )?;
+        input_stream.play().map_err(other)?;
+        output_stream.play().map_err(other)?;
+
+        Ok(Self {
+            spec,
+            shared,
+            output_channels,
+            _input: input_stream,
+            _output: output_stream,
+        })
+    }
+
+    /// The format of the recorded audio.
+    pub fn spec(&self) -> Spec {
+        self.spec
+    }
+
+    /// Wait for the next stretch of recorded audio, with one `Vec` per channel. Returns `None` once
+    /// the input device has gone away.
+    pub fn next_chunk(&self) -> Option<Vec<Vec<i32>>> {
+        let num_channels = self.spec.num_channels;
+        loop {
+            let dropped = self.shared.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                log::warn!("Dropped {dropped} recorded samples the cleaning couldn't keep up with");
+            }
+
+            let len = self.shared.recorded.len();
+            let len = len - len % num_channels;
+            if len > 0 {
+                let mut samples = Vec::with_capacity(len);
+                self.shared.recorded.pop(len, |sample| samples.push(sample));
+                return Some(deinterleave(samples.into_iter(), num_channels));
+            }
+            if self.shared.stopped.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Queue audio with one `Vec` per recorded channel for playback. Outputs with more channels
+    /// than the input repeat the input's channels, so mono input plays on both sides.
+    pub fn play(&self, channels: &[Vec<i32>]) {
+        let dropped = queue(&self.shared.playback, channels, self.output_channels);
+        if dropped > 0 {
+            log::debug!("Dropped {dropped} samples the output couldn't keep up with");
+        }
+    }
+}
+
+/// Queue `channels` on `fifo` for an output with `num_channels` channels, and return the number of
+/// samples that didn't fit. Only whole frames are queued, so the channels stay in order.
+fn queue(fifo: &Fifo, channels: &[Vec<i32>], num_channels: usize) -> usize {
+    let samples = interleave(channels, num_channels);
+    samples.len() - fifo.push(samples.into_iter(), num_channels)
+}
+
+/// The output's configuration for playing at `sample_rate`, preferring its default channels and
+/// sample format. Fails with both rates in the message if the output can't play at that rate.
+fn output_config(
+    device: &Device,
+    sample_rate: SampleRate,
+) -> io::Result<cpal::SupportedStreamConfig> {
+    let default = device.default_output_config().map_err(other)?;
+    let supported: Vec<_> = device
+        .supported_output_configs()
+        .map_err(other)?
+        .filter(|range| {
+            range.min_sample_rate() <= sample_rate && sample_rate <= range.max_sample_rate()
+        })
+        .collect();
+    let range = supported
+        .iter()
+        .find(|range| {
+            range.channels() == default.channels()
+                && range.sample_format() == default.sample_format()
+        })
+        .or_else(|| supported.first())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "The input records at {} Hz, but the output device can't play at that rate \
+                     (it defaults to {} Hz)",
+                    sample_rate.0,
+                    default.sample_rate().0
+                ),
+            )
+        })?;
+
+    Ok((*range).with_sample_rate(sample_rate))
+}
+
+fn build_input(
+    device: &Device,
+    config: &StreamConfig,
+    format: SampleFormat,
+    shared: Arc<Shared>,
+) -> io::Result<Stream> {
+    match format {
+        SampleFormat::I8 => build_input_as::<i8>(device, config, shared),
+        SampleFormat::I16 => build_input_as::<i16>(device, config, shared),
+        SampleFormat::I32 => build_input_as::<i32>(device, config, shared),
+        SampleFormat::U8 => build_input_as::<u8>(device, config, shared),
+        SampleFormat::U16 => build_input_as::<u16>(device, config, shared),
+        SampleFormat::F32 => build_input_as::<f32>(device, config, shared),
+        SampleFormat::F64 => build_input_as::<f64>(device, config, shared),
+        format => Err(unsupported(format)),
+    }
+}
+
+fn build_input_as<T>(
+    device: &Device,
+    config: &StreamConfig,
+    shared: Arc<Shared>,
+) -> io::Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let num_channels = config.channels as usize;
+    let error_shared = shared.clone();
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let samples = data.iter().map(|&sample| f32::from_sample_(sample));
+                let written = shared.recorded.push(samples, num_channels);
+                shared
+                    .dropped
+                    .fetch_add((data.len() - written) as u64, Ordering::Relaxed);
+            },
+            move |error| {
+                log::warn!("Recording: {error}");
+                if matches!(error, cpal::StreamError::DeviceNotAvailable) {
+                    error_shared.stopped.store(true, Ordering::Relaxed);
+                }
+            },
+            None,
+        )
+        .map_err(other)
+}
+
+fn build_output(
+    device: &Device,
+    config: &StreamConfig,
+    format: SampleFormat,
+    shared: Arc<Shared>,
+) -> io::Result<Stream> {
+    match format {
+        SampleFormat::I8 => build_output_as::<i8>(device, config, shared),
+        SampleFormat::I16 => build_output_as::<i16>(device, config, shared),
+        SampleFormat::I32 => build_output_as::<i32>(device, config, shared),
+        SampleFormat::U8 => build_output_as::<u8>(device, config, shared),
+        SampleFormat::U16 => build_output_as::<u16>(device, config, shared),
+        SampleFormat::F32 => build_output_as::<f32>(device, config, shared),
+        SampleFormat::F64 => build_output_as::<f64>(device, config, shared),
+        format => Err(unsupported(format)),
+    }
+}
+
+fn build_output_as<T>(
+    device: &Device,
+    config: &StreamConfig,
+    shared: Arc<Shared>,
+) -> io::Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let mut outputs = data.iter_mut();
+                let len = outputs.len();
+                shared.playback.pop(len, |sample| {
+                    if let Some(output) = outputs.next() {
+                        *output = T::from_sample_(sample);
+                    }
+                });
+                // Silence until the cleaned audio catches up
+                for output in outputs {
+                    *output = T::from_sample_(0.0f32);
+                }
+            },
+            |error| log::warn!("Playback: {error}"),
+            None,
+        )
+        .map_err(other)
+}
+
+/// Split interleaved floating point samples into a `Vec` of integer samples per channel.
+fn deinterleave(samples: impl Iterator<Item = f32>, num_channels: usize) -> Vec<Vec<i32>> {
+    let full_scale = (1 << (BITS_PER_SAMPLE - 1)) as f32;
+    let mut channels = vec![Vec::new(); num_channels];
+    for (index, sample) in samples.enumerate() {
+        let sample = (sample * full_scale)
+            .round()
+            .clamp(-full_scale, full_scale - 1.0);
+        channels[index % num_channels].push(sample as i32);
+    }
+
+    channels
+}
+
+/// Interleave the integer samples in `channels` as floating point samples for an output with
+/// `num_channels` channels.
+fn interleave(channels: &[Vec<i32>], num_channels: usize) -> Vec<f32> {
+    let full_scale = (1 << (BITS_PER_SAMPLE - 1)) as f32;
+    let len = channels.first().map_or(0, Vec::len);
+    (0..len)
+        .flat_map(|index| {
+            (0..num_channels)
+                .map(move |channel| channels[channel % channels.len()][index] as f32 / full_scale)
+        })
+        .collect()
+}
+
+fn other(error: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::other(error)
+}
+
+fn not_found(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, message)
+}
+
+fn unsupported(format: SampleFormat) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("The device's {format} samples are not supported"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_device_and_channel_samples() {
+        let samples = [0.5, -0.5, 1.0, -1.0, 0.0, 0.25];
+        let channels = deinterleave(samples.into_iter(), 2);
+        assert_eq!(
+            channels,
+            [
+                vec![4_194_304, 8_388_607, 0],
+                vec![-4_194_304, -8_388_608, 2_097_152]
+            ]
+        );
+
+        // Full scale is one step short of 1 on the positive side
+        let full_scale = (1 << (BITS_PER_SAMPLE - 1)) as f32;
+        let mut expected = samples;
+        expected[2] = (full_scale - 1.0) / full_scale;
+        assert_eq!(interleave(&channels, 2), expected);
+    }
+
+    #[test]
+    fn mono_input_plays_on_both_sides() {
+        let mono = [vec![4_194_304, -8_388_608]];
+        assert_eq!(interleave(&mono, 2), [0.5, 0.5, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn playback_drops_whole_frames_that_dont_fit() {
+        // Room for two and a half stereo frames
+        let fifo = Fifo::new(5);
+        let channels = [vec![1, 2, 3], vec![-1, -2, -3]];
+        assert_eq!(queue(&fifo, &channels, 2), 2);
+
+        let mut played = Vec::new();
+        fifo.pop(usize::MAX, |sample| played.push(sample));
+        let full_scale = (1 << (BITS_PER_SAMPLE - 1)) as f32;
+        let expected: Vec<f32> = [1, -1, 2, -2]
+            .iter()
+            .map(|&sample| sample as f32 / full_scale)
+            .collect();
+        assert_eq!(played, expected);
+
+        // Played frames make room for new ones, which follow in order
+        assert_eq!(queue(&fifo, &[vec![4], vec![-4]], 2), 0);
+        played.clear();
+        fifo.pop(usize::MAX, |sample| played.push(sample));
+        assert_eq!(played, [4.0 / full_scale, -4.0 / full_scale]);
+    }
+}
@@ -0,0 +1,29 @@
+//! Messages about what the command line tool is doing, on standard error. Errors are always shown,
+//! status messages like each file's summary unless `--quiet` is given, and `-v` adds details about
+//! each file, like its format and the repairs per channel. `-vv` lists every single repair.
+
+use log::{Level, LevelFilter};
+use std::io::Write;
+
+/// Set up the logger for the `-v` flags given and `--quiet`. `RUST_LOG` still overrides the level,
+/// the way it does for other tools built on `env_logger`.
+pub fn init(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    // The libraries' debug messages are about their internals, which is just noise here
+    env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Warn))
+        .filter_module(env!("CARGO_CRATE_NAME"), level)
+        .parse_default_env()
+        .format(|buf, record| match record.level() {
+            // Status messages read like the tool's output always has
+            Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .init();
+}
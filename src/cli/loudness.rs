@@ -0,0 +1,301 @@
+//! Loudness measurement following ITU-R BS.1770-4 and EBU R128, for the records broadcast archives
+//! keep: the integrated loudness, the loudest three seconds (the maximum short-term loudness), and
+//! the true peak.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// The length of the blocks the loudness is gated in, in 100 ms steps.
+const MOMENTARY_STEPS: usize = 4;
+/// The length of the short-term window, in 100 ms steps.
+const SHORT_TERM_STEPS: usize = 30;
+/// Blocks quieter than this are left out of the integrated loudness altogether.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks this far below the loudness of the blocks above the absolute gate are left out as well.
+const RELATIVE_GATE: f64 = -10.0;
+/// The true peak is found by upsampling by this factor.
+const OVERSAMPLING: usize = 4;
+/// The length of each phase of the upsampling filter.
+const PHASE_LEN: usize = 16;
+
+/// The loudness of a file. All of these are `None` for silence, and the loudness for files that
+/// are shorter than the windows they're measured over.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Loudness {
+    #[serde(rename = "integrated_lufs")]
+    pub integrated: Option<f64>,
+    #[serde(rename = "max_short_term_lufs")]
+    pub max_short_term: Option<f64>,
+    #[serde(rename = "true_peak_dbtp")]
+    pub true_peak: Option<f64>,
+}
+
+/// The loudness of a file before and after cleaning.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LoudnessComparison {
+    pub input: Loudness,
+    pub output: Loudness,
+}
+
+/// Measures the loudness of a stream a chunk at a time.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    /// The value of a full scale sample.
+    full_scale: f64,
+    /// How much each channel counts towards the loudness.
+    weights: Vec<f64>,
+    /// The K-weighting filter of each channel, as two biquads.
+    filters: Vec<[Biquad; 2]>,
+    /// The upsampling filter for the true peak, split into its phases.
+    phases: Vec<[f64; PHASE_LEN]>,
+    /// The last `PHASE_LEN` samples of each channel, newest first.
+    histories: Vec<VecDeque<f64>>,
+    peak: f64,
+    step_len: usize,
+    /// The weighted sum of the squared samples in the current 100 ms step, and how many samples
+    /// per channel it has so far.
+    step_energy: f64,
+    step_samples: usize,
+    /// The mean square of the last few steps.
+    steps: VecDeque<f64>,
+    /// The mean square of every gating block, which overlap by 75%.
+    blocks: Vec<f64>,
+    max_short_term: Option<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(num_channels: usize, sample_rate: u32, bits_per_sample: u16) -> Self {
+        let weights = (0..num_channels)
+            .map(|channel| channel_weight(channel, num_channels))
+            .collect();
+
+        Self {
+            full_scale: (1u64 << (bits_per_sample - 1)) as f64,
+            weights,
+            filters: vec![k_weighting(sample_rate as f64); num_channels],
+            phases: upsampling_phases(),
+            histories: vec![VecDeque::from(vec![0.0; PHASE_LEN]); num_channels],
+            peak: 0.0,
+            step_len: (sample_rate as usize / 10).max(1),
+            step_energy: 0.0,
+            step_samples: 0,
+            steps: VecDeque::new(),
+            blocks: Vec::new(),
+            max_short_term: None,
+        }
+    }
+
+    /// Measure the next chunk, with one `Vec` per channel.
+    pub fn push(&mut self, channels: &[Vec<i32>]) {
+        let len = channels.first().map_or(0, Vec::len);
+        for index in 0..len {
+            for (channel, samples) in channels.iter().enumerate() {
+                let sample = samples[index] as f64 / self.full_scale;
+                self.measure_peak(channel, sample);
+
+                let [high_shelf, high_pass] = &mut self.filters[channel];
+                let filtered = high_pass.process(high_shelf.process(sample));
+                self.step_energy += self.weights[channel] * filtered * filtered;
+            }
+
+            self.step_samples += 1;
+            if self.step_samples == self.step_len {
+                self.finish_step();
+            }
+        }
+    }
+
+    pub fn finish(self) -> Loudness {
+        let above_absolute: Vec<f64> = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&block| loudness(block) > ABSOLUTE_GATE)
+            .collect();
+        let integrated = mean(&above_absolute).and_then(|mean_square| {
+            let relative_gate = loudness(mean_square) + RELATIVE_GATE;
+            let above_relative: Vec<f64> = above_absolute
+                .into_iter()
+                .filter(|&block| loudness(block) > relative_gate)
+                .collect();
+            mean(&above_relative).map(loudness)
+        });
+
+        Loudness {
+            integrated,
+            max_short_term: self
+                .max_short_term
+                .filter(|&mean_square| mean_square > 0.0)
+                .map(loudness),
+            true_peak: (self.peak > 0.0).then(|| 20.0 * self.peak.log10()),
+        }
+    }
+
+    fn measure_peak(&mut self, channel: usize, sample: f64) {
+        let history = &mut self.histories[channel];
+        history.pop_back();
+        history.push_front(sample);
+
+        for phase in &self.phases {
+            let value: f64 = phase.iter().zip(history.iter()).map(|(h, x)| h * x).sum();
+            self.peak = self.peak.max(value.abs());
+        }
+        self.peak = self.peak.max(sample.abs());
+    }
+
+    fn finish_step(&mut self) {
+        self.steps
+            .push_back(self.step_energy / self.step_len as f64);
+        self.step_energy = 0.0;
+        self.step_samples = 0;
+        if self.steps.len() > SHORT_TERM_STEPS {
+            self.steps.pop_front();
+        }
+
+        let steps = self.steps.make_contiguous();
+        if steps.len() >= MOMENTARY_STEPS {
+            self.blocks
+                .push(mean(&steps[steps.len() - MOMENTARY_STEPS..]).unwrap());
+        }
+        if steps.len() == SHORT_TERM_STEPS {
+            let short_term = mean(steps).unwrap();
+            self.max_short_term = Some(
+                self.max_short_term
+                    .map_or(short_term, |max| max.max(short_term)),
+            );
+        }
+    }
+}
+
+/// The loudness in LUFS of a weighted mean square.
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// How much a channel counts towards the loudness. In 5.1 audio the LFE channel is left out and
+/// the surround channels count a bit more, and every other channel counts the same.
+fn channel_weight(channel: usize, num_channels: usize) -> f64 {
+    match (num_channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4 | 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+/// The K-weighting filter from BS.1770, a high shelf followed by a high pass, for any sample rate.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let shelf_frequency = 1681.974450955533;
+    let shelf_gain = 3.999843853973347;
+    let shelf_q = 0.7071752369554196;
+    let k = (PI * shelf_frequency / sample_rate).tan();
+    let vh = 10f64.powf(shelf_gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / shelf_q + k * k;
+    let high_shelf = Biquad {
+        b: [
+            (vh + vb * k / shelf_q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / shelf_q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / shelf_q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    let pass_frequency = 38.13547087602444;
+    let pass_q = 0.5003270373238773;
+    let k = (PI * pass_frequency / sample_rate).tan();
+    let a0 = 1.0 + k / pass_q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / pass_q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    [high_shelf, high_pass]
+}
+
+/// A Hann windowed sinc filter that upsamples by `OVERSAMPLING`, split into one filter per phase.
+fn upsampling_phases() -> Vec<[f64; PHASE_LEN]> {
+    let len = PHASE_LEN * OVERSAMPLING;
+    let center = (len - 1) as f64 / 2.0;
+    let filter: Vec<f64> = (0..len)
+        .map(|tap| {
+            let x = (tap as f64 - center) / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            let window = 0.5 - 0.5 * (2.0 * PI * (tap as f64 + 0.5) / len as f64).cos();
+            sinc * window
+        })
+        .collect();
+
+    (0..OVERSAMPLING)
+        .map(|phase| std::array::from_fn(|index| filter[phase + index * OVERSAMPLING]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_a_sine() {
+        // A 997 Hz sine peaking at -20 dBFS in both channels, which should read close to -20 LUFS
+        let amplitude = 0.1 * 8_388_608.0;
+        let samples: Vec<i32> = (0..48_000 * 5)
+            .map(|i| (amplitude * (2.0 * PI * 997.0 * i as f64 / 48_000.0).sin()).round() as i32)
+            .collect();
+        let mut meter = LoudnessMeter::new(2, 48_000, 24);
+        for chunk in samples.chunks(1000) {
+            meter.push(&[chunk.to_vec(), chunk.to_vec()]);
+        }
+        let loudness = meter.finish();
+
+        assert!(
+            (loudness.integrated.unwrap() + 20.0).abs() < 0.1,
+            "{loudness:?}"
+        );
+        assert!(
+            (loudness.max_short_term.unwrap() + 20.0).abs() < 0.1,
+            "{loudness:?}"
+        );
+        assert!(
+            (loudness.true_peak.unwrap() + 20.0).abs() < 0.1,
+            "{loudness:?}"
+        );
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        let mut meter = LoudnessMeter::new(1, 44_100, 16);
+        meter.push(&[vec![0; 44_100 * 4]]);
+        let loudness = meter.finish();
+
+        assert_eq!(loudness.integrated, None);
+        assert_eq!(loudness.max_short_term, None);
+        assert_eq!(loudness.true_peak, None);
+    }
+}
@@ -0,0 +1,222 @@
+//! Carries the metadata of FLAC files over to the cleaned files, so tags, cover art, and cue sheets
+//! aren't lost along the way.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::bwf::Bext;
+use super::is_stdio;
+
+const APPLICATION: u8 = 2;
+const VORBIS_COMMENT: u8 = 4;
+const CUESHEET: u8 = 5;
+const PICTURE: u8 = 6;
+
+/// Where the lead-in and the number of tracks are in a CUESHEET block.
+const CUESHEET_LEAD_IN: usize = 128;
+const CUESHEET_NUM_TRACKS: usize = 395;
+/// The size of a track in a CUESHEET block without its index points, and of an index point.
+const CUESHEET_TRACK_LEN: usize = 36;
+const CUESHEET_INDEX_LEN: usize = 12;
+
+/// A FLAC metadata block, without its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataBlock {
+    pub kind: u8,
+    pub data: Vec<u8>,
+}
+
+/// Everything that's carried over from the input. Each output format only writes what it can hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// For FLAC output.
+    pub flac_blocks: Vec<MetadataBlock>,
+    /// For WAV output.
+    pub bext: Option<Bext>,
+}
+
+/// Read the metadata blocks worth keeping from `path`, which is everything except for the blocks
+/// that describe the audio stream itself. Files that aren't FLAC and standard input don't have any.
+pub fn read_flac_metadata(path: &Path) -> io::Result<Vec<MetadataBlock>> {
+    if is_stdio(path) {
+        return Ok(Vec::new());
+    }
+
+    read_blocks(BufReader::new(File::open(path)?))
+}
+
+/// Move the sample offsets in `blocks` from `input_rate` to `output_rate`, for audio that was
+/// resampled. Cue sheets are the only blocks with offsets, and ones that can't be read are dropped
+/// rather than left pointing at the wrong samples.
+pub fn resample_blocks(blocks: &mut Vec<MetadataBlock>, input_rate: u32, output_rate: u32) {
+    blocks.retain_mut(|block| {
+        block.kind != CUESHEET
+            || resample_cuesheet(&mut block.data, input_rate, output_rate).is_some()
+    });
+}
+
+fn resample_cuesheet(data: &mut [u8], input_rate: u32, output_rate: u32) -> Option<()> {
+    let resample = |data: &mut [u8], position: usize| -> Option<()> {
+        let bytes: &mut [u8; 8] = data.get_mut(position..position + 8)?.try_into().ok()?;
+        let offset = u64::from_be_bytes(*bytes) as u128 * output_rate as u128 / input_rate as u128;
+        *bytes = (offset as u64).to_be_bytes();
+        Some(())
+    };
+
+    resample(data, CUESHEET_LEAD_IN)?;
+    let num_tracks = *data.get(CUESHEET_NUM_TRACKS)?;
+    let mut position = CUESHEET_NUM_TRACKS + 1;
+    for _ in 0..num_tracks {
+        resample(data, position)?;
+        let num_indices = *data.get(position + CUESHEET_TRACK_LEN - 1)?;
+        position += CUESHEET_TRACK_LEN;
+        for _ in 0..num_indices {
+            resample(data, position)?;
+            position += CUESHEET_INDEX_LEN;
+        }
+    }
+    // The offsets of a CD's cue sheet have to fall on its frames, which only exist at 44.1 kHz
+    if output_rate != 44_100 {
+        data[CUESHEET_LEAD_IN + 8] &= 0x7F;
+    }
+
+    Some(())
+}
+
+fn read_blocks<R: Read + Seek>(mut reader: R) -> io::Result<Vec<MetadataBlock>> {
+    let mut magic = [0; 4];
+    if reader.read_exact(&mut magic).is_err() {
+        return Ok(Vec::new());
+    }
+
+    // Some taggers put an ID3 tag in front of the stream, despite the specification
+    if magic.starts_with(b"ID3") {
+        let mut header = [0; 6];
+        reader.read_exact(&mut header)?;
+        let size = header[2..]
+            .iter()
+            .fold(0, |size, &byte| (size << 7) | (byte & 0x7F) as u64);
+        reader.seek(SeekFrom::Current(size as i64))?;
+        reader.read_exact(&mut magic)?;
+    }
+    if &magic != b"fLaC" {
+        return Ok(Vec::new());
+    }
+
+    let mut blocks = Vec::new();
+    loop {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let kind = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]);
+
+        // The stream info, seek table, and padding are written anew for the cleaned audio
+        if matches!(kind, APPLICATION | VORBIS_COMMENT | CUESHEET | PICTURE) {
+            let mut data = vec![0; length as usize];
+            reader.read_exact(&mut data)?;
+            blocks.push(MetadataBlock { kind, data });
+        } else {
+            reader.seek(SeekFrom::Current(length as i64))?;
+        }
+
+        if is_last {
+            return Ok(blocks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use crate::cli::flac_encoder::FlacWriter;
+
+    #[test]
+    fn blocks_survive_a_round_trip() {
+        let mut comment = Vec::new();
+        comment.extend(4u32.to_le_bytes());
+        comment.extend(b"test");
+        comment.extend(1u32.to_le_bytes());
+        comment.extend(13u32.to_le_bytes());
+        comment.extend(b"TITLE=Side A!");
+        let blocks = vec![
+            MetadataBlock {
+                kind: VORBIS_COMMENT,
+                data: comment,
+            },
+            MetadataBlock {
+                kind: APPLICATION,
+                data: b"abcdsome data".to_vec(),
+            },
+        ];
+
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), 44_100, 16, 1, 5).unwrap();
+        writer.write_metadata(&blocks).unwrap();
+        writer.write(&[&[1, 2, 3, -4]]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(read_blocks(Cursor::new(bytes.clone())).unwrap(), blocks);
+
+        let mut reader = claxon::FlacReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.get_tag("TITLE").collect::<Vec<_>>(), ["Side A!"]);
+        let samples: Vec<i32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, [1, 2, 3, -4]);
+    }
+
+    #[test]
+    fn cue_sheets_follow_the_sample_rate() {
+        let mut cuesheet = vec![0; 128];
+        cuesheet.extend(88_200u64.to_be_bytes());
+        cuesheet.push(0x80);
+        cuesheet.extend([0; 258]);
+        cuesheet.push(2);
+        // A track with one index point, and the lead-out
+        for (offset, number, indices) in [(44_100u64, 1, &[441u64][..]), (441_000, 170, &[])] {
+            cuesheet.extend(offset.to_be_bytes());
+            cuesheet.push(number);
+            cuesheet.extend([0; 26]);
+            cuesheet.push(indices.len() as u8);
+            for &index in indices {
+                cuesheet.extend(index.to_be_bytes());
+                cuesheet.extend([1, 0, 0, 0]);
+            }
+        }
+        let tags = MetadataBlock {
+            kind: VORBIS_COMMENT,
+            data: vec![0; 8],
+        };
+        let mut blocks = vec![
+            tags.clone(),
+            MetadataBlock {
+                kind: CUESHEET,
+                data: cuesheet.clone(),
+            },
+            MetadataBlock {
+                kind: CUESHEET,
+                data: cuesheet[..400].to_vec(),
+            },
+        ];
+        resample_blocks(&mut blocks, 44_100, 48_000);
+
+        let offset = |position: usize| {
+            u64::from_be_bytes(blocks[1].data[position..position + 8].try_into().unwrap())
+        };
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0], tags);
+        assert_eq!(offset(128), 96_000);
+        assert_eq!(blocks[1].data[136], 0);
+        assert_eq!(offset(396), 48_000);
+        assert_eq!(offset(432), 480);
+        assert_eq!(offset(444), 480_000);
+        assert_eq!(blocks[1].data.len(), cuesheet.len());
+    }
+
+    #[test]
+    fn other_formats_have_no_blocks() {
+        let blocks = read_blocks(Cursor::new(b"RIFF\0\0\0\0WAVE".to_vec())).unwrap();
+        assert!(blocks.is_empty());
+    }
+}
@@ -0,0 +1,54 @@
+//! Peak normalization for `--normalize`. Big pops usually set a recording's peak, so once they're
+//! gone the audio can be brought back up to a sensible level.
+
+/// Parse the `--normalize` level in dBFS, which can't go above full scale.
+pub fn parse_level(value: &str) -> Result<f64, String> {
+    let level: f64 = value.parse().map_err(|error| format!("{error}"))?;
+    if !(level <= 0.0 && level.is_finite()) {
+        return Err("must be a number of dBFS no higher than 0".to_owned());
+    }
+
+    Ok(level)
+}
+
+/// The gain that brings audio with the given `peak` value to `level` dBFS, at `bits_per_sample`.
+/// Silence is left alone.
+pub fn gain(peak: u32, bits_per_sample: u16, level: f64) -> f64 {
+    if peak == 0 {
+        return 1.0;
+    }
+
+    let full_scale = (1u64 << (bits_per_sample - 1)) as f64;
+    10f64.powf(level / 20.0) * full_scale / peak as f64
+}
+
+/// Apply `gain` to samples with `bits_per_sample` bits, rounding and clipping the results.
+pub fn apply_gain(samples: &mut [i32], gain: f64, bits_per_sample: u16) {
+    let min = -(1i64 << (bits_per_sample - 1)) as f64;
+    let max = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+    for sample in samples {
+        *sample = (*sample as f64 * gain).round().clamp(min, max) as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_the_peak() {
+        let mut samples = [-8192, 100, 4096];
+        let gain = gain(8192, 16, -6.0);
+        apply_gain(&mut samples, gain, 16);
+        assert_eq!(samples, [-16_423, 200, 8211]);
+
+        // Full scale clips the positive side by one step
+        let mut samples = [-8192, 8192];
+        apply_gain(&mut samples, super::gain(8192, 16, 0.0), 16);
+        assert_eq!(samples, [-32_768, 32_767]);
+
+        assert_eq!(super::gain(0, 16, -1.0), 1.0);
+        assert!(parse_level("0.5").is_err());
+        assert_eq!(parse_level("-1"), Ok(-1.0));
+    }
+}
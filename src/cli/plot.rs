@@ -0,0 +1,115 @@
+//! Renders a stretch of a file before and after cleaning, like the plots in the readme.
+
+use plotters::prelude::*;
+use robo_depop_plugin::{clean_data_with, CleanConfig};
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::decode::Decoder;
+use super::invalid_data;
+
+/// The size of the rendered images in pixels.
+const IMAGE_SIZE: (u32, u32) = (1600, 1600);
+
+/// Plot `len` samples of `channel` (counting from zero) starting at `start_seconds`, before and
+/// after cleaning them with `config`. The images are written to `output_dir`, named after the
+/// input file, and their paths are returned.
+pub fn plot_region(
+    input: &Path,
+    channel: usize,
+    start_seconds: f64,
+    len: usize,
+    config: &CleanConfig,
+    output_dir: &Path,
+) -> io::Result<(PathBuf, PathBuf)> {
+    let mut decoder = Decoder::open(input)?;
+    let spec = decoder.spec();
+    if channel >= spec.num_channels {
+        return Err(invalid_data(format!(
+            "the file only has {} channels",
+            spec.num_channels
+        )));
+    }
+
+    // The samples around the region are cleaned along with it, so the edges of the plot look
+    // exactly like they would in the cleaned file
+    let start = (start_seconds * spec.sample_rate as f64).round() as u64;
+    let context = (config.radius * config.passes.max(1)) as u64;
+    let first = start.saturating_sub(context);
+    let end = start + len as u64 + context;
+
+    let samples = decoder.read_range(first, end)?.swap_remove(channel);
+
+    let region_start = (start - first) as usize;
+    if samples.len() <= region_start {
+        return Err(invalid_data(
+            "the region starts after the end of the file".to_owned(),
+        ));
+    }
+    let region_end = (region_start + len).min(samples.len());
+    let cleaned = clean_data_with(&samples, config);
+    let before = &samples[region_start..region_end];
+    let after = &cleaned[region_start..region_end];
+
+    // Both plots share their scale, so the repairs stand out when flipping between them
+    let min = *before.iter().chain(after).min().unwrap() as f64;
+    let max = *before.iter().chain(after).max().unwrap() as f64;
+    let y_range = if min < max {
+        min..max
+    } else {
+        min - 1.0..max + 1.0
+    };
+
+    let stem = input
+        .file_stem()
+        .map_or_else(|| "plot".into(), |stem| stem.to_string_lossy());
+    let before_path = output_dir.join(format!("{stem}-before.png"));
+    let after_path = output_dir.join(format!("{stem}-after.png"));
+    draw(&before_path, "before", before, start, y_range.clone(), &RED)?;
+    draw(&after_path, "after", after, start, y_range, &BLUE)?;
+
+    Ok((before_path, after_path))
+}
+
+/// Plot `samples`, the first of which is at `first_position` in its channel.
+fn draw(
+    path: &Path,
+    caption: &str,
+    samples: &[i32],
+    first_position: u64,
+    y_range: std::ops::Range<f64>,
+    color: &RGBColor,
+) -> io::Result<()> {
+    let x_range = first_position as f64..(first_position + samples.len() as u64) as f64;
+
+    let root = BitMapBackend::new(path, IMAGE_SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(plot_error)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(caption, ("sans-serif", 50).into_font())
+        .margin(5)
+        .x_label_area_size(30)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range, y_range)
+        .map_err(plot_error)?;
+
+    chart.configure_mesh().draw().map_err(plot_error)?;
+    chart
+        .draw_series(
+            LineSeries::new(
+                samples
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &sample)| ((first_position + i as u64) as f64, sample as f64)),
+                color,
+            )
+            .point_size(2),
+        )
+        .map_err(plot_error)?;
+
+    root.present().map_err(plot_error)
+}
+
+fn plot_error(error: impl Display) -> io::Error {
+    io::Error::other(format!("could not draw the plot: {error}"))
+}
@@ -0,0 +1,122 @@
+//! Named sets of settings, so batch runs don't have to spell out every flag. They live in a TOML
+//! file with a table per profile:
+//!
+//! ```toml
+//! [profiles.vinyl]
+//! algorithm = "lpc"
+//! threshold = 1.5
+//! window = 9
+//! format = "flac"
+//! ```
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::clean::{check_threshold, check_window, AlgorithmArg};
+use super::encode::OutputFormat;
+use super::invalid_data;
+
+/// The settings a profile can hold. Anything left out keeps its usual default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub algorithm: Option<AlgorithmArg>,
+    pub threshold: Option<f64>,
+    pub window: Option<usize>,
+    pub passes: Option<u32>,
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+/// Where the profiles are read from unless `--config` says otherwise, which is `depop/config.toml`
+/// in the user's configuration directory.
+pub fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("depop").join("config.toml"))
+}
+
+/// Read the profile called `name` from the config file at `path`.
+pub fn load_profile(path: &Path, name: &str) -> io::Result<Profile> {
+    let text = fs::read_to_string(path)?;
+    let mut config: ConfigFile = toml::from_str(&text)
+        .map_err(|error| invalid_data(format!("invalid config file: {error}")))?;
+
+    let profile = config.profiles.remove(name).ok_or_else(|| {
+        let mut names: Vec<_> = config.profiles.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        invalid_data(format!(
+            "there is no profile called `{name}`, the config file has: {}",
+            names.join(", ")
+        ))
+    })?;
+
+    // These get the same checks as their command line flags
+    let invalid_setting =
+        |setting: &str, error: String| invalid_data(format!("profile `{name}`: {setting} {error}"));
+    if let Some(threshold) = profile.threshold {
+        check_threshold(threshold).map_err(|error| invalid_setting("threshold", error))?;
+    }
+    if let Some(window) = profile.window {
+        check_window(window).map_err(|error| invalid_setting("window", error))?;
+    }
+    if profile.passes == Some(0) {
+        return Err(invalid_setting("passes", "must be at least 1".to_owned()));
+    }
+
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str, name: &str) -> io::Result<Profile> {
+        let path =
+            std::env::temp_dir().join(format!("depop-profile-{}-{name}.toml", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let profile = load_profile(&path, name);
+        fs::remove_file(&path).unwrap();
+
+        profile
+    }
+
+    #[test]
+    fn profiles() {
+        let text = r#"
+            [profiles.vinyl]
+            algorithm = "window-avg"
+            threshold = 1.5
+            window = 9
+            format = "float-wav"
+
+            [profiles.tape]
+            passes = 2
+
+            [profiles.broken]
+            window = 4
+        "#;
+
+        assert_eq!(
+            load(text, "vinyl").unwrap(),
+            Profile {
+                algorithm: Some(AlgorithmArg::WindowAvg),
+                threshold: Some(1.5),
+                window: Some(9),
+                passes: None,
+                format: Some(OutputFormat::FloatWav),
+            }
+        );
+        assert_eq!(load(text, "tape").unwrap().passes, Some(2));
+        assert!(load(text, "broken").is_err());
+        assert!(load(text, "cassette").is_err());
+        assert!(load("[profiles.typo]\nthreshhold = 2", "typo").is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use indicatif::{HumanCount, ProgressBar, ProgressState, ProgressStyle};
+use std::fmt::Write;
+
+const TEMPLATE: &str =
+    "{prefix} [{bar:30}] {percent:>3}% {human_pos}/{human_len} samples, {rate}, ETA {eta}";
+
+/// A progress bar for cleaning a single file, counting the samples per channel. This draws to
+/// standard error, so it doesn't get in the way of audio written to standard output. With `quiet`
+/// set, this is hidden.
+pub fn file_bar(quiet: bool, prefix: String) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+
+    ProgressBar::new(0)
+        .with_style(
+            ProgressStyle::with_template(TEMPLATE)
+                .expect("the template is valid")
+                .with_key("rate", |state: &ProgressState, w: &mut dyn Write| {
+                    // The built-in rate has four decimals, which is silly for samples
+                    write!(w, "{}/s", HumanCount(state.per_sec() as u64)).unwrap()
+                })
+                .progress_chars("=> "),
+        )
+        .with_prefix(prefix)
+}
@@ -0,0 +1,108 @@
+//! Provenance files written next to cleaned files, recording what each one was made from and how,
+//! so a file still accounts for itself once it's been moved out of its batch or archived.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Everything recorded about a cleaned file.
+#[derive(Debug, Serialize)]
+pub struct Provenance<'a> {
+    pub version: &'static str,
+    /// When the file was cleaned, in UTC as `yyyy-mm-ddThh:mm:ssZ`.
+    pub cleaned_at: String,
+    pub input: FileDigest<'a>,
+    pub output: FileDigest<'a>,
+    pub settings: Settings,
+    /// Repairs close enough together to be heard as one click count once, like in the summary.
+    pub clicks: usize,
+    pub repaired_samples: usize,
+}
+
+/// A file and the SHA-256 hash of its contents.
+#[derive(Debug, Serialize)]
+pub struct FileDigest<'a> {
+    pub path: &'a Path,
+    pub sha256: String,
+}
+
+/// The settings the file was cleaned with. Output settings that were left at their defaults are
+/// left out, as they follow the input.
+#[derive(Debug, Serialize)]
+pub struct Settings {
+    pub algorithm: String,
+    pub threshold: f64,
+    pub window: usize,
+    pub passes: u32,
+    pub link_channels: bool,
+    /// Counted from one, and empty when all channels were kept.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub select_channels: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mono_mix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
+    pub format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bit_depth: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dither: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<f64>,
+}
+
+/// Where the provenance of `output` goes: next to it, with `.json` added to its name.
+pub fn path_for(output: &Path) -> PathBuf {
+    let mut name = OsString::from(output.as_os_str());
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+/// The SHA-256 hash of the file at `path`, as lowercase hexadecimal.
+pub fn sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Write `provenance` to `path` as JSON.
+pub fn write(path: &Path, provenance: &Provenance) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, provenance)?;
+    writeln!(writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn hashes_files() {
+        let path = std::env::temp_dir().join(format!("depop-hash-{}.txt", std::process::id()));
+        fs::write(&path, "abc").unwrap();
+        let hash = sha256(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            path_for(Path::new("out/song.flac")),
+            Path::new("out/song.flac.json")
+        );
+    }
+}
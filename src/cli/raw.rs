@@ -0,0 +1,176 @@
+//! Headerless PCM, as written by hardware loggers and tools that only dump their samples. Its
+//! format can't be detected, so it has to be given on the command line.
+
+use std::io::{self, Read};
+
+/// The sample formats raw input can be in. All of them are interleaved and little-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RawFormat {
+    /// Signed 16-bit integers
+    S16le,
+    /// Signed 24-bit integers in three bytes
+    S24le,
+    /// Signed 32-bit integers
+    S32le,
+    /// 32-bit floating point numbers between -1 and 1
+    F32le,
+}
+
+/// Floating point samples are cleaned at this depth, which is the precision of their mantissa.
+const FLOAT_BITS_PER_SAMPLE: u16 = 24;
+
+/// The number of samples per channel read at a time.
+const CHUNK_FRAMES: usize = 4096;
+
+impl RawFormat {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            RawFormat::S16le => 2,
+            RawFormat::S24le => 3,
+            RawFormat::S32le | RawFormat::F32le => 4,
+        }
+    }
+
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            RawFormat::S16le => 16,
+            RawFormat::S24le => 24,
+            RawFormat::S32le => 32,
+            RawFormat::F32le => FLOAT_BITS_PER_SAMPLE,
+        }
+    }
+
+    fn sample(self, bytes: &[u8]) -> i32 {
+        match self {
+            RawFormat::S16le => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+            // Shifted up and back down to extend the sign
+            RawFormat::S24le => i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8,
+            RawFormat::S32le => i32::from_le_bytes(bytes.try_into().unwrap()),
+            RawFormat::F32le => {
+                let full_scale = (1i64 << (FLOAT_BITS_PER_SAMPLE - 1)) as f32;
+                let sample = f32::from_le_bytes(bytes.try_into().unwrap());
+                (sample * full_scale)
+                    .round()
+                    .clamp(-full_scale, full_scale - 1.0) as i32
+            }
+        }
+    }
+}
+
+/// Everything needed to read raw input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawSpec {
+    pub format: RawFormat,
+    pub sample_rate: u32,
+    pub num_channels: usize,
+}
+
+impl RawSpec {
+    /// The number of bytes in one sample for every channel.
+    pub fn frame_len(&self) -> usize {
+        self.format.bytes_per_sample() * self.num_channels
+    }
+}
+
+/// Reads raw samples a chunk at a time.
+pub struct RawReader {
+    reader: Box<dyn Read>,
+    spec: RawSpec,
+    buffer: Vec<u8>,
+}
+
+impl RawReader {
+    pub fn new(reader: Box<dyn Read>, spec: RawSpec) -> Self {
+        Self {
+            reader,
+            spec,
+            buffer: vec![0; CHUNK_FRAMES * spec.frame_len()],
+        }
+    }
+
+    /// Read the next chunk, with one `Vec` per channel. Returns `None` at the end of the input. A
+    /// frame that was cut off at the end is left out.
+    pub fn next_chunk(&mut self) -> io::Result<Option<Vec<Vec<i32>>>> {
+        let mut len = 0;
+        while len < self.buffer.len() {
+            match self.reader.read(&mut self.buffer[len..]) {
+                Ok(0) => break,
+                Ok(read) => len += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => return Err(error),
+            }
+        }
+
+        let frame_len = self.spec.frame_len();
+        let num_frames = len / frame_len;
+        if num_frames == 0 {
+            return Ok(None);
+        }
+        let bytes_per_sample = self.spec.format.bytes_per_sample();
+        let mut channels = vec![Vec::with_capacity(num_frames); self.spec.num_channels];
+        for frame in self.buffer[..num_frames * frame_len].chunks_exact(frame_len) {
+            for (channel, bytes) in channels
+                .iter_mut()
+                .zip(frame.chunks_exact(bytes_per_sample))
+            {
+                channel.push(self.spec.format.sample(bytes));
+            }
+        }
+
+        Ok(Some(channels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read_all(bytes: Vec<u8>, format: RawFormat, num_channels: usize) -> Vec<Vec<i32>> {
+        let spec = RawSpec {
+            format,
+            sample_rate: 48_000,
+            num_channels,
+        };
+        let mut reader = RawReader::new(Box::new(Cursor::new(bytes)), spec);
+        let mut channels = vec![Vec::new(); num_channels];
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            for (channel, samples) in channels.iter_mut().zip(chunk) {
+                channel.extend(samples);
+            }
+        }
+
+        channels
+    }
+
+    #[test]
+    fn reads_every_format() {
+        let bytes: Vec<u8> = [1i16, -2, 300, -400]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        assert_eq!(read_all(bytes, RawFormat::S16le, 2), [[1, 300], [-2, -400]]);
+
+        // The last frame is cut off
+        let bytes = vec![0x01, 0x00, 0x80, 0xff, 0xff, 0x7f, 0x00];
+        assert_eq!(
+            read_all(bytes, RawFormat::S24le, 1),
+            [[-8_388_607, 8_388_607]]
+        );
+
+        let bytes: Vec<u8> = [i32::MIN, 5]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        assert_eq!(read_all(bytes, RawFormat::S32le, 1), [[i32::MIN, 5]]);
+
+        let bytes: Vec<u8> = [0.5f32, -1.0, 2.0]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        assert_eq!(
+            read_all(bytes, RawFormat::F32le, 1),
+            [[4_194_304, -8_388_608, 8_388_607]]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::clean::Outcome;
+use super::loudness::LoudnessComparison;
+use super::time::format_timestamp;
+
+/// The formats a report of the repairs can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    /// Reports ending in `.csv` are written as CSV, everything else as JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => ReportFormat::Csv,
+            _ => ReportFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport<'a> {
+    files: Vec<JsonFile<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonFile<'a> {
+    file: &'a Path,
+    sample_rate: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loudness: Option<LoudnessComparison>,
+    repairs: Vec<Row<'a>>,
+}
+
+/// A single repair. The channels are counted from one, like in most editors.
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a Path>,
+    sample: u64,
+    seconds: f64,
+    timestamp: String,
+    channel: usize,
+    original: i32,
+    replacement: i32,
+}
+
+/// Write every repair made to `files` to `path`, as JSON or CSV depending on its extension.
+pub fn write(path: &Path, files: &[(PathBuf, Outcome)]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match ReportFormat::from_path(path) {
+        ReportFormat::Json => {
+            let report = JsonReport {
+                files: files
+                    .iter()
+                    .map(|(file, outcome)| JsonFile {
+                        file,
+                        sample_rate: outcome.spec.sample_rate,
+                        loudness: outcome.loudness,
+                        repairs: rows(None, outcome).collect(),
+                    })
+                    .collect(),
+            };
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+        ReportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(&mut writer);
+            for (file, outcome) in files {
+                for row in rows(Some(file), outcome) {
+                    csv_writer.serialize(row)?;
+                }
+            }
+            csv_writer.flush()?;
+        }
+    }
+
+    writer.flush()
+}
+
+fn rows<'a>(file: Option<&'a Path>, outcome: &'a Outcome) -> impl Iterator<Item = Row<'a>> {
+    outcome.repairs.iter().map(move |repair| {
+        let seconds = repair.position as f64 / outcome.spec.sample_rate as f64;
+        Row {
+            file,
+            sample: repair.position,
+            seconds,
+            timestamp: format_timestamp(seconds),
+            channel: repair.channel + 1,
+            original: repair.original,
+            replacement: repair.replacement,
+        }
+    })
+}
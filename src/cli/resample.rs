@@ -0,0 +1,233 @@
+//! Sample rate conversion for the command line tool's output, using a Kaiser windowed sinc filter.
+//! The filter is tabulated once and linearly interpolated, which keeps the conversion between any
+//! two rates accurate to well below the noise floor of 24-bit audio.
+
+use std::f64::consts::PI;
+
+/// The number of zero crossings of the sinc function on either side of its center.
+const ZERO_CROSSINGS: usize = 32;
+/// The number of table entries between two zero crossings.
+const OVERSAMPLING: usize = 512;
+/// Where the passband ends, relative to the lower of the two Nyquist frequencies. The rest is left
+/// for the filter's transition band.
+const ROLLOFF: f64 = 0.95;
+/// The Kaiser window's shape, which trades the transition band's width for stopband attenuation.
+const KAISER_BETA: f64 = 10.0;
+
+/// Converts a single channel from one sample rate to another a chunk at a time, with the same
+/// result as converting it in one go. The output starts at the same time as the input, and has as
+/// many samples as it takes to cover the input's duration.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    input_rate: u64,
+    output_rate: u64,
+    /// The filter's cutoff frequency relative to the input's Nyquist frequency.
+    cutoff: f64,
+    /// The filter's half length in input samples.
+    half_width: usize,
+    /// The right half of the filter, sampled `OVERSAMPLING` times between every zero crossing.
+    table: Vec<f64>,
+    /// The input samples that are still needed, starting at `offset`.
+    pending: Vec<f64>,
+    /// The position of `pending[0]` in the input. This starts out negative, as the first output
+    /// samples are computed with silence before the start of the input.
+    offset: i64,
+    /// The number of input samples pushed so far.
+    input_len: u64,
+    /// The number of output samples produced so far.
+    output_len: u64,
+    /// The range the output is clamped to, matching the input's bit depth.
+    min: i32,
+    max: i32,
+}
+
+impl Resampler {
+    /// Convert `bits_per_sample` bit samples from `input_rate` to `output_rate` Hz.
+    pub fn new(input_rate: u32, output_rate: u32, bits_per_sample: u16) -> Self {
+        let cutoff = ROLLOFF * (output_rate as f64 / input_rate as f64).min(1.0);
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+        let window_norm = bessel_i0(KAISER_BETA);
+        let table = (0..=ZERO_CROSSINGS * OVERSAMPLING + 1)
+            .map(|i| {
+                let x = i as f64 / OVERSAMPLING as f64;
+                let position = (x / ZERO_CROSSINGS as f64).min(1.0);
+                let window = bessel_i0(KAISER_BETA * (1.0 - position * position).sqrt());
+                sinc(x) * window / window_norm
+            })
+            .collect();
+
+        Self {
+            input_rate: input_rate as u64,
+            output_rate: output_rate as u64,
+            cutoff,
+            half_width,
+            table,
+            pending: vec![0.0; half_width],
+            offset: -(half_width as i64),
+            input_len: 0,
+            output_len: 0,
+            min: -(1 << (bits_per_sample - 1)),
+            max: ((1i64 << (bits_per_sample - 1)) - 1) as i32,
+        }
+    }
+
+    /// Add the next chunk, and return the output samples that could be computed so far.
+    pub fn push(&mut self, samples: &[i32]) -> Vec<i32> {
+        self.pending
+            .extend(samples.iter().map(|&sample| sample as f64));
+        self.input_len += samples.len() as u64;
+
+        self.resample(u64::MAX)
+    }
+
+    /// Compute the rest of the output, with silence after the end of the input.
+    pub fn finish(mut self) -> Vec<i32> {
+        let end = (self.input_len * self.output_rate).div_ceil(self.input_rate);
+        if end == 0 {
+            return Vec::new();
+        }
+
+        let (last_position, _) = self.position(end - 1);
+        let available = self.offset + self.pending.len() as i64;
+        let padding = (last_position + self.half_width as i64 + 1 - available).max(0);
+        self.pending.extend(vec![0.0; padding as usize]);
+
+        self.resample(end)
+    }
+
+    /// Compute the output samples up to `end` for which all of the input they need is available,
+    /// and drop the input that's no longer needed.
+    fn resample(&mut self, end: u64) -> Vec<i32> {
+        let mut output = Vec::new();
+        while self.output_len < end {
+            let (position, fraction) = self.position(self.output_len);
+            let first = position - self.half_width as i64 + 1;
+            if position + self.half_width as i64 >= self.offset + self.pending.len() as i64 {
+                break;
+            }
+
+            let start = (first - self.offset) as usize;
+            let window = &self.pending[start..start + 2 * self.half_width];
+            let value: f64 = window
+                .iter()
+                .enumerate()
+                .map(|(tap, &sample)| {
+                    let distance = (tap as i64 + first - position) as f64 - fraction;
+                    sample * self.kernel(distance)
+                })
+                .sum();
+
+            output.push(value.round().clamp(self.min as f64, self.max as f64) as i32);
+            self.output_len += 1;
+        }
+
+        // Everything before the next output sample's window can go
+        let (position, _) = self.position(self.output_len);
+        let first = position - self.half_width as i64 + 1;
+        let unneeded = (first - self.offset).clamp(0, self.pending.len() as i64) as usize;
+        self.pending.drain(..unneeded);
+        self.offset += unneeded as i64;
+
+        output
+    }
+
+    /// The exact position of an output sample in the input, as a whole sample and a fraction.
+    fn position(&self, output_index: u64) -> (i64, f64) {
+        let numerator = output_index * self.input_rate;
+        let whole = (numerator / self.output_rate) as i64;
+        let fraction = (numerator % self.output_rate) as f64 / self.output_rate as f64;
+
+        (whole, fraction)
+    }
+
+    /// The filter's response at `distance` input samples from its center.
+    fn kernel(&self, distance: f64) -> f64 {
+        let x = distance.abs() * self.cutoff * OVERSAMPLING as f64;
+        let index = x as usize;
+        if index >= ZERO_CROSSINGS * OVERSAMPLING {
+            return 0.0;
+        }
+
+        let fraction = x - index as f64;
+        let value = self.table[index] + (self.table[index + 1] - self.table[index]) * fraction;
+        value * self.cutoff
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// The zeroth order modified Bessel function of the first kind, which the Kaiser window is built
+/// from.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..50 {
+        term *= (x / (2.0 * k as f64)).powi(2);
+        sum += term;
+        if term < sum * 1e-16 {
+            break;
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(len: usize, frequency: f64, sample_rate: f64) -> Vec<i32> {
+        (0..len)
+            .map(|i| ((2.0 * PI * frequency * i as f64 / sample_rate).sin() * 1_000_000.0) as i32)
+            .collect()
+    }
+
+    fn resample(samples: &[i32], input_rate: u32, output_rate: u32, chunk_size: usize) -> Vec<i32> {
+        let mut resampler = Resampler::new(input_rate, output_rate, 24);
+        let mut output = Vec::new();
+        for chunk in samples.chunks(chunk_size) {
+            output.extend(resampler.push(chunk));
+        }
+        output.extend(resampler.finish());
+
+        output
+    }
+
+    #[test]
+    fn converts_a_sine() {
+        for (input_rate, output_rate) in [(96_000, 44_100), (44_100, 48_000), (48_000, 44_101)] {
+            let input = sine(9_600, 1_000.0, input_rate as f64);
+            let output = resample(&input, input_rate, output_rate, 1000);
+            let expected = sine(output.len(), 1_000.0, output_rate as f64);
+
+            assert_eq!(
+                output.len() as u64,
+                (9_600 * output_rate as u64).div_ceil(input_rate as u64)
+            );
+            // The edges are filtered against the silence around the input
+            let margin = 200;
+            let error = output[margin..output.len() - margin]
+                .iter()
+                .zip(&expected[margin..])
+                .map(|(&a, &b)| (a - b).abs())
+                .max()
+                .unwrap();
+            assert!(error < 30, "{input_rate} to {output_rate} Hz: {error}");
+        }
+    }
+
+    #[test]
+    fn chunk_sizes_make_no_difference() {
+        let input = sine(5_000, 3_000.0, 96_000.0);
+        let expected = resample(&input, 96_000, 44_100, input.len());
+        for chunk_size in [1, 7, 64, 4096] {
+            assert_eq!(resample(&input, 96_000, 44_100, chunk_size), expected);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Scores for the `simulate` subcommand, which adds known pops to a clean recording, cleans it, and
+//! checks the result against the original. This puts numbers on how well each algorithm finds pops
+//! and how close it gets to the audio underneath them.
+
+use super::inject::Pop;
+
+/// How well the cleaning undid the pops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Score {
+    pub pops: usize,
+    /// The pops that had at least one of their samples repaired.
+    pub detected: usize,
+    /// All samples that were changed by the cleaning, counting every channel.
+    pub repaired_samples: usize,
+    /// The repaired samples that were part of a pop.
+    pub repaired_pop_samples: usize,
+    /// The root mean square of the difference between the cleaned and the original samples where
+    /// the pops were, relative to full scale. Missed pops count with their whole size.
+    pub pop_error: f64,
+    /// The same everywhere else, which is the damage done to audio that didn't need repairs.
+    pub collateral_error: f64,
+}
+
+impl Score {
+    /// The share of the repaired samples that were part of a pop, or 1 if nothing was repaired.
+    pub fn precision(&self) -> f64 {
+        if self.repaired_samples == 0 {
+            1.0
+        } else {
+            self.repaired_pop_samples as f64 / self.repaired_samples as f64
+        }
+    }
+
+    /// The share of the pops that were found, or 1 if there weren't any.
+    pub fn recall(&self) -> f64 {
+        if self.pops == 0 {
+            1.0
+        } else {
+            self.detected as f64 / self.pops as f64
+        }
+    }
+}
+
+/// Score `cleaned` against the `original` audio, given the `corrupted` version with `pops` in it
+/// that was cleaned. All of them have one `Vec` per channel.
+pub fn score(
+    original: &[Vec<i32>],
+    corrupted: &[Vec<i32>],
+    cleaned: &[Vec<i32>],
+    pops: &[Pop],
+    bits_per_sample: u16,
+) -> Score {
+    let full_scale = (1u64 << (bits_per_sample - 1)) as f64;
+    let error = |channel: usize, position: usize| {
+        let difference = cleaned[channel][position] as f64 - original[channel][position] as f64;
+        (difference / full_scale).powi(2)
+    };
+
+    let mut detected = 0;
+    let mut repaired_pop_samples = 0;
+    let mut pop_samples = 0;
+    let mut pop_power = 0.0;
+    for pop in pops {
+        let positions = pop.position as usize..pop.position as usize + pop.width;
+        let repaired = positions
+            .clone()
+            .filter(|&position| cleaned[pop.channel][position] != corrupted[pop.channel][position])
+            .count();
+        if repaired > 0 {
+            detected += 1;
+        }
+        repaired_pop_samples += repaired;
+        pop_samples += pop.width;
+        pop_power += positions
+            .map(|position| error(pop.channel, position))
+            .sum::<f64>();
+    }
+
+    let mut repaired_samples = 0;
+    let mut total_power = 0.0;
+    for (channel, (corrupted, cleaned)) in corrupted.iter().zip(cleaned).enumerate() {
+        for (position, (corrupted, cleaned)) in corrupted.iter().zip(cleaned).enumerate() {
+            if corrupted != cleaned {
+                repaired_samples += 1;
+            }
+            total_power += error(channel, position);
+        }
+    }
+    let num_samples = original.iter().map(Vec::len).sum::<usize>();
+    let rms = |power: f64, count: usize| {
+        if count == 0 {
+            0.0
+        } else {
+            (power / count as f64).sqrt()
+        }
+    };
+
+    Score {
+        pops: pops.len(),
+        detected,
+        repaired_samples,
+        repaired_pop_samples,
+        pop_error: rms(pop_power, pop_samples),
+        collateral_error: rms(
+            (total_power - pop_power).max(0.0),
+            num_samples - pop_samples,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_the_repairs() {
+        let original = vec![vec![0; 100]];
+        let mut corrupted = original.clone();
+        let pops = [
+            Pop {
+                position: 10,
+                channel: 0,
+                width: 2,
+                offset: 16_384,
+            },
+            Pop {
+                position: 50,
+                channel: 0,
+                width: 1,
+                offset: -16_384,
+            },
+        ];
+        corrupted[0][10] = 16_384;
+        corrupted[0][11] = 16_384;
+        corrupted[0][50] = -16_384;
+
+        // The first pop is half undone, the second one is missed, and a good sample is changed
+        let mut cleaned = corrupted.clone();
+        cleaned[0][10] = 0;
+        cleaned[0][80] = 3277;
+        let score = score(&original, &corrupted, &cleaned, &pops, 16);
+
+        assert_eq!(score.detected, 1);
+        assert_eq!(score.recall(), 0.5);
+        assert_eq!(score.repaired_samples, 2);
+        assert_eq!(score.precision(), 0.5);
+        assert!((score.pop_error - (0.5f64.powi(2) * 2.0 / 3.0).sqrt()).abs() < 1e-9);
+        assert!((score.collateral_error - (0.1f64.powi(2) / 97.0).sqrt()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,175 @@
+//! Short WAV files of the audio around each click, before and after it was repaired, to audition
+//! every fix on its own instead of scrubbing through the whole file.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::clean::Repair;
+use super::decode::{Decoder, Spec};
+use super::encode::{Encoder, Output, OutputFormat};
+use super::labels::clicks;
+use super::metadata::Metadata;
+
+/// How much audio goes into a snippet on either side of a click.
+const CONTEXT_SECONDS: f64 = 0.05;
+
+/// The part of the file a snippet covers, and the repairs in it.
+#[derive(Debug)]
+struct Snippet<'a> {
+    start: u64,
+    end: u64,
+    repairs: &'a [Repair],
+    /// The samples collected so far, one `Vec` per channel.
+    channels: Vec<Vec<i32>>,
+}
+
+/// Decode the file the `repairs` were made in again with `decoder`, and write a pair of snippets
+/// for each click into `dir`, like `0003_12.345s_before.wav` and `0003_12.345s_after.wav`. Returns
+/// the number of pairs.
+pub fn write_snippets(mut decoder: Decoder, dir: &Path, repairs: &[Repair]) -> io::Result<usize> {
+    let spec = decoder.spec();
+    let context = (CONTEXT_SECONDS * spec.sample_rate as f64).round() as u64;
+    let mut snippets: Vec<Snippet> = clicks(repairs, spec.sample_rate)
+        .into_iter()
+        .map(|repairs| Snippet {
+            start: repairs[0].position.saturating_sub(context),
+            end: repairs[repairs.len() - 1].position + 1 + context,
+            repairs,
+            channels: vec![Vec::new(); spec.num_channels],
+        })
+        .collect();
+    if snippets.is_empty() {
+        return Ok(0);
+    }
+    fs::create_dir_all(dir)?;
+
+    // The snippets are in order, so only the ones from `next` on can still need audio
+    let mut next = 0;
+    let mut position = 0;
+    while next < snippets.len() {
+        let Some(chunk) = decoder.next_chunk()? else {
+            break;
+        };
+        let len = chunk.first().map_or(0, Vec::len) as u64;
+        for snippet in snippets[next..]
+            .iter_mut()
+            .take_while(|snippet| snippet.start < position + len)
+        {
+            let from = snippet.start.max(position) - position;
+            let to = snippet.end.min(position + len).max(position) - position;
+            for (collected, samples) in snippet.channels.iter_mut().zip(&chunk) {
+                collected.extend_from_slice(&samples[from as usize..to as usize]);
+            }
+        }
+        position += len;
+        while next < snippets.len() && snippets[next].end <= position {
+            write_pair(dir, next, &snippets[next], spec)?;
+            snippets[next].channels = Vec::new();
+            next += 1;
+        }
+    }
+    // Clicks right at the end of the file get cut short
+    for (index, snippet) in snippets.iter().enumerate().skip(next) {
+        write_pair(dir, index, snippet, spec)?;
+    }
+
+    Ok(snippets.len())
+}
+
+fn write_pair(dir: &Path, index: usize, snippet: &Snippet, spec: Spec) -> io::Result<()> {
+    let mut after = snippet.channels.clone();
+    // Later passes come later in the list, so their replacements win
+    for repair in snippet.repairs {
+        if let Some(sample) =
+            after[repair.channel].get_mut((repair.position - snippet.start) as usize)
+        {
+            *sample = repair.replacement;
+        }
+    }
+
+    let seconds = snippet.repairs[0].position as f64 / spec.sample_rate as f64;
+    let name = format!("{:04}_{seconds:.3}s", index + 1);
+    write_wav(
+        &dir.join(format!("{name}_before.wav")),
+        &snippet.channels,
+        spec,
+    )?;
+    write_wav(&dir.join(format!("{name}_after.wav")), &after, spec)
+}
+
+fn write_wav(path: &Path, channels: &[Vec<i32>], spec: Spec) -> io::Result<()> {
+    let mut output = Output::create(path)?;
+    let mut encoder = Encoder::new(
+        &mut output,
+        OutputFormat::Wav,
+        spec,
+        0,
+        &Metadata::default(),
+    )?;
+    encoder.write(channels)?;
+    encoder.finish()?;
+    output.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::flac_encoder::FlacWriter;
+
+    #[test]
+    fn writes_before_and_after() {
+        let dir = std::env::temp_dir().join(format!("depop-snippets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.flac");
+        let samples: Vec<i32> = (0..10_000).map(|i| (i % 100) - 50).collect();
+        let mut writer = FlacWriter::new(
+            io::BufWriter::new(fs::File::create(&input).unwrap()),
+            1000,
+            16,
+            1,
+            0,
+        )
+        .unwrap();
+        writer.write(&[&samples]).unwrap();
+        writer.finish().unwrap();
+
+        let repairs = [
+            Repair {
+                position: 5000,
+                channel: 0,
+                original: samples[5000],
+                replacement: 1234,
+            },
+            Repair {
+                position: 9990,
+                channel: 0,
+                original: samples[9990],
+                replacement: -1234,
+            },
+        ];
+        let snippets = dir.join("snippets");
+        assert_eq!(
+            write_snippets(Decoder::open(&input).unwrap(), &snippets, &repairs).unwrap(),
+            2
+        );
+
+        let read = |name: &str| {
+            let mut decoder = Decoder::open(&snippets.join(name)).unwrap();
+            let mut channel = Vec::new();
+            while let Some(chunk) = decoder.next_chunk().unwrap() {
+                channel.extend_from_slice(&chunk[0]);
+            }
+            channel
+        };
+        let before = read("0001_5.000s_before.wav");
+        assert_eq!(before, samples[4950..5051]);
+        let after = read("0001_5.000s_after.wav");
+        assert_eq!(after[50], 1234);
+        assert_eq!(after[..50], before[..50]);
+        // The last click only has the audio up to the end
+        assert_eq!(read("0002_9.990s_after.wav").len(), 60);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+//! The summary shown after each file, to tell at a glance how much cleaning it needed.
+
+use std::fmt;
+use std::time::Duration;
+
+use super::clean::Repair;
+use super::decode::Spec;
+use super::labels::clicks;
+use super::time::format_timestamp;
+
+/// The numbers behind the summary of a cleaned file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// The length of the audio in seconds.
+    pub duration: f64,
+    /// Repairs close enough together to be heard as one click count once, like for labels.
+    pub clicks: usize,
+    pub repaired_samples: usize,
+    /// The largest change made to a sample, in dBFS.
+    pub largest_spike: f64,
+    /// The peaks in dBFS before and after cleaning, including any gain.
+    pub input_peak: f64,
+    pub output_peak: f64,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    /// Summarize a file of `len` samples per channel. The peaks are sample values at the input's
+    /// bit depth.
+    pub fn new(
+        spec: Spec,
+        repairs: &[Repair],
+        len: u64,
+        input_peak: u32,
+        output_peak: f64,
+        elapsed: Duration,
+    ) -> Self {
+        let full_scale = (1u64 << (spec.bits_per_sample - 1)) as f64;
+        let largest_spike = repairs
+            .iter()
+            .map(|repair| (repair.original as i64 - repair.replacement as i64).unsigned_abs())
+            .max()
+            .unwrap_or(0);
+
+        Self {
+            duration: len as f64 / spec.sample_rate as f64,
+            clicks: clicks(repairs, spec.sample_rate).len(),
+            repaired_samples: repairs.len(),
+            largest_spike: dbfs(largest_spike as f64, full_scale),
+            input_peak: dbfs(input_peak as f64, full_scale),
+            output_peak: dbfs(output_peak, full_scale),
+            elapsed,
+        }
+    }
+
+    pub fn clicks_per_minute(&self) -> f64 {
+        if self.duration > 0.0 {
+            self.clicks as f64 * 60.0 / self.duration
+        } else {
+            0.0
+        }
+    }
+}
+
+fn dbfs(value: f64, full_scale: f64) -> f64 {
+    20.0 * (value / full_scale).log10()
+}
+
+fn format_dbfs(level: f64) -> String {
+    if level.is_finite() {
+        format!("{level:.1} dBFS")
+    } else {
+        "silent".to_owned()
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let clicks = if self.clicks == 1 { "click" } else { "clicks" };
+        write!(
+            f,
+            "{} of audio, {} {clicks} ({:.1} per minute) in {} samples",
+            format_timestamp(self.duration),
+            self.clicks,
+            self.clicks_per_minute(),
+            self.repaired_samples
+        )?;
+        if self.clicks > 0 {
+            write!(f, ", largest spike {}", format_dbfs(self.largest_spike))?;
+        }
+        write!(
+            f,
+            "; peak {} before and {} after; took {:.1} s",
+            format_dbfs(self.input_peak),
+            format_dbfs(self.output_peak),
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_the_repairs() {
+        let repair = |position, original, replacement| Repair {
+            position,
+            channel: 0,
+            original,
+            replacement,
+        };
+        let spec = Spec {
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            num_channels: 1,
+        };
+        let repairs = [
+            repair(100, 4000, 0),
+            repair(101, -16_384, 0),
+            repair(20_000, 100, 50),
+        ];
+        let summary = Summary::new(
+            spec,
+            &repairs,
+            30_000,
+            32_768,
+            8192.0,
+            Duration::from_millis(1500),
+        );
+
+        assert_eq!(summary.clicks, 2);
+        assert_eq!(summary.clicks_per_minute(), 4.0);
+        assert_eq!(
+            summary.to_string(),
+            "0:00:30.000 of audio, 2 clicks (4.0 per minute) in 3 samples, largest spike -6.0 dBFS; \
+             peak 0.0 dBFS before and -12.0 dBFS after; took 1.5 s"
+        );
+    }
+}
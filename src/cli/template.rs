@@ -0,0 +1,132 @@
+//! Output paths given as templates like `{stem}_clean.{ext}`, which are filled in for every input.
+
+use std::path::{Path, PathBuf};
+
+use super::batch::Job;
+use super::encode::OutputFormat;
+
+/// The placeholders a template can use.
+const PLACEHOLDERS: &str = "{dir}, {name}, {stem}, and {ext}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    /// The directory the input is in.
+    Dir,
+    /// The input's file name.
+    Name,
+    /// The input's file name without its extension.
+    Stem,
+    /// The extension of the format the file is written in.
+    Ext,
+}
+
+/// A parsed output template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<Part>,
+}
+
+impl OutputTemplate {
+    /// Output paths with a `{` in them are templates.
+    pub fn is_template(path: &Path) -> bool {
+        path.to_string_lossy().contains('{')
+    }
+
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("`{template}` has a `{{` without a `}}`"))?;
+            parts.push(match &rest[start + 1..start + end] {
+                "dir" => Part::Dir,
+                "name" => Part::Name,
+                "stem" => Part::Stem,
+                "ext" => Part::Ext,
+                other => {
+                    return Err(format!(
+                        "`{{{other}}}` isn't a placeholder, templates can use {PLACEHOLDERS}"
+                    ))
+                }
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// The output path for `input`, written with the given `extension`.
+    pub fn render(&self, input: &Path, extension: &str) -> PathBuf {
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => path.push_str(text),
+                Part::Dir => match input.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => {
+                        path.push_str(&dir.to_string_lossy())
+                    }
+                    _ => path.push('.'),
+                },
+                Part::Name => {
+                    path.push_str(&input.file_name().unwrap_or_default().to_string_lossy())
+                }
+                Part::Stem => {
+                    path.push_str(&input.file_stem().unwrap_or_default().to_string_lossy())
+                }
+                Part::Ext => path.push_str(extension),
+            }
+        }
+
+        PathBuf::from(path)
+    }
+
+    /// Send `job` to the path this template gives it. Without a `format`, `{ext}` follows the
+    /// input like for directories, and the file is written in the format of the path it ends up
+    /// with.
+    pub fn apply(&self, job: &mut Job, format: Option<OutputFormat>) {
+        let extension = format
+            .unwrap_or_else(|| OutputFormat::from_path(&job.input))
+            .extension();
+        job.output = self.render(&job.input, extension);
+        job.format = match format {
+            Some(format) => format,
+            None if job.output.extension().is_none() => OutputFormat::from_path(&job.input),
+            None => OutputFormat::from_path(&job.output),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_the_placeholders() {
+        let template = OutputTemplate::parse("{dir}/clean/{stem}_clean.{ext}").unwrap();
+        let mut job = Job {
+            input: PathBuf::from("captures/side a.MP3"),
+            name: PathBuf::from("side a.MP3"),
+            output: PathBuf::new(),
+            format: OutputFormat::Wav,
+        };
+        template.apply(&mut job, None);
+        assert_eq!(job.output, Path::new("captures/clean/side a_clean.wav"));
+        assert_eq!(job.format, OutputFormat::Wav);
+
+        let template = OutputTemplate::parse("{name}.flac").unwrap();
+        template.apply(&mut job, None);
+        assert_eq!(job.output, Path::new("side a.MP3.flac"));
+        assert_eq!(job.format, OutputFormat::Flac);
+
+        assert!(OutputTemplate::parse("{stem").is_err());
+        assert!(OutputTemplate::parse("{track}.flac").is_err());
+    }
+}
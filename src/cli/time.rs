@@ -0,0 +1,98 @@
+/// Format a position in seconds as `h:mm:ss.sss`, like most editors show it.
+pub fn format_timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Parse a position given either in seconds, or as `h:mm:ss.sss` or `m:ss.sss`.
+pub fn parse_timestamp(value: &str) -> Result<f64, String> {
+    let invalid = || format!("`{value}` is not a number of seconds or `h:mm:ss.sss`");
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let (seconds, larger_units) = parts.split_last().unwrap();
+    let seconds: f64 = seconds.parse().map_err(|_| invalid())?;
+    if !seconds.is_finite() || seconds < 0.0 || (!larger_units.is_empty() && seconds >= 60.0) {
+        return Err(invalid());
+    }
+
+    // Hours are only allowed to go past their usual range if they come first
+    let mut total = 0;
+    for (index, unit) in larger_units.iter().enumerate() {
+        let unit: u64 = unit.parse().map_err(|_| invalid())?;
+        if index > 0 && unit >= 60 {
+            return Err(invalid());
+        }
+        total = total * 60 + unit;
+    }
+
+    Ok(total as f64 * 60.0 + seconds)
+}
+
+/// The UTC date and time at `unix_seconds` as `yyyy-mm-dd` and `hh:mm:ss`.
+pub fn format_utc(unix_seconds: u64) -> (String, String) {
+    let days = (unix_seconds / 86_400) as i64;
+    let seconds = unix_seconds % 86_400;
+
+    // From days since 1970 to the proleptic Gregorian calendar, counting years from March so the
+    // leap day comes last (Howard Hinnant's `civil_from_days`)
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{year:04}-{month:02}-{day:02}"),
+        format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0.0), "0:00:00.000");
+        assert_eq!(format_timestamp(3723.0456), "1:02:03.046");
+
+        assert_eq!(parse_timestamp("12.5"), Ok(12.5));
+        assert_eq!(parse_timestamp("2:03.5"), Ok(123.5));
+        assert_eq!(parse_timestamp("1:02:03.046"), Ok(3723.046));
+        assert_eq!(parse_timestamp("90:00"), Ok(5400.0));
+        for invalid in ["", "-1", "1:60", "1:61:00", "1:2:3:4", "a:00", "inf"] {
+            assert!(parse_timestamp(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn utc_dates() {
+        let format = |date: &str, time: &str| (date.to_owned(), time.to_owned());
+        assert_eq!(format_utc(0), format("1970-01-01", "00:00:00"));
+        assert_eq!(format_utc(951_782_400), format("2000-02-29", "00:00:00"));
+        assert_eq!(format_utc(1_709_288_430), format("2024-03-01", "10:20:30"));
+    }
+}
@@ -0,0 +1,131 @@
+use std::io;
+use std::path::Path;
+
+use super::decode::{Decoder, Spec};
+
+/// What was found when decoding a file from start to finish.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    pub spec: Spec,
+    /// The number of samples per channel the file says it has, if it says so.
+    pub expected_frames: Option<u64>,
+    pub decoded_frames: u64,
+    pub damaged_packets: usize,
+    /// Whether the decoded audio matches the MD5 signature in the file, if it has one.
+    pub md5_matches: Option<bool>,
+}
+
+impl Verification {
+    /// Everything that's wrong with the file. This is empty for valid files.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Some(expected_frames) = self.expected_frames {
+            if expected_frames != self.decoded_frames {
+                problems.push(format!(
+                    "the file says it has {} samples, but {} could be decoded",
+                    expected_frames, self.decoded_frames
+                ));
+            }
+        }
+        if self.damaged_packets > 0 {
+            problems.push(format!(
+                "{} packets could not be decoded",
+                self.damaged_packets
+            ));
+        }
+
+        if self.md5_matches == Some(false) {
+            problems.push("the audio does not match the file's MD5 signature".to_owned());
+        }
+
+        problems
+    }
+}
+
+/// Decode all of `path` to check that it's intact. Files that can't be opened at all are reported
+/// as errors.
+pub fn verify(path: &Path) -> io::Result<Verification> {
+    let mut decoder = Decoder::open(path)?;
+    let mut decoded_frames = 0;
+    while let Some(chunk) = decoder.next_chunk()? {
+        decoded_frames += chunk.first().map_or(0, Vec::len) as u64;
+    }
+
+    Ok(Verification {
+        spec: decoder.spec(),
+        expected_frames: decoder.num_frames(),
+        decoded_frames,
+        damaged_packets: decoder.damaged_packets(),
+        md5_matches: decoder.md5_matches(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+
+    use crate::cli::flac_encoder::FlacWriter;
+
+    #[test]
+    fn finds_damaged_frames() {
+        let samples: Vec<i32> = (0..50_000).map(|i| (i * 7919 % 20_000) - 10_000).collect();
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), 44_100, 16, 1, 5).unwrap();
+        writer.write(&[&samples]).unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        let path = std::env::temp_dir().join(format!("depop-verify-{}.flac", std::process::id()));
+        fs::write(&path, &bytes).unwrap();
+        let intact = verify(&path).unwrap();
+
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0xFF;
+        fs::write(&path, &bytes).unwrap();
+        let damaged = verify(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(intact.decoded_frames, 50_000);
+        assert_eq!(intact.problems(), Vec::<String>::new());
+        // The damaged frame is skipped, which shows up as missing samples and a wrong signature
+        assert_eq!(damaged.decoded_frames, 50_000 - 4096);
+        assert_eq!(damaged.md5_matches, Some(false));
+        assert_eq!(damaged.problems().len(), 2);
+    }
+
+    #[test]
+    fn checks_the_md5_signature() {
+        use symphonia::core::checksum::Md5;
+        use symphonia::core::io::Monitor;
+
+        let samples: Vec<i32> = (0..10_000).map(|i| (i * 7919 % 20_000) - 10_000).collect();
+        let mut writer = FlacWriter::new(Cursor::new(Vec::new()), 44_100, 16, 1, 5).unwrap();
+        writer.write(&[&samples]).unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+        let mut md5 = Md5::default();
+        for sample in &samples {
+            md5.process_buf_bytes(&(*sample as i16).to_le_bytes());
+        }
+
+        // The signature follows the marker, the block header, and 18 bytes of STREAMINFO
+        let path = std::env::temp_dir().join(format!("depop-md5-{}.flac", std::process::id()));
+        let check = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            verify(&path).unwrap()
+        };
+        let signature = bytes[26..42].to_vec();
+        let signed = check(&bytes);
+        bytes[30] ^= 0xFF;
+        let wrong = check(&bytes);
+        bytes[26..42].fill(0);
+        let unsigned = check(&bytes);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(signature, md5.md5());
+        assert_eq!(unsigned.md5_matches, None);
+        assert_eq!(signed.md5_matches, Some(true));
+        assert_eq!(signed.problems(), Vec::<String>::new());
+        assert_eq!(wrong.md5_matches, Some(false));
+        assert_eq!(wrong.problems().len(), 1);
+    }
+}
@@ -0,0 +1,99 @@
+//! Watches a directory for new captures by scanning it periodically. A file is only picked up once
+//! its size and modification time have stopped changing between two scans, so files that are still
+//! being recorded or copied aren't cleaned halfway through.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::batch::{collect_jobs, Job};
+use super::encode::OutputFormat;
+
+/// The marker written next to every file that has been cleaned, so it isn't cleaned again after a
+/// restart.
+pub fn marker_path(input: &Path) -> PathBuf {
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!(".{name}.cleaned"))
+}
+
+pub struct Watcher {
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    format: Option<OutputFormat>,
+    /// The size and modification time of the files that haven't been cleaned yet, as of the last
+    /// scan.
+    seen: HashMap<PathBuf, (u64, SystemTime)>,
+}
+
+impl Watcher {
+    pub fn new(input_dir: &Path, output_dir: &Path, format: Option<OutputFormat>) -> Self {
+        Self {
+            input_dir: input_dir.to_owned(),
+            output_dir: output_dir.to_owned(),
+            format,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Scan the directory, and return the files that haven't changed since the last scan and
+    /// haven't been cleaned yet.
+    pub fn scan(&mut self) -> io::Result<Vec<Job>> {
+        let mut seen = HashMap::new();
+        let mut ready = Vec::new();
+        for job in collect_jobs(&self.input_dir, &self.output_dir, self.format)? {
+            if marker_path(&job.input).exists() {
+                continue;
+            }
+            // The file may have been moved away in the meantime
+            let Ok(metadata) = fs::metadata(&job.input) else {
+                continue;
+            };
+
+            let state = (metadata.len(), metadata.modified()?);
+            if self.seen.get(&job.input) == Some(&state) {
+                ready.push(job);
+            } else {
+                seen.insert(job.input, state);
+            }
+        }
+        self.seen = seen;
+
+        Ok(ready)
+    }
+}
+
+/// Note down that `job` has been cleaned.
+pub fn mark_done(job: &Job) -> io::Result<()> {
+    fs::write(marker_path(&job.input), b"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_files_to_settle() {
+        let root = std::env::temp_dir().join(format!("depop-watch-{}", std::process::id()));
+        let input_dir = root.join("in");
+        fs::create_dir_all(&input_dir).unwrap();
+        let capture = input_dir.join("capture.wav");
+        fs::write(&capture, b"RIFF").unwrap();
+
+        let mut watcher = Watcher::new(&input_dir, &root.join("out"), None);
+        let first_scan = watcher.scan().unwrap();
+        fs::write(&capture, b"RIFF and more").unwrap();
+        let while_growing = watcher.scan().unwrap();
+        let settled = watcher.scan().unwrap();
+        mark_done(&settled[0]).unwrap();
+        let after_marking = watcher.scan().unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(first_scan.is_empty());
+        assert!(while_growing.is_empty());
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].input, capture);
+        assert!(after_marking.is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+//! A WAV writer for files of any length. The 32-bit sizes in a WAV header only go up to 4 GB, which
+//! a few hours of 96 kHz 24-bit audio goes past, so files that grow larger than that are turned
+//! into RF64 (EBU Tech 3306) when they're finished. Smaller files stay plain WAV.
+//!
+//! Outputs that can't seek, like pipes, get the largest possible sizes in their header instead,
+//! which readers take to mean that the audio goes on until the end of the stream.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// The size of the `ds64` chunk body that RF64 needs: the RIFF size, the data size, the number of
+/// frames, and an empty table of other chunk sizes. It's reserved as a `JUNK` chunk up front.
+const DS64_LEN: u32 = 28;
+
+/// The format GUIDs of WAVE_FORMAT_EXTENSIBLE, which only differ in their first two bytes.
+const PCM_GUID: [u8; 16] = guid(1);
+const FLOAT_GUID: [u8; 16] = guid(3);
+
+const fn guid(format_tag: u8) -> [u8; 16] {
+    [
+        format_tag, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38,
+        0x9b, 0x71,
+    ]
+}
+
+/// How the samples are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Integers of 8, 16, 24, or 32 bits.
+    Int(u16),
+    /// 32-bit floating point numbers between -1 and 1.
+    Float,
+}
+
+impl SampleFormat {
+    fn bits(self) -> u16 {
+        match self {
+            SampleFormat::Int(bits) => bits,
+            SampleFormat::Float => 32,
+        }
+    }
+}
+
+/// Writes interleaved samples into a WAV file, going back to its header once the length is known.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    format: SampleFormat,
+    num_channels: u16,
+    /// Whether the header can be filled in at the end.
+    seekable: bool,
+    /// Where the data chunk's size goes.
+    data_size_offset: u64,
+    data_len: u64,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Start a file, with `chunks` like `bext` written between the format and the audio.
+    pub fn new(
+        writer: W,
+        num_channels: u16,
+        sample_rate: u32,
+        format: SampleFormat,
+        chunks: &[(&[u8; 4], &[u8])],
+    ) -> io::Result<Self> {
+        Self::start(writer, num_channels, sample_rate, format, chunks, true)
+    }
+
+    /// Start a file for an output that can't seek, with the sizes left at their maximum.
+    pub fn streaming(
+        writer: W,
+        num_channels: u16,
+        sample_rate: u32,
+        format: SampleFormat,
+        chunks: &[(&[u8; 4], &[u8])],
+    ) -> io::Result<Self> {
+        Self::start(writer, num_channels, sample_rate, format, chunks, false)
+    }
+
+    fn start(
+        mut writer: W,
+        num_channels: u16,
+        sample_rate: u32,
+        format: SampleFormat,
+        chunks: &[(&[u8; 4], &[u8])],
+        seekable: bool,
+    ) -> io::Result<Self> {
+        if !matches!(format.bits(), 8 | 16 | 24 | 32) || num_channels == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "WAV can't hold {num_channels} channels of {} bit samples",
+                    format.bits()
+                ),
+            ));
+        }
+
+        let bytes_per_sample = format.bits() / 8;
+        let block_align = num_channels * bytes_per_sample;
+        // Like most writers, the older and more widely supported header is used where it's enough
+        let extensible = num_channels > 2 || format.bits() > 16;
+        // The sizes are filled in at the end if they can be
+        let unknown_size = if seekable { 0 } else { u32::MAX };
+
+        let mut header = Vec::new();
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&unknown_size.to_le_bytes());
+        header.extend_from_slice(b"WAVE");
+        if seekable {
+            header.extend_from_slice(b"JUNK");
+            header.extend_from_slice(&DS64_LEN.to_le_bytes());
+            header.extend_from_slice(&[0; DS64_LEN as usize]);
+        }
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&(if extensible { 40u32 } else { 16 }).to_le_bytes());
+        let format_tag: u16 = match (extensible, format) {
+            (true, _) => 0xfffe,
+            (false, SampleFormat::Int(_)) => 1,
+            (false, SampleFormat::Float) => 3,
+        };
+        header.extend_from_slice(&format_tag.to_le_bytes());
+        header.extend_from_slice(&num_channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&format.bits().to_le_bytes());
+        if extensible {
+            let channel_mask: u32 = match num_channels {
+                1 => 0x4,
+                2 => 0x3,
+                n if n <= 18 => (1 << n) - 1,
+                _ => 0,
+            };
+            header.extend_from_slice(&22u16.to_le_bytes());
+            header.extend_from_slice(&format.bits().to_le_bytes());
+            header.extend_from_slice(&channel_mask.to_le_bytes());
+            header.extend_from_slice(match format {
+                SampleFormat::Int(_) => &PCM_GUID,
+                SampleFormat::Float => &FLOAT_GUID,
+            });
+        }
+        for (id, data) in chunks {
+            header.extend_from_slice(*id);
+            header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            header.extend_from_slice(data);
+            // Chunks have to have an even length
+            if data.len() % 2 == 1 {
+                header.push(0);
+            }
+        }
+        header.extend_from_slice(b"data");
+        let data_size_offset = header.len() as u64;
+        header.extend_from_slice(&unknown_size.to_le_bytes());
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            format,
+            num_channels,
+            seekable,
+            data_size_offset,
+            data_len: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Append integer samples, with one `Vec` per channel. They have to fit into the sample size.
+    pub fn write_int(&mut self, channels: &[Vec<i32>]) -> io::Result<()> {
+        let bytes_per_sample = (self.format.bits() / 8) as usize;
+        self.buffer.clear();
+        for frame in 0..channels.first().map_or(0, Vec::len) {
+            for channel in channels {
+                let sample = channel[frame];
+                if bytes_per_sample == 1 {
+                    // 8-bit WAV is the odd one out with unsigned samples
+                    self.buffer.push((sample + 128) as u8);
+                } else {
+                    self.buffer
+                        .extend_from_slice(&sample.to_le_bytes()[..bytes_per_sample]);
+                }
+            }
+        }
+
+        self.write_buffer()
+    }
+
+    /// Append floating point samples, with one `Vec` per channel.
+    pub fn write_float(&mut self, channels: &[Vec<f32>]) -> io::Result<()> {
+        self.buffer.clear();
+        for frame in 0..channels.first().map_or(0, Vec::len) {
+            for channel in channels {
+                self.buffer.extend_from_slice(&channel[frame].to_le_bytes());
+            }
+        }
+
+        self.write_buffer()
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.data_len += self.buffer.len() as u64;
+        Ok(())
+    }
+
+    /// Fill in the sizes, turning the file into RF64 if they don't fit into a WAV header, and
+    /// return the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        // Chunks have to have an even length
+        if self.data_len % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+        if !self.seekable {
+            self.writer.flush()?;
+            return Ok(self.writer);
+        }
+        let riff_len = self.data_size_offset + 4 + self.data_len.next_multiple_of(2) - 8;
+
+        match (u32::try_from(riff_len), u32::try_from(self.data_len)) {
+            (Ok(riff_len), Ok(data_len)) => {
+                self.writer.seek(SeekFrom::Start(4))?;
+                self.writer.write_all(&riff_len.to_le_bytes())?;
+                self.writer.seek(SeekFrom::Start(self.data_size_offset))?;
+                self.writer.write_all(&data_len.to_le_bytes())?;
+            }
+            _ => {
+                let block_align = self.num_channels as u64 * (self.format.bits() / 8) as u64;
+                let mut header = Vec::new();
+                header.extend_from_slice(b"RF64");
+                header.extend_from_slice(&u32::MAX.to_le_bytes());
+                header.extend_from_slice(b"WAVE");
+                header.extend_from_slice(b"ds64");
+                header.extend_from_slice(&DS64_LEN.to_le_bytes());
+                header.extend_from_slice(&riff_len.to_le_bytes());
+                header.extend_from_slice(&self.data_len.to_le_bytes());
+                header.extend_from_slice(&(self.data_len / block_align).to_le_bytes());
+                header.extend_from_slice(&0u32.to_le_bytes());
+                self.writer.seek(SeekFrom::Start(0))?;
+                self.writer.write_all(&header)?;
+                self.writer.seek(SeekFrom::Start(self.data_size_offset))?;
+                self.writer.write_all(&u32::MAX.to_le_bytes())?;
+            }
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn readers_can_read_it() {
+        let left: Vec<i32> = (0..1001).map(|i| i * 37 - 18_000).collect();
+        let right: Vec<i32> = left.iter().map(|sample| -sample).collect();
+        for bits in [8, 16, 24, 32] {
+            let channels: Vec<Vec<i32>> = [&left, &right]
+                .iter()
+                .map(|channel| {
+                    channel
+                        .iter()
+                        .map(|sample| sample >> (24 - bits.min(24)))
+                        .collect()
+                })
+                .collect();
+            let mut writer = WavWriter::new(
+                Cursor::new(Vec::new()),
+                2,
+                44_100,
+                SampleFormat::Int(bits),
+                &[(b"bext", b"even")],
+            )
+            .unwrap();
+            writer.write_int(&channels).unwrap();
+            let wav = writer.finish().unwrap().into_inner();
+
+            let mut reader = hound::WavReader::new(Cursor::new(wav)).unwrap();
+            assert_eq!(reader.spec().bits_per_sample, bits);
+            let samples: Vec<i32> = reader.samples().map(Result::unwrap).collect();
+            let expected: Vec<i32> = (0..left.len())
+                .flat_map(|frame| [channels[0][frame], channels[1][frame]])
+                .collect();
+            assert_eq!(samples, expected);
+        }
+    }
+
+    #[test]
+    fn large_files_become_rf64() {
+        let mut writer = WavWriter::new(
+            Cursor::new(Vec::new()),
+            1,
+            48_000,
+            SampleFormat::Int(16),
+            &[],
+        )
+        .unwrap();
+        writer.write_int(&[vec![1, 2, 3]]).unwrap();
+        // Pretend the data went past what a WAV header can hold
+        writer.data_len += u32::MAX as u64;
+        let data_len = writer.data_len;
+        let wav = writer.finish().unwrap().into_inner();
+
+        assert_eq!(&wav[0..4], b"RF64");
+        assert_eq!(&wav[12..16], b"ds64");
+        assert_eq!(wav[28..36], data_len.to_le_bytes());
+        assert_eq!(wav[36..44], (data_len / 2).to_le_bytes());
+        assert_eq!(wav[76..80], u32::MAX.to_le_bytes());
+    }
+}
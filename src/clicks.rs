@@ -0,0 +1,317 @@
+use nih_plug::prelude::Transport;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::{BLOCK_SIZE, MAX_CHANNELS};
+
+/// The number of repairs kept in the [`ClickLog`]. Older repairs are dropped.
+pub const MAX_CLICKS: usize = 1024;
+
+/// A single repaired sample.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Click {
+    pub channel: usize,
+    pub position: Position,
+    /// The sample's value before and after the repair. With multiband processing these are the
+    /// values within the band the pop was found in.
+    pub original: f32,
+    pub repaired: f32,
+}
+
+/// The block around the most recent repair, before and after repairing, so the editor can show
+/// exactly what was changed. This is a plain copy so it can be handed over without allocating.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RepairSnapshot {
+    pub channel: usize,
+    /// The position of the first sample in the snapshot.
+    pub position: Position,
+    pub original: [f32; BLOCK_SIZE],
+    pub repaired: [f32; BLOCK_SIZE],
+    /// The number of samples in use, since the last block in a buffer can be shorter. Zero until
+    /// the first repair.
+    pub len: usize,
+}
+
+impl Default for RepairSnapshot {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            position: Position::default(),
+            original: [0.0; BLOCK_SIZE],
+            repaired: [0.0; BLOCK_SIZE],
+            len: 0,
+        }
+    }
+}
+
+impl RepairSnapshot {
+    pub fn original(&self) -> &[f32] {
+        &self.original[..self.len]
+    }
+
+    pub fn repaired(&self) -> &[f32] {
+        &self.repaired[..self.len]
+    }
+}
+
+/// A position in the host's timeline. Every part is optional since hosts only report what they
+/// know, and nothing at all when they don't have a timeline.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub samples: Option<i64>,
+    pub seconds: Option<f64>,
+    /// The bar (starting at 1) and the beat within that bar (starting at 1, in the time
+    /// signature's beats).
+    pub bar: Option<(i32, f64)>,
+}
+
+/// The timeline information for the start of a block, from which the positions of the samples in
+/// the block are derived.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BlockPosition {
+    pub samples: Option<i64>,
+    pub seconds: Option<f64>,
+    pub sample_rate: f32,
+    /// The position in quarter notes and the tempo in quarter notes per minute.
+    pub beats: Option<(f64, f64)>,
+    /// The start of the current bar in quarter notes, its (zero based) number, and the time
+    /// signature.
+    pub bar: Option<(f64, i32, (i32, i32))>,
+}
+
+impl BlockPosition {
+    pub fn from_transport(transport: &Transport) -> Self {
+        let time_signature = transport
+            .time_sig_numerator
+            .zip(transport.time_sig_denominator);
+
+        Self {
+            samples: transport.pos_samples(),
+            seconds: transport.pos_seconds(),
+            sample_rate: transport.sample_rate,
+            beats: transport.pos_beats().zip(transport.tempo),
+            bar: transport
+                .bar_start_pos_beats()
+                .zip(transport.bar_number())
+                .zip(time_signature)
+                .map(|((start, number), time_signature)| (start, number, time_signature)),
+        }
+    }
+
+    /// The position `offset` samples after the start of the block. This may be negative to
+    /// account for latency.
+    pub fn at(&self, offset: i64) -> Position {
+        let offset_seconds = offset as f64 / self.sample_rate as f64;
+        let beats = self
+            .beats
+            .map(|(beats, tempo)| beats + offset_seconds * tempo / 60.0);
+
+        Position {
+            samples: self.samples.map(|samples| samples + offset),
+            seconds: self.seconds.map(|seconds| seconds + offset_seconds),
+            bar: beats.zip(self.bar).and_then(
+                |(beats, (bar_start, bar_number, (numerator, denominator)))| {
+                    if numerator <= 0 || denominator <= 0 {
+                        return None;
+                    }
+
+                    // The offset may well fall into a different bar than the block's start
+                    let bar_length = numerator as f64 * 4.0 / denominator as f64;
+                    let bars = ((beats - bar_start) / bar_length).floor();
+                    let quarters_into_bar = beats - bar_start - bars * bar_length;
+
+                    Some((
+                        bar_number + bars as i32 + 1,
+                        quarters_into_bar * denominator as f64 / 4.0 + 1.0,
+                    ))
+                },
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.bar, self.seconds, self.samples) {
+            (Some((bar, beat)), _, _) => write!(f, "bar {bar} beat {beat:.2}"),
+            (None, Some(seconds), _) => {
+                let minutes = (seconds / 60.0).floor();
+                write!(f, "{minutes}:{:06.3}", seconds - minutes * 60.0)
+            }
+            (None, None, Some(samples)) => write!(f, "sample {samples}"),
+            (None, None, None) => write!(f, "unknown position"),
+        }
+    }
+}
+
+/// The most recent repairs, shared between the audio thread and the editor. Like the
+/// [`DetectionHistory`][crate::analysis::DetectionHistory], this never allocates after being
+/// created.
+#[derive(Debug)]
+pub struct ClickLog {
+    clicks: Vec<Click>,
+    /// The index in `clicks` the next click will be written to.
+    next: usize,
+    /// The number of clicks written so far, capped at `MAX_CLICKS`.
+    len: usize,
+    /// The total number of clicks since the log was last cleared, including the dropped ones.
+    total: u64,
+    /// The same, for every channel separately.
+    channel_totals: [u64; MAX_CHANNELS],
+}
+
+impl Default for ClickLog {
+    fn default() -> Self {
+        Self {
+            clicks: vec![Click::default(); MAX_CLICKS],
+            next: 0,
+            len: 0,
+            total: 0,
+            channel_totals: [0; MAX_CHANNELS],
+        }
+    }
+}
+
+impl ClickLog {
+    pub fn push(&mut self, click: Click) {
+        self.clicks[self.next] = click;
+        self.next = (self.next + 1) % MAX_CLICKS;
+        self.len = (self.len + 1).min(MAX_CLICKS);
+        self.total += 1;
+        if let Some(channel_total) = self.channel_totals.get_mut(click.channel) {
+            *channel_total += 1;
+        }
+    }
+
+    /// The logged clicks from oldest to newest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Click> {
+        let start = (self.next + MAX_CLICKS - self.len) % MAX_CLICKS;
+        (0..self.len).map(move |i| &self.clicks[(start + i) % MAX_CLICKS])
+    }
+
+    pub fn last(&self) -> Option<&Click> {
+        self.iter().next_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The total number of clicks in each channel since the log was last cleared.
+    pub fn channel_totals(&self) -> [u64; MAX_CHANNELS] {
+        self.channel_totals
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+        self.total = 0;
+        self.channel_totals = [0; MAX_CHANNELS];
+    }
+
+    /// Write the logged clicks to `writer` as CSV, from oldest to newest. Unknown positions are
+    /// left empty.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "channel,sample,seconds,bar,beat,original,repaired")?;
+        for click in self.iter() {
+            let Position {
+                samples,
+                seconds,
+                bar,
+            } = click.position;
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                click.channel + 1,
+                optional(samples),
+                optional(seconds),
+                optional(bar.map(|(bar, _)| bar)),
+                optional(bar.map(|(_, beat)| beat)),
+                click.original,
+                click.repaired
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn optional(value: Option<impl ToString>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_keeps_the_most_recent_clicks() {
+        let mut log = ClickLog::default();
+        for channel in 0..MAX_CLICKS + 10 {
+            log.push(Click {
+                channel,
+                ..Click::default()
+            });
+        }
+
+        assert_eq!(log.len(), MAX_CLICKS);
+        assert_eq!(log.total(), MAX_CLICKS as u64 + 10);
+        assert_eq!(log.iter().next().unwrap().channel, 10);
+        assert_eq!(log.last().unwrap().channel, MAX_CLICKS + 9);
+        assert_eq!(
+            log.channel_totals().iter().sum::<u64>(),
+            MAX_CHANNELS as u64
+        );
+    }
+
+    #[test]
+    fn csv_export() {
+        let mut log = ClickLog::default();
+        log.push(Click {
+            channel: 1,
+            position: Position {
+                samples: Some(48_000),
+                seconds: Some(1.0),
+                bar: None,
+            },
+            original: -0.5,
+            repaired: 0.25,
+        });
+
+        let mut csv = Vec::new();
+        log.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "channel,sample,seconds,bar,beat,original,repaired\n2,48000,1,,,-0.5,0.25\n"
+        );
+    }
+
+    #[test]
+    fn positions_within_a_block() {
+        // 120 BPM in 3/4, one quarter note into the second bar
+        let block = BlockPosition {
+            samples: Some(96_000),
+            seconds: Some(2.0),
+            sample_rate: 48_000.0,
+            beats: Some((4.0, 120.0)),
+            bar: Some((3.0, 1, (3, 4))),
+        };
+
+        let position = block.at(24_000);
+        assert_eq!(position.samples, Some(120_000));
+        assert_eq!(position.seconds, Some(2.5));
+        assert_eq!(position.bar, Some((2, 3.0)));
+
+        // Crossing into the next bar
+        assert_eq!(block.at(48_000).bar, Some((3, 1.0)));
+        assert_eq!(block.at(0).to_string(), "bar 2 beat 2.00");
+    }
+}
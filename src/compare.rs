@@ -0,0 +1,120 @@
+use nih_plug::prelude::Params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A parameter snapshot, stored as normalized values keyed by parameter ID so that it survives
+/// parameters being added or removed between versions.
+pub type Snapshot = BTreeMap<String, f32>;
+
+/// Which of the two compare slots is currently loaded into the plugin's parameters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Slot {
+    #[default]
+    A,
+    B,
+}
+
+impl Slot {
+    pub fn other(self) -> Self {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A,
+        }
+    }
+}
+
+/// The A/B compare state. The active slot's snapshot is only refreshed when switching away from
+/// it, the live parameter values are the source of truth while a slot is active.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CompareSlots {
+    pub active: Slot,
+    pub a: Snapshot,
+    pub b: Snapshot,
+}
+
+impl CompareSlots {
+    pub fn slot_mut(&mut self, slot: Slot) -> &mut Snapshot {
+        match slot {
+            Slot::A => &mut self.a,
+            Slot::B => &mut self.b,
+        }
+    }
+
+    /// Store the current values in the active slot and switch to the other one. Returns the
+    /// snapshot that should be applied to the parameters, or `None` if the other slot has never
+    /// been filled, in which case it starts out as a copy of the current values.
+    pub fn toggle(&mut self, current: Snapshot) -> Option<Snapshot> {
+        let target = self.active.other();
+        *self.slot_mut(self.active) = current.clone();
+        self.active = target;
+
+        let target_snapshot = self.slot_mut(target);
+        if target_snapshot.is_empty() {
+            *target_snapshot = current;
+            None
+        } else {
+            Some(target_snapshot.clone())
+        }
+    }
+
+    /// Copy slot A into slot B. Returns the snapshot that should be applied to the parameters if
+    /// B is the active slot, since B's values are then what should be heard.
+    pub fn copy_a_to_b(&mut self, current: Snapshot) -> Option<Snapshot> {
+        match self.active {
+            Slot::A => {
+                self.b = current;
+                None
+            }
+            Slot::B => {
+                self.b = self.a.clone();
+                Some(self.b.clone())
+            }
+        }
+    }
+}
+
+/// Capture the current (unmodulated) values of all parameters in `params`, minus the ones listed
+/// in `excluded`.
+pub fn capture(params: &impl Params, excluded: &[&str]) -> Snapshot {
+    params
+        .param_map()
+        .into_iter()
+        .filter(|(id, _, _)| !excluded.contains(&id.as_str()))
+        // SAFETY: The parameter pointers point into `params`, which outlives this function call
+        .map(|(id, ptr, _)| (id, unsafe { ptr.unmodulated_normalized_value() }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(value: f32) -> Snapshot {
+        Snapshot::from([(String::from("threshold"), value)])
+    }
+
+    #[test]
+    fn toggle_round_trip() {
+        let mut slots = CompareSlots::default();
+
+        // The first switch to B has nothing to load, so B starts out as a copy of A
+        assert_eq!(slots.toggle(snapshot(0.25)), None);
+        assert_eq!(slots.active, Slot::B);
+
+        // Switching back restores A while remembering B's edits
+        assert_eq!(slots.toggle(snapshot(0.75)), Some(snapshot(0.25)));
+        assert_eq!(slots.active, Slot::A);
+        assert_eq!(slots.toggle(snapshot(0.25)), Some(snapshot(0.75)));
+    }
+
+    #[test]
+    fn copy_a_to_b() {
+        let mut slots = CompareSlots::default();
+        assert_eq!(slots.copy_a_to_b(snapshot(0.5)), None);
+        assert_eq!(slots.b, snapshot(0.5));
+
+        slots.toggle(snapshot(0.5));
+        slots.a = snapshot(0.1);
+        assert_eq!(slots.copy_a_to_b(snapshot(0.9)), Some(snapshot(0.1)));
+    }
+}
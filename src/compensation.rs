@@ -0,0 +1,118 @@
+/// The time constant of the loudness measurements. This is long enough that removing a single
+/// loud pop barely moves the level, so only sustained differences are compensated.
+const MEASUREMENT_SECONDS: f32 = 3.0;
+/// The largest correction that will be applied in either direction, in decibels.
+const MAX_CORRECTION_DB: f32 = 1.0;
+/// Below this mean square level (-100 dBFS) the signal is considered silent, and the current
+/// correction is kept.
+const SILENCE: f32 = 1e-10;
+
+/// Measures the level before and after repairing, and computes the gain that makes up for the
+/// difference. The same gain is used for all channels so the stereo image doesn't shift.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainCompensation {
+    sample_rate: f32,
+    /// The smoothed mean square levels.
+    input_level: f32,
+    output_level: f32,
+    /// The sum of squares of the current block, over all channels.
+    input_block: f32,
+    output_block: f32,
+    gain: f32,
+}
+
+impl Default for GainCompensation {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44_100.0,
+            input_level: 0.0,
+            output_level: 0.0,
+            input_block: 0.0,
+            output_block: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+impl GainCompensation {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self {
+            sample_rate: self.sample_rate,
+            ..Self::default()
+        };
+    }
+
+    /// The gain to apply to the current block, as a linear factor.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Add a channel of the current block before it's repaired.
+    pub fn measure_input(&mut self, samples: &[f32]) {
+        self.input_block += samples.iter().map(|sample| sample * sample).sum::<f32>();
+    }
+
+    /// Add a channel of the current block after it's been repaired.
+    pub fn measure_output(&mut self, samples: &[f32]) {
+        self.output_block += samples.iter().map(|sample| sample * sample).sum::<f32>();
+    }
+
+    /// Fold the current block's measurements into the levels, and update the gain for the next
+    /// block. `num_samples` is the number of samples in all measured channels together.
+    pub fn finish_block(&mut self, num_samples: usize) {
+        if num_samples > 0 {
+            let weight = (-(num_samples as f32) / (MEASUREMENT_SECONDS * self.sample_rate)).exp();
+            let input = self.input_block / num_samples as f32;
+            let output = self.output_block / num_samples as f32;
+            self.input_level = self.input_level * weight + input * (1.0 - weight);
+            self.output_level = self.output_level * weight + output * (1.0 - weight);
+        }
+        self.input_block = 0.0;
+        self.output_block = 0.0;
+
+        if self.input_level > SILENCE && self.output_level > SILENCE {
+            let max_gain = 10f32.powf(MAX_CORRECTION_DB / 20.0);
+            self.gain = (self.input_level / self.output_level)
+                .sqrt()
+                .clamp(max_gain.recip(), max_gain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(compensation: &mut GainCompensation, output_gain: f32, blocks: usize) {
+        let input: Vec<f32> = (0..128).map(|i| (i as f32 * 0.1).sin() * 0.5).collect();
+        let output: Vec<f32> = input.iter().map(|sample| sample * output_gain).collect();
+        for _ in 0..blocks {
+            compensation.measure_input(&input);
+            compensation.measure_output(&output);
+            compensation.finish_block(input.len());
+        }
+    }
+
+    #[test]
+    fn makes_up_for_level_changes() {
+        let mut compensation = GainCompensation::default();
+        compensation.set_sample_rate(48_000.0);
+
+        // 30 seconds at 48 kHz
+        run(&mut compensation, 0.95, 11_250);
+        assert!((compensation.gain() * 0.95 - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn correction_is_limited() {
+        let mut compensation = GainCompensation::default();
+        compensation.set_sample_rate(48_000.0);
+
+        run(&mut compensation, 0.5, 11_250);
+        assert!((compensation.gain() - 10f32.powf(MAX_CORRECTION_DB / 20.0)).abs() < 1e-4);
+    }
+}
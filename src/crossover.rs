@@ -0,0 +1,199 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// The highest number of bands the signal can be split into.
+pub const MAX_BANDS: usize = 3;
+
+/// A biquad filter in transposed direct form II, with coefficients from the RBJ audio EQ
+/// cookbook.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Lowpass,
+    Highpass,
+    Allpass,
+}
+
+impl Biquad {
+    /// Update the coefficients of this Butterworth (Q = 1/sqrt(2)) filter while keeping its state,
+    /// so the crossover frequencies can be changed while the audio is playing.
+    fn set(&mut self, shape: Shape, frequency: f32, sample_rate: f32) {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * FRAC_1_SQRT_2);
+
+        let (b0, b1, b2) = match shape {
+            Shape::Lowpass => ((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0),
+            Shape::Highpass => ((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0),
+            Shape::Allpass => (1.0 - alpha, -2.0 * cos, 1.0 + alpha),
+        };
+        let a0 = 1.0 + alpha;
+
+        self.b0 = b0 / a0;
+        self.b1 = b1 / a0;
+        self.b2 = b2 / a0;
+        self.a1 = -2.0 * cos / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let output = self.b0 * sample + self.z1;
+        self.z1 = self.b1 * sample - self.a1 * output + self.z2;
+        self.z2 = self.b2 * sample - self.a2 * output;
+
+        output
+    }
+
+    fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// A fourth order Linkwitz-Riley filter, two cascaded Butterworth filters. The low and high pass
+/// versions sum up to an all-pass filter, so splitting a signal with these doesn't color it.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct LinkwitzRiley([Biquad; 2]);
+
+impl LinkwitzRiley {
+    fn set(&mut self, shape: Shape, frequency: f32, sample_rate: f32) {
+        for filter in &mut self.0 {
+            filter.set(shape, frequency, sample_rate);
+        }
+    }
+
+    fn process(&mut self, sample: f32) -> f32 {
+        let [first, second] = &mut self.0;
+        second.process(first.process(sample))
+    }
+
+    fn reset(&mut self) {
+        for filter in &mut self.0 {
+            filter.reset();
+        }
+    }
+}
+
+/// Splits a single channel into two or three bands that sum back up to the (phase shifted) input.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Crossover {
+    low_lowpass: LinkwitzRiley,
+    low_highpass: LinkwitzRiley,
+    high_lowpass: LinkwitzRiley,
+    high_highpass: LinkwitzRiley,
+    /// Gives the low band the same phase shift the other two bands get from the high crossover.
+    high_allpass: Biquad,
+
+    /// The frequencies and sample rate the filters were last configured for.
+    configuration: (f32, f32, f32),
+}
+
+impl Crossover {
+    /// Set the crossover frequencies. Only `low` is used when splitting into two bands. `high` is
+    /// kept above `low`, and both are kept below the Nyquist frequency.
+    pub fn set_frequencies(&mut self, low: f32, high: f32, sample_rate: f32) {
+        if self.configuration == (low, high, sample_rate) {
+            return;
+        }
+        self.configuration = (low, high, sample_rate);
+
+        let max_frequency = sample_rate * 0.45;
+        let low = low.min(max_frequency);
+        let high = high.max(low).min(max_frequency);
+        self.low_lowpass.set(Shape::Lowpass, low, sample_rate);
+        self.low_highpass.set(Shape::Highpass, low, sample_rate);
+        self.high_lowpass.set(Shape::Lowpass, high, sample_rate);
+        self.high_highpass.set(Shape::Highpass, high, sample_rate);
+        self.high_allpass.set(Shape::Allpass, high, sample_rate);
+    }
+
+    /// Split a sample into `num_bands` bands, from low to high. The unused bands are zero.
+    pub fn split(&mut self, num_bands: usize, sample: f32) -> [f32; MAX_BANDS] {
+        match num_bands {
+            0 | 1 => [sample, 0.0, 0.0],
+            2 => [
+                self.low_lowpass.process(sample),
+                self.low_highpass.process(sample),
+                0.0,
+            ],
+            _ => {
+                let low = self.low_lowpass.process(sample);
+                let rest = self.low_highpass.process(sample);
+                [
+                    self.high_allpass.process(low),
+                    self.high_lowpass.process(rest),
+                    self.high_highpass.process(rest),
+                ]
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.low_lowpass.reset();
+        self.low_highpass.reset();
+        self.high_lowpass.reset();
+        self.high_highpass.reset();
+        self.high_allpass.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_sum_to_an_allpass() {
+        let sample_rate = 48_000.0;
+        let mut crossover = Crossover::default();
+        crossover.set_frequencies(1_000.0, 6_000.0, sample_rate);
+
+        let mut reference = [Biquad::default(); 2];
+        reference[0].set(Shape::Allpass, 1_000.0, sample_rate);
+        reference[1].set(Shape::Allpass, 6_000.0, sample_rate);
+
+        for i in 0..1000 {
+            let sample = if i == 0 {
+                1.0
+            } else {
+                (i as f32 * 0.3).sin() * 0.5
+            };
+            let bands = crossover.split(3, sample);
+            let expected = reference
+                .iter_mut()
+                .fold(sample, |sample, filter| filter.process(sample));
+
+            assert!((bands.iter().sum::<f32>() - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn low_band_rejects_high_frequencies() {
+        let sample_rate = 48_000.0;
+        let mut crossover = Crossover::default();
+        crossover.set_frequencies(1_000.0, 6_000.0, sample_rate);
+
+        // A 12 kHz sine should end up almost entirely in the high band
+        let mut peaks = [0.0f32; MAX_BANDS];
+        for i in 0..4800 {
+            let sample = (2.0 * PI * 12_000.0 * i as f32 / sample_rate).sin();
+            let bands = crossover.split(3, sample);
+            if i > 480 {
+                for (peak, band) in peaks.iter_mut().zip(bands) {
+                    *peak = peak.max(band.abs());
+                }
+            }
+        }
+
+        assert!(peaks[0] < 0.01, "{peaks:?}");
+        assert!(peaks[2] > 0.9, "{peaks:?}");
+    }
+}
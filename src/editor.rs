@@ -1,60 +1,352 @@
 use atomic_float::AtomicF32;
-use nih_plug::prelude::{util, Editor, GuiContext};
+use nih_plug::prelude::{
+    util, AsyncExecutor, BoolParam, Editor, Enum, GuiContext, Param, ParamPtr, Params,
+    PersistentField,
+};
 use nih_plug_iced::widgets as nih_widgets;
 use nih_plug_iced::*;
+use parking_lot::Mutex;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::GainParams;
+#[cfg(feature = "egui")]
+pub(crate) mod egui_editor;
+mod envelope;
+mod histogram;
+mod i18n;
+mod oscilloscope;
+mod panels;
+mod resize;
+mod scale;
+mod spectrogram;
+mod theme;
+mod timeline;
+mod value_entry;
+mod waveform;
+
+pub(crate) use i18n::Language;
+pub(crate) use panels::Panels;
+pub(crate) use scale::UiScale;
+pub(crate) use theme::Theme;
+
+use crate::algorithm::Algorithm;
+use crate::analysis::{AnalysisResult, AnalysisTask, DetectionHistory};
+use crate::clicks::{Click, ClickLog, RepairSnapshot};
+use crate::compare::{self, Slot, Snapshot};
+use crate::meter::MeterMode;
+use crate::presets::{self, Preset, PresetStore};
+use crate::ring::SampleRing;
+use crate::spectrogram::SpectrogramAnalyzer;
+use crate::waveform::WaveformHistory;
+use crate::{RoboDepop, RoboDepopParams, MAX_CHANNELS};
+
+/// How long the limiter indicator stays lit after the limiter was last engaged.
+const LIMITER_INDICATOR_HOLD: Duration = Duration::from_millis(300);
+/// The CPU load above which the CPU indicator is highlighted, as a fraction of the real-time budget.
+const CPU_LOAD_WARNING: f32 = 0.7;
+/// How long a channel is shown as active after its last repair.
+const CHANNEL_ACTIVITY_HOLD: Duration = Duration::from_millis(300);
+
+/// How much detection history is analyzed when pressing the analyze button.
+const ANALYSIS_SECONDS: usize = 30;
+
+/// How far the up and down arrow keys move the threshold, with and without shift held down.
+const THRESHOLD_NUDGE_DB: f32 = 1.0;
+const THRESHOLD_FINE_NUDGE_DB: f32 = 0.1;
+
+/// The number of repairs listed in the log panel. The export contains the entire log.
+const LISTED_CLICKS: usize = 50;
+/// The environment variable that picks the directory the click log is exported to.
+const EXPORT_DIR_VAR: &str = "ROBO_DEPOP_EXPORT_DIR";
+
+/// The editor's size when it's first opened, which is also the smallest it can be resized to since
+/// the controls need all of it.
+const DEFAULT_SIZE: (u32, u32) = (200, 1135);
+/// The largest size the editor can be resized to, before the UI scale is applied.
+const MAX_SIZE: (u32, u32) = (800, 2000);
 
 // Makes sense to also define this here, makes it a bit easier to keep track of
 pub(crate) fn default_state() -> Arc<IcedState> {
-    IcedState::from_size(200, 150)
+    IcedState::from_size(DEFAULT_SIZE.0, DEFAULT_SIZE.1)
 }
 
-pub(crate) fn create(
-    params: Arc<GainParams>,
-    peak_meter: Arc<AtomicF32>,
-    editor_state: Arc<IcedState>,
-) -> Option<Box<dyn Editor>> {
-    create_iced_editor::<GainEditor>(editor_state, (params, peak_meter))
+/// Everything the editor shares with the plugin.
+#[derive(Clone)]
+pub(crate) struct EditorData {
+    pub params: Arc<RoboDepopParams>,
+    pub peak_meter: Arc<AtomicF32>,
+    pub input_peak_meter: Arc<AtomicF32>,
+    pub limiter_engaged: Arc<AtomicBool>,
+    pub clipped: Arc<AtomicBool>,
+    pub cpu_load: Arc<AtomicF32>,
+    pub analysis_result: Arc<Mutex<Option<AnalysisResult>>>,
+    pub detection_history: Arc<Mutex<DetectionHistory>>,
+    pub click_log: Arc<Mutex<ClickLog>>,
+    pub last_repair: Arc<Mutex<RepairSnapshot>>,
+    pub waveform: Arc<Mutex<WaveformHistory>>,
+    pub spectrogram_samples: Arc<SampleRing>,
+    pub spectrogram_enabled: Arc<AtomicBool>,
+    pub async_executor: AsyncExecutor<RoboDepop>,
 }
 
-struct GainEditor {
-    params: Arc<GainParams>,
+pub(crate) fn create(data: EditorData, editor_state: Arc<IcedState>) -> Option<Box<dyn Editor>> {
+    let params = data.params.clone();
+    create_iced_editor::<RoboDepopEditor>(editor_state, data)
+        .map(|editor| Box::new(scale::ScaledEditor::new(editor, params)) as Box<dyn Editor>)
+}
+
+struct RoboDepopEditor {
+    params: Arc<RoboDepopParams>,
     context: Arc<dyn GuiContext>,
 
     peak_meter: Arc<AtomicF32>,
+    input_peak_meter: Arc<AtomicF32>,
+    /// The highest peak meter reading in decibels and when it was reached, for the numeric
+    /// readout. This is held for as long as the meter's own hold indicator.
+    peak_hold: Option<(f32, Instant)>,
+    limiter_engaged: Arc<AtomicBool>,
+    /// When the limiter was last seen engaged, used to keep the indicator lit for a moment.
+    limiter_last_engaged: Option<Instant>,
+    /// Latched by the plugin when the output clips, and cleared by clicking the indicator.
+    clipped: Arc<AtomicBool>,
+    /// The fraction of the real-time budget the plugin's processing takes.
+    cpu_load: Arc<AtomicF32>,
+    analysis_result: Arc<Mutex<Option<AnalysisResult>>>,
+    detection_history: Arc<Mutex<DetectionHistory>>,
+    click_log: Arc<Mutex<ClickLog>>,
+    last_repair: Arc<Mutex<RepairSnapshot>>,
+    /// The per channel repair counts as of the last frame, and when each channel's count last
+    /// went up.
+    channel_totals: [u64; MAX_CHANNELS],
+    channel_last_active: [Option<Instant>; MAX_CHANNELS],
+    /// When new repairs came in, for the timeline.
+    repair_times: timeline::RepairTimes,
+    waveform: Arc<Mutex<WaveformHistory>>,
+    spectrogram_samples: Arc<SampleRing>,
+    /// Mirrors the spectrogram panel's visibility for the audio thread. The plugin only fills
+    /// `spectrogram_samples` while it's set.
+    spectrogram_enabled: Arc<AtomicBool>,
+    spectrogram: SpectrogramAnalyzer,
+    async_executor: AsyncExecutor<RoboDepop>,
 
-    gain_slider_state: nih_widgets::param_slider::State,
+    theme_button_state: button::State,
+    waveform_button_state: button::State,
+    spectrogram_button_state: button::State,
+    log_button_state: button::State,
+    scale_list_state: pick_list::State<UiScale>,
+    language_list_state: pick_list::State<Language>,
+    limiter_toggle_state: nih_widgets::param_slider::State,
+    clip_button_state: button::State,
+    threshold_slider_state: nih_widgets::param_slider::State,
+    threshold_entry: value_entry::ValueEntry,
+    algorithm_list_state: pick_list::State<Algorithm>,
+    meter_mode_list_state: pick_list::State<MeterMode>,
+    input_peak_meter_state: nih_widgets::peak_meter::State,
     peak_meter_state: nih_widgets::peak_meter::State,
+
+    compare_button_state: button::State,
+    copy_a_to_b_button_state: button::State,
+    analyze_button_state: button::State,
+    apply_suggestion_button_state: button::State,
+    reset_histogram_button_state: button::State,
+
+    preset_store: PresetStore,
+    /// The factory presets followed by the user presets.
+    presets: Vec<Preset>,
+    /// The preset that was last loaded, by its label in the preset list.
+    selected_preset: Option<String>,
+    /// The name used when saving or renaming a preset.
+    preset_name: String,
+    /// What happened to the last save, rename, or delete.
+    preset_status: Option<String>,
+    preset_list_state: pick_list::State<String>,
+    preset_name_state: text_input::State,
+    save_preset_button_state: button::State,
+    rename_preset_button_state: button::State,
+    delete_preset_button_state: button::State,
+    copy_settings_button_state: button::State,
+    paste_settings_button_state: button::State,
+
+    click_list_state: scrollable::State,
+    export_button_state: button::State,
+    /// Where the log was last exported to, or why that failed.
+    export_status: Option<String>,
+
+    resize_handle_state: resize::State,
+    /// The size the editor was dragged to, which it opens at the next time.
+    new_size: Option<(u32, u32)>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 enum Message {
     /// Update a parameter's value.
     ParamUpdate(nih_widgets::ParamMessage),
+    /// Switch between the dark and light themes.
+    ToggleTheme,
+    /// Show or hide the waveform.
+    ToggleWaveform,
+    /// Show or hide the spectrogram.
+    ToggleSpectrogram,
+    /// Show or hide the repair log.
+    ToggleLog,
+    /// Change the editor's size the next time it's opened.
+    SetScale(UiScale),
+    /// Switch the editor's text to another language.
+    SetLanguage(Language),
+    /// Switch to another algorithm.
+    SetAlgorithm(Algorithm),
+    /// Switch the meters to another mode.
+    SetMeterMode(MeterMode),
+    /// Load a preset, by its label in the preset list.
+    SelectPreset(String),
+    /// The preset name field was edited.
+    PresetNameChanged(String),
+    /// Save the current settings as a user preset with the entered name.
+    SavePreset,
+    /// Rename the selected user preset to the entered name.
+    RenamePreset,
+    /// Delete the selected user preset.
+    DeletePreset,
+    /// Put the current settings on the clipboard as text.
+    CopySettings,
+    /// Load settings from text on the clipboard.
+    PasteSettings,
+    /// Store the current settings in the active compare slot and load the other one.
+    ToggleCompare,
+    /// Overwrite compare slot B with the settings from slot A.
+    CopyAToB,
+    /// Analyze the recent detections on the background thread.
+    Analyze,
+    /// Set the threshold to the one suggested by the last analysis.
+    ApplySuggestion,
+    /// Start the detection histogram over.
+    ResetHistogram,
+    /// Clear the latched clip indicator.
+    ClearClip,
+    /// Flip the bypass parameter, from a keyboard shortcut.
+    ToggleBypass,
+    /// Flip the listen parameter, from a keyboard shortcut.
+    ToggleListen,
+    /// Clear the repair log and the detection histogram, from a keyboard shortcut.
+    ResetCounters,
+    /// Move the threshold by this many decibels, from a keyboard shortcut.
+    NudgeThreshold(f32),
+    /// The threshold's value was clicked, which opens its text field on a double click.
+    ThresholdValuePressed,
+    /// The text in the threshold's text field was edited.
+    ThresholdValueEdited(String),
+    /// Set the threshold to the value typed into its text field.
+    ThresholdValueEntered,
+    /// Close the open text field without changing anything, from the escape key.
+    CancelEntry,
+    /// Write the click log to a CSV file in the export directory, see [`export_path()`].
+    ExportClicks,
+    /// The resize grip was dragged, to this size.
+    ResizeEditor((u32, u32)),
+    /// The resize grip was let go, so the size it was dragged to is saved.
+    ResizeFinished,
 }
 
-impl IcedEditor for GainEditor {
+impl IcedEditor for RoboDepopEditor {
     type Executor = executor::Default;
     type Message = Message;
-    type InitializationFlags = (Arc<GainParams>, Arc<AtomicF32>);
+    type InitializationFlags = EditorData;
 
     fn new(
-        (params, peak_meter): Self::InitializationFlags,
+        data: Self::InitializationFlags,
         context: Arc<dyn GuiContext>,
     ) -> (Self, Command<Self::Message>) {
-        let editor = GainEditor {
+        let EditorData {
+            params,
+            peak_meter,
+            input_peak_meter,
+            limiter_engaged,
+            clipped,
+            cpu_load,
+            analysis_result,
+            detection_history,
+            click_log,
+            last_repair,
+            waveform,
+            spectrogram_samples,
+            spectrogram_enabled,
+            async_executor,
+        } = data;
+        spectrogram_enabled.store(params.editor_panels.lock().spectrogram, Ordering::Relaxed);
+
+        let editor = RoboDepopEditor {
             params,
             context,
 
             peak_meter,
+            input_peak_meter,
+            peak_hold: None,
+            limiter_engaged,
+            limiter_last_engaged: None,
+            clipped,
+            cpu_load,
+            analysis_result,
+            detection_history,
+            click_log,
+            last_repair,
+            channel_totals: [0; MAX_CHANNELS],
+            channel_last_active: [None; MAX_CHANNELS],
+            repair_times: Default::default(),
+            waveform,
+            spectrogram_samples,
+            spectrogram_enabled,
+            spectrogram: SpectrogramAnalyzer::default(),
+            async_executor,
 
-            gain_slider_state: Default::default(),
+            theme_button_state: Default::default(),
+            waveform_button_state: Default::default(),
+            spectrogram_button_state: Default::default(),
+            log_button_state: Default::default(),
+            scale_list_state: Default::default(),
+            language_list_state: Default::default(),
+            limiter_toggle_state: Default::default(),
+            clip_button_state: Default::default(),
+            threshold_slider_state: Default::default(),
+            threshold_entry: Default::default(),
+            algorithm_list_state: Default::default(),
+            meter_mode_list_state: Default::default(),
+            input_peak_meter_state: Default::default(),
             peak_meter_state: Default::default(),
+
+            compare_button_state: Default::default(),
+            copy_a_to_b_button_state: Default::default(),
+            analyze_button_state: Default::default(),
+            apply_suggestion_button_state: Default::default(),
+            reset_histogram_button_state: Default::default(),
+
+            preset_store: PresetStore::default(),
+            presets: Vec::new(),
+            selected_preset: None,
+            preset_name: String::new(),
+            preset_status: None,
+            preset_list_state: Default::default(),
+            preset_name_state: Default::default(),
+            save_preset_button_state: Default::default(),
+            rename_preset_button_state: Default::default(),
+            delete_preset_button_state: Default::default(),
+            copy_settings_button_state: Default::default(),
+            paste_settings_button_state: Default::default(),
+
+            click_list_state: Default::default(),
+            export_button_state: Default::default(),
+            export_status: None,
+
+            resize_handle_state: Default::default(),
+            new_size: None,
         };
 
+        let mut editor = editor;
+        editor.reload_presets();
+
         (editor, Command::none())
     }
 
@@ -62,58 +354,971 @@ impl IcedEditor for GainEditor {
         self.context.as_ref()
     }
 
+    fn subscription(
+        &self,
+        _window_subs: &mut WindowSubs<Self::Message>,
+    ) -> Subscription<Self::Message> {
+        subscription::events_with(keyboard_shortcut)
+    }
+
     fn update(
         &mut self,
         _window: &mut WindowQueue,
         message: Self::Message,
     ) -> Command<Self::Message> {
+        // The shortcuts are letters and arrow keys, which text fields don't consume
+        let typing = self.threshold_entry.is_open() || self.preset_name_state.is_focused();
+        if typing
+            && matches!(
+                message,
+                Message::ToggleBypass
+                    | Message::ToggleListen
+                    | Message::ResetCounters
+                    | Message::NudgeThreshold(_)
+            )
+        {
+            return Command::none();
+        }
+
         match message {
             Message::ParamUpdate(message) => self.handle_param_message(message),
+            Message::ToggleTheme => {
+                let mut theme = self.params.editor_theme.lock();
+                *theme = theme.other();
+            }
+            Message::SetScale(scale) => *self.params.editor_scale.lock() = scale,
+            Message::SetLanguage(language) => *self.params.editor_language.lock() = language,
+            Message::ToggleWaveform => {
+                let mut panels = self.params.editor_panels.lock();
+                panels.waveform = !panels.waveform;
+            }
+            Message::ToggleSpectrogram => {
+                let mut panels = self.params.editor_panels.lock();
+                panels.spectrogram = !panels.spectrogram;
+                self.spectrogram_enabled
+                    .store(panels.spectrogram, Ordering::Relaxed);
+            }
+            Message::ToggleLog => {
+                let mut panels = self.params.editor_panels.lock();
+                panels.log = !panels.log;
+            }
+            Message::SetMeterMode(mode) => {
+                let param = &self.params.metering.mode;
+                self.set_normalized(param.as_ptr(), param.preview_normalized(mode));
+            }
+            Message::SetAlgorithm(algorithm) => {
+                let param = &self.params.detection.algorithm;
+                self.set_normalized(param.as_ptr(), param.preview_normalized(algorithm));
+            }
+            Message::SelectPreset(label) => {
+                if let Some(preset) = self
+                    .presets
+                    .iter()
+                    .find(|preset| preset.to_string() == label)
+                {
+                    self.apply_snapshot(&preset.snapshot);
+                    self.preset_name = preset.name.clone();
+                    self.selected_preset = Some(label);
+                    self.preset_status = None;
+                }
+            }
+            Message::PresetNameChanged(name) => self.preset_name = name,
+            Message::SavePreset => {
+                let snapshot = compare::capture(self.params.as_ref(), crate::NOT_COMPARED);
+                let result = self.preset_store.save(&self.preset_name, &snapshot);
+                if self.finish_preset_action(result, self.strings().preset_saved) {
+                    self.selected_preset = Some(self.preset_name.clone());
+                }
+            }
+            Message::RenamePreset => {
+                let result = self
+                    .selected_user_preset()
+                    .and_then(|name| self.preset_store.rename(&name, &self.preset_name));
+                if self.finish_preset_action(result, self.strings().preset_renamed) {
+                    self.selected_preset = Some(self.preset_name.clone());
+                }
+            }
+            Message::DeletePreset => {
+                let result = self.selected_user_preset().and_then(|name| {
+                    self.preset_store.delete(&name)?;
+                    self.preset_name = name;
+                    Ok(())
+                });
+                if self.finish_preset_action(result, self.strings().preset_deleted) {
+                    self.selected_preset = None;
+                }
+            }
+            Message::CopySettings => {
+                let text =
+                    presets::to_text(&compare::capture(self.params.as_ref(), crate::NOT_COMPARED));
+                let strings = self.strings();
+                self.preset_status = Some(
+                    match arboard::Clipboard::new()
+                        .and_then(|mut clipboard| clipboard.set_text(text))
+                    {
+                        Ok(()) => String::from(strings.settings_copied),
+                        Err(err) => (strings.clipboard_failed)(&err),
+                    },
+                );
+            }
+            Message::PasteSettings => {
+                let strings = self.strings();
+                self.preset_status = Some(
+                    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+                        Ok(text) => match presets::from_text(&text) {
+                            Ok(mut snapshot) => {
+                                // Shared settings shouldn't be able to bypass the plugin
+                                snapshot
+                                    .retain(|id, _| !crate::NOT_COMPARED.contains(&id.as_str()));
+                                self.apply_snapshot(&snapshot);
+                                self.selected_preset = None;
+                                String::from(strings.settings_pasted)
+                            }
+                            Err(err) => (strings.preset_failed)(&err),
+                        },
+                        Err(err) => (strings.clipboard_failed)(&err),
+                    },
+                );
+            }
+            Message::ToggleCompare => {
+                let current = compare::capture(self.params.as_ref(), crate::NOT_COMPARED);
+                let target = self.params.compare_slots.lock().toggle(current);
+                if let Some(snapshot) = target {
+                    self.apply_snapshot(&snapshot);
+                }
+            }
+            Message::CopyAToB => {
+                let current = compare::capture(self.params.as_ref(), crate::NOT_COMPARED);
+                let target = self.params.compare_slots.lock().copy_a_to_b(current);
+                if let Some(snapshot) = target {
+                    self.apply_snapshot(&snapshot);
+                }
+            }
+            Message::Analyze => self
+                .async_executor
+                .execute_background(AnalysisTask::Analyze {
+                    seconds: ANALYSIS_SECONDS,
+                }),
+            Message::ApplySuggestion => {
+                let result = *self.analysis_result.lock();
+                if let Some(result) = result {
+                    let threshold = &self.params.detection.threshold;
+                    let normalized = threshold
+                        .preview_normalized(util::db_to_gain(result.suggested_threshold_db));
+
+                    self.set_normalized(threshold.as_ptr(), normalized);
+                }
+            }
+            Message::ResetHistogram => self.detection_history.lock().reset_total(),
+            Message::ClearClip => self.clipped.store(false, Ordering::Relaxed),
+            Message::ToggleBypass => self.toggle(&self.params.output.bypass),
+            Message::ToggleListen => self.toggle(&self.params.output.listen),
+            Message::ResetCounters => {
+                self.repair_times.clear();
+                self.click_log.lock().clear();
+                self.detection_history.lock().reset_total();
+            }
+            Message::NudgeThreshold(db) => {
+                let threshold = &self.params.detection.threshold;
+                let normalized = threshold
+                    .preview_normalized(util::db_to_gain(util::gain_to_db(threshold.value()) + db));
+
+                self.set_normalized(threshold.as_ptr(), normalized);
+            }
+            Message::ThresholdValuePressed => {
+                let threshold = &self.params.detection.threshold;
+                self.threshold_entry.press(threshold.to_string());
+            }
+            Message::ThresholdValueEdited(text) => self.threshold_entry.edit(text),
+            Message::ThresholdValueEntered => {
+                let threshold = &self.params.detection.threshold;
+                // Anything that isn't a valid value simply leaves the threshold alone
+                if let Some(normalized) = self
+                    .threshold_entry
+                    .close()
+                    .and_then(|text| threshold.string_to_normalized_value(&text))
+                {
+                    self.set_normalized(threshold.as_ptr(), normalized);
+                }
+            }
+            Message::CancelEntry => {
+                self.threshold_entry.close();
+            }
+            Message::ExportClicks => {
+                let path = export_path();
+                let result = path
+                    .parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|()| File::create(&path))
+                    .and_then(|file| {
+                        let mut writer = BufWriter::new(file);
+                        self.click_log.lock().write_csv(&mut writer)?;
+                        writer.flush()
+                    });
+                let strings = self.strings();
+                self.export_status = Some(match result {
+                    Ok(()) => (strings.exported)(&path),
+                    Err(err) => (strings.export_failed)(&err),
+                });
+            }
+            Message::ResizeEditor(size) => self.new_size = Some(size),
+            Message::ResizeFinished => {
+                if let Some((width, height)) = self.new_size {
+                    // The open window keeps its size, the host gets the new one when the editor is
+                    // reopened. `IcedState` can only be created behind an `Arc`, which isn't
+                    // shared yet so it can always be unwrapped.
+                    if let Ok(state) = Arc::try_unwrap(IcedState::from_size(width, height)) {
+                        self.params.editor_state.set(state);
+                    }
+                }
+            }
         }
 
         Command::none()
     }
 
     fn view(&mut self) -> Element<'_, Self::Message> {
+        let theme = *self.params.editor_theme.lock();
+        let palette = theme.palette();
+        let language = *self.params.editor_language.lock();
+        let strings = language.strings();
+        let active_slot = match self.params.compare_slots.lock().active {
+            Slot::A => "A",
+            Slot::B => "B",
+        };
+        let detections = self.detection_history.lock().total();
+        let detections_summary = (strings.detections_summary)(detections.total());
+        let editor_size = self
+            .new_size
+            .unwrap_or_else(|| self.params.editor_state.size());
+        let resize_status = self.new_size.map_or(String::new(), strings.resized);
+        let analysis_summary = match *self.analysis_result.lock() {
+            Some(result) => (strings.analysis_summary)(
+                result.histogram.total(),
+                result.seconds,
+                result.suggested_threshold_db,
+            ),
+            None => String::from(strings.not_analyzed),
+        };
+        let (repair_summary, listed_clicks, channel_totals) = {
+            let click_log = self.click_log.lock();
+            let summary = match click_log.last() {
+                Some(click) => (strings.repair_summary)(click_log.total(), &click.position),
+                None => String::from(strings.no_repairs),
+            };
+            let listed_clicks: Vec<String> = click_log
+                .iter()
+                .rev()
+                .take(LISTED_CLICKS)
+                .map(|click| click_line(click, strings))
+                .collect();
+
+            (summary, listed_clicks, click_log.channel_totals())
+        };
+        let now = Instant::now();
+        let mut new_repairs = 0;
+        for ((total, last_total), last_active) in channel_totals
+            .iter()
+            .zip(&mut self.channel_totals)
+            .zip(&mut self.channel_last_active)
+        {
+            if total > last_total {
+                *last_active = Some(now);
+                new_repairs += total - *last_total;
+            }
+            *last_total = *total;
+        }
+        self.repair_times.record(now, new_repairs);
+        let channel_breakdown = channel_totals
+            .iter()
+            .zip(&self.channel_last_active)
+            .enumerate()
+            .fold(
+                Row::new().spacing(10),
+                |row, (channel, (total, last_active))| {
+                    let active = last_active
+                        .is_some_and(|last_active| last_active.elapsed() < CHANNEL_ACTIVITY_HOLD);
+                    row.push(
+                        Text::new(format!("{} {total}", channel_name(channel, strings)))
+                            .size(12)
+                            .color(if active { palette.accent } else { palette.text }),
+                    )
+                },
+            );
+        let panels = *self.params.editor_panels.lock();
+        let log: Element<'_, Message> = if panels.log {
+            let click_list = listed_clicks.into_iter().fold(
+                Scrollable::new(&mut self.click_list_state)
+                    .height(100.into())
+                    .width(Length::Fill),
+                |list, click| list.push(Text::new(click).size(12).color(palette.text)),
+            );
+
+            Column::new()
+                .align_items(Alignment::Center)
+                .push(click_list)
+                .push(
+                    Button::new(&mut self.export_button_state, Text::new(strings.export))
+                        .style(palette)
+                        .on_press(Message::ExportClicks),
+                )
+                .push(
+                    Text::new(self.export_status.as_deref().unwrap_or(""))
+                        .size(12)
+                        .color(palette.text)
+                        .width(Length::Fill)
+                        .horizontal_alignment(alignment::Horizontal::Center),
+                )
+                .into()
+        } else {
+            Space::with_height(0.into()).into()
+        };
+        // The threshold's range, shown on either side of its slider
+        let threshold = &self.params.detection.threshold;
+        let [threshold_min, threshold_max] = [0.0, 1.0].map(|normalized| {
+            format!(
+                "{:.0} dB",
+                util::gain_to_db(threshold.preview_plain(normalized))
+            )
+        });
+        let waveform: Element<'_, Message> = if panels.waveform {
+            let columns: Vec<_> = self.waveform.lock().columns().copied().collect();
+            let threshold_db = util::gain_to_db(self.params.detection.threshold.value());
+
+            Column::new()
+                .spacing(2)
+                .push(waveform::Waveform::new(columns.clone(), palette))
+                .push(envelope::EnvelopeGraph::new(columns, threshold_db, palette))
+                .into()
+        } else {
+            Space::with_height(0.into()).into()
+        };
+        let spectrogram: Element<'_, Message> = if panels.spectrogram {
+            self.spectrogram.update(&self.spectrogram_samples);
+            spectrogram::Spectrogram::new(self.spectrogram.columns().copied().collect(), palette)
+                .into()
+        } else {
+            Space::with_height(0.into()).into()
+        };
+        if self.limiter_engaged.swap(false, Ordering::Relaxed) {
+            self.limiter_last_engaged = Some(Instant::now());
+        }
+        let limiting = self
+            .limiter_last_engaged
+            .is_some_and(|last_engaged| last_engaged.elapsed() < LIMITER_INDICATOR_HOLD);
+        let input_peak_db = util::gain_to_db(self.input_peak_meter.load(Ordering::Relaxed));
+        let peak_db = util::gain_to_db(self.peak_meter.load(Ordering::Relaxed));
+        let peak_hold_time = Duration::from_millis(self.params.metering.hold_ms.value() as u64);
+        let peak_held = match self.peak_hold {
+            Some((held_db, reached))
+                if held_db >= peak_db && reached.elapsed() < peak_hold_time =>
+            {
+                held_db
+            }
+            _ => {
+                self.peak_hold = Some((peak_db, Instant::now()));
+                peak_db
+            }
+        };
+        let cpu_load = self.cpu_load.load(Ordering::Relaxed);
+        let meter_mode = self.params.metering.mode.value();
+        let unit = if meter_mode == MeterMode::Peak && self.params.metering.true_peak.value() {
+            "dBTP"
+        } else {
+            "dBFS"
+        };
+        let peak_readout = if peak_held <= util::MINUS_INFINITY_DB {
+            format!("{meter_mode} -inf")
+        } else {
+            format!("{meter_mode} {peak_held:.1} {unit}")
+        };
+
         Column::new()
             .align_items(Alignment::Center)
             .push(
-                Text::new("Gain GUI")
+                Text::new("Robo Depop")
                     .font(assets::NOTO_SANS_LIGHT)
-                    .size(40)
+                    .size(32)
+                    .color(palette.text)
                     .height(50.into())
                     .width(Length::Fill)
                     .horizontal_alignment(alignment::Horizontal::Center)
                     .vertical_alignment(alignment::Vertical::Bottom),
             )
             .push(
-                Text::new("Gain")
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        Button::new(
+                            &mut self.waveform_button_state,
+                            Text::new(strings.waveform).size(12),
+                        )
+                        .style(theme::Led {
+                            palette,
+                            lit: panels.waveform,
+                        })
+                        .on_press(Message::ToggleWaveform),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.spectrogram_button_state,
+                            Text::new(strings.spectrogram).size(12),
+                        )
+                        .style(theme::Led {
+                            palette,
+                            lit: panels.spectrogram,
+                        })
+                        .on_press(Message::ToggleSpectrogram),
+                    )
+                    .push(
+                        Button::new(&mut self.log_button_state, Text::new(strings.log).size(12))
+                            .style(theme::Led {
+                                palette,
+                                lit: panels.log,
+                            })
+                            .on_press(Message::ToggleLog),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Button::new(
+                            &mut self.theme_button_state,
+                            Text::new(strings.theme(theme.other())).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::ToggleTheme),
+                    )
+                    .push(Text::new(strings.size).size(12).color(palette.text))
+                    .push(
+                        Tooltip::new(
+                            PickList::new(
+                                &mut self.scale_list_state,
+                                UiScale::ALL.to_vec(),
+                                Some(*self.params.editor_scale.lock()),
+                                Message::SetScale,
+                            )
+                            .text_size(12),
+                            strings.size_tooltip,
+                            tooltip::Position::Bottom,
+                        )
+                        .size(12),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(strings.language).size(12).color(palette.text))
+                    .push(
+                        PickList::new(
+                            &mut self.language_list_state,
+                            Language::ALL.to_vec(),
+                            Some(language),
+                            Message::SetLanguage,
+                        )
+                        .text_size(12),
+                    ),
+            )
+            .push(Space::with_height(5.into()))
+            .push(waveform)
+            .push(spectrogram)
+            .push(Space::with_height(2.into()))
+            .push(timeline::Timeline::new(
+                self.repair_times.marks(now),
+                palette,
+            ))
+            .push(Space::with_height(10.into()))
+            .push(
+                Text::new(strings.threshold)
+                    .color(palette.text)
                     .height(20.into())
                     .width(Length::Fill)
                     .horizontal_alignment(alignment::Horizontal::Center)
                     .vertical_alignment(alignment::Vertical::Center),
             )
             .push(
-                nih_widgets::ParamSlider::new(&mut self.gain_slider_state, &self.params.gain)
-                    .map(Message::ParamUpdate),
+                Row::new()
+                    .spacing(4)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(threshold_min).size(12).color(palette.text))
+                    .push(
+                        Tooltip::new(
+                            nih_widgets::ParamSlider::new(
+                                &mut self.threshold_slider_state,
+                                &self.params.detection.threshold,
+                            )
+                            .width(130.into())
+                            .map(Message::ParamUpdate),
+                            strings.threshold_slider_tooltip,
+                            tooltip::Position::Bottom,
+                        )
+                        .size(12),
+                    )
+                    .push(Text::new(threshold_max).size(12).color(palette.text)),
+            )
+            .push(
+                Tooltip::new(
+                    self.threshold_entry.view(
+                        self.params.detection.threshold.to_string(),
+                        palette,
+                        Message::ThresholdValuePressed,
+                        Message::ThresholdValueEdited,
+                        Message::ThresholdValueEntered,
+                    ),
+                    strings.threshold_value_tooltip,
+                    tooltip::Position::Bottom,
+                )
+                .size(12),
+            )
+            .push(Space::with_height(5.into()))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(strings.algorithm).size(14).color(palette.text))
+                    .push(
+                        PickList::new(
+                            &mut self.algorithm_list_state,
+                            (0..Algorithm::variants().len())
+                                .map(Algorithm::from_index)
+                                .collect::<Vec<_>>(),
+                            Some(self.params.detection.algorithm.value()),
+                            Message::SetAlgorithm,
+                        )
+                        .text_size(14),
+                    ),
             )
             .push(Space::with_height(10.into()))
             .push(
-                nih_widgets::PeakMeter::new(
-                    &mut self.peak_meter_state,
-                    util::gain_to_db(self.peak_meter.load(std::sync::atomic::Ordering::Relaxed)),
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        Button::new(
+                            &mut self.compare_button_state,
+                            Text::new(format!("A/B: {active_slot}")),
+                        )
+                        .style(palette)
+                        .on_press(Message::ToggleCompare),
+                    )
+                    .push(
+                        Button::new(&mut self.copy_a_to_b_button_state, Text::new("A → B"))
+                            .style(palette)
+                            .on_press(Message::CopyAToB),
+                    ),
+            )
+            .push(Space::with_height(10.into()))
+            .push(
+                PickList::new(
+                    &mut self.preset_list_state,
+                    self.presets
+                        .iter()
+                        .map(Preset::to_string)
+                        .collect::<Vec<_>>(),
+                    self.selected_preset.clone(),
+                    Message::SelectPreset,
                 )
-                .hold_time(Duration::from_millis(600)),
+                .placeholder(strings.presets)
+                .text_size(14)
+                .width(Length::Fill),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(
+                        TextInput::new(
+                            &mut self.preset_name_state,
+                            strings.preset_name,
+                            &self.preset_name,
+                            Message::PresetNameChanged,
+                        )
+                        .size(14)
+                        .padding(3)
+                        .on_submit(Message::SavePreset),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.save_preset_button_state,
+                            Text::new(strings.save).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::SavePreset),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        Button::new(
+                            &mut self.rename_preset_button_state,
+                            Text::new(strings.rename).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::RenamePreset),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.delete_preset_button_state,
+                            Text::new(strings.delete).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::DeletePreset),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        Button::new(
+                            &mut self.copy_settings_button_state,
+                            Text::new(strings.copy_settings).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::CopySettings),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.paste_settings_button_state,
+                            Text::new(strings.paste_settings).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::PasteSettings),
+                    ),
+            )
+            .push(
+                Text::new(self.preset_status.as_deref().unwrap_or(""))
+                    .size(12)
+                    .color(palette.text)
+                    .width(Length::Fill)
+                    .horizontal_alignment(alignment::Horizontal::Center),
+            )
+            .push(Space::with_height(10.into()))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .push(
+                        Button::new(&mut self.analyze_button_state, Text::new(strings.analyze))
+                            .style(palette)
+                            .on_press(Message::Analyze),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.apply_suggestion_button_state,
+                            Text::new(strings.apply),
+                        )
+                        .style(palette)
+                        .on_press(Message::ApplySuggestion),
+                    ),
+            )
+            .push(
+                Text::new(analysis_summary)
+                    .size(14)
+                    .color(palette.text)
+                    .height(20.into())
+                    .width(Length::Fill)
+                    .horizontal_alignment(alignment::Horizontal::Center)
+                    .vertical_alignment(alignment::Vertical::Center),
+            )
+            .push(histogram::HistogramView::new(
+                detections,
+                util::gain_to_db(self.params.detection.threshold.value()),
+                palette,
+            ))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(detections_summary).size(12).color(palette.text))
+                    .push(
+                        Button::new(
+                            &mut self.reset_histogram_button_state,
+                            Text::new(strings.reset).size(12),
+                        )
+                        .style(palette)
+                        .on_press(Message::ResetHistogram),
+                    ),
+            )
+            .push(
+                Text::new(repair_summary)
+                    .size(14)
+                    .color(palette.text)
+                    .height(20.into())
+                    .width(Length::Fill)
+                    .horizontal_alignment(alignment::Horizontal::Center)
+                    .vertical_alignment(alignment::Vertical::Center),
+            )
+            .push(channel_breakdown)
+            .push(oscilloscope::Oscilloscope::new(
+                *self.last_repair.lock(),
+                palette,
+            ))
+            .push(log)
+            .push(Space::with_height(10.into()))
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Text::new(strings.input)
+                            .size(12)
+                            .color(palette.text)
+                            .width(25.into()),
+                    )
+                    .push(
+                        nih_widgets::PeakMeter::new(
+                            &mut self.input_peak_meter_state,
+                            input_peak_db,
+                        )
+                        .hold_time(peak_hold_time)
+                        .width(160.into()),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(
+                        Text::new(strings.output)
+                            .size(12)
+                            .color(palette.text)
+                            .width(25.into()),
+                    )
+                    .push(
+                        nih_widgets::PeakMeter::new(&mut self.peak_meter_state, peak_db)
+                            .hold_time(peak_hold_time)
+                            .width(160.into()),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(peak_readout).size(12).color(palette.text))
+                    .push(
+                        PickList::new(
+                            &mut self.meter_mode_list_state,
+                            (0..MeterMode::variants().len())
+                                .map(MeterMode::from_index)
+                                .collect::<Vec<_>>(),
+                            Some(meter_mode),
+                            Message::SetMeterMode,
+                        )
+                        .text_size(12),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(5)
+                    .align_items(Alignment::Center)
+                    .push(
+                        nih_widgets::ParamSlider::new(
+                            &mut self.limiter_toggle_state,
+                            &self.params.output.limiter,
+                        )
+                        .width(100.into())
+                        .map(Message::ParamUpdate),
+                    )
+                    .push(
+                        Text::new(if limiting { strings.limit } else { "" })
+                            .size(14)
+                            .color(palette.accent),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.clip_button_state,
+                            Text::new(strings.clip).size(12),
+                        )
+                        .style(theme::Led {
+                            palette,
+                            lit: self.clipped.load(Ordering::Relaxed),
+                        })
+                        .on_press(Message::ClearClip),
+                    ),
+            )
+            .push(
+                Text::new((strings.cpu_load)(cpu_load * 100.0))
+                    .size(12)
+                    // Anything close to the full budget risks dropouts
+                    .color(if cpu_load > CPU_LOAD_WARNING {
+                        palette.accent
+                    } else {
+                        palette.text
+                    }),
+            )
+            .push(
+                // Pushed to the bottom of the window, since the controls don't grow with it
+                Row::new()
+                    .height(Length::Fill)
+                    .align_items(Alignment::End)
+                    .push(
+                        Text::new(resize_status)
+                            .size(12)
+                            .color(palette.text)
+                            .width(Length::Fill)
+                            .horizontal_alignment(alignment::Horizontal::Center),
+                    )
+                    .push(
+                        resize::ResizeHandle::new(
+                            &mut self.resize_handle_state,
+                            editor_size,
+                            Message::ResizeEditor,
+                            Message::ResizeFinished,
+                            palette,
+                        )
+                        .min_size(DEFAULT_SIZE)
+                        .max_size(MAX_SIZE),
+                    ),
             )
             .into()
     }
 
     fn background_color(&self) -> nih_plug_iced::Color {
-        nih_plug_iced::Color {
-            r: 0.98,
-            g: 0.98,
-            b: 0.98,
-            a: 1.0,
+        self.params.editor_theme.lock().palette().background
+    }
+}
+
+impl RoboDepopEditor {
+    /// Read the user presets from disk again, after they've been changed.
+    fn reload_presets(&mut self) {
+        let mut presets = crate::factory_presets();
+        match self.preset_store.load_all() {
+            Ok(user_presets) => presets.extend(user_presets),
+            Err(err) => self.preset_status = Some((self.strings().presets_unreadable)(&err)),
+        }
+
+        self.presets = presets;
+    }
+
+    /// The name of the selected preset, if it's a user preset that can be changed.
+    fn selected_user_preset(&self) -> io::Result<String> {
+        self.presets
+            .iter()
+            .find(|preset| Some(preset.to_string()) == self.selected_preset)
+            .filter(|preset| !preset.factory)
+            .map(|preset| preset.name.clone())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, self.strings().select_user_preset))
+    }
+
+    /// Show the outcome of a preset action, and pick up the changes if it succeeded. Returns
+    /// whether it did.
+    fn finish_preset_action(&mut self, result: io::Result<()>, done: fn(&str) -> String) -> bool {
+        match result {
+            Ok(()) => {
+                self.preset_status = Some(done(&self.preset_name));
+                self.reload_presets();
+                true
+            }
+            Err(err) => {
+                self.preset_status = Some((self.strings().preset_failed)(&err));
+                false
+            }
         }
     }
+
+    /// The editor's text in the selected language.
+    fn strings(&self) -> &'static i18n::Strings {
+        self.params.editor_language.lock().strings()
+    }
+
+    /// Load a compare slot's snapshot into the plugin's parameters through the host.
+    fn apply_snapshot(&self, snapshot: &Snapshot) {
+        for (id, param_ptr, _) in self.params.param_map() {
+            if let Some(&value) = snapshot.get(&id) {
+                self.set_normalized(param_ptr, value);
+            }
+        }
+    }
+
+    /// Flip a boolean parameter through the host.
+    fn toggle(&self, param: &BoolParam) {
+        self.set_normalized(param.as_ptr(), if param.value() { 0.0 } else { 1.0 });
+    }
+
+    /// Set a parameter from outside of a parameter widget, as a single gesture.
+    fn set_normalized(&self, param_ptr: ParamPtr, normalized: f32) {
+        self.handle_param_message(nih_widgets::ParamMessage::BeginSetParameter(param_ptr));
+        self.handle_param_message(nih_widgets::ParamMessage::SetParameterNormalized(
+            param_ptr, normalized,
+        ));
+        self.handle_param_message(nih_widgets::ParamMessage::EndSetParameter(param_ptr));
+    }
+}
+
+/// The editor's keyboard shortcuts. Keys that were already handled by a widget, like when typing a
+/// value into a parameter slider, are left alone.
+///
+/// - B toggles bypass
+/// - L toggles listen
+/// - R resets the repair log and the detection histogram
+/// - Up and down nudge the threshold by a decibel, or a tenth of one with shift held down
+/// - Escape closes the threshold's text field without changing the threshold
+fn keyboard_shortcut(event: Event, status: event::Status) -> Option<Message> {
+    if status == event::Status::Captured {
+        return None;
+    }
+
+    match event {
+        Event::Keyboard(keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        }) => {
+            let nudge = if modifiers.shift() {
+                THRESHOLD_FINE_NUDGE_DB
+            } else {
+                THRESHOLD_NUDGE_DB
+            };
+
+            match key_code {
+                keyboard::KeyCode::B => Some(Message::ToggleBypass),
+                keyboard::KeyCode::L => Some(Message::ToggleListen),
+                keyboard::KeyCode::R => Some(Message::ResetCounters),
+                keyboard::KeyCode::Up => Some(Message::NudgeThreshold(nudge)),
+                keyboard::KeyCode::Down => Some(Message::NudgeThreshold(-nudge)),
+                keyboard::KeyCode::Escape => Some(Message::CancelEntry),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A repair as it's listed in the log panel.
+fn click_line(click: &Click, strings: &i18n::Strings) -> String {
+    format!(
+        "{} {}  {}  {:+.3} → {:+.3}",
+        strings.channel,
+        click.channel + 1,
+        click.position,
+        click.original,
+        click.repaired
+    )
+}
+
+/// The short name for a channel in the per channel breakdown.
+fn channel_name(channel: usize, strings: &i18n::Strings) -> String {
+    match (MAX_CHANNELS, channel) {
+        (2, 0) => String::from("L"),
+        (2, 1) => String::from("R"),
+        _ => format!("{} {}", strings.channel, channel + 1),
+    }
+}
+
+/// A new file to export the click log to, in the directory named by [`EXPORT_DIR_VAR`]. Without
+/// it, the log goes into the user's documents directory, or their home or the temporary directory
+/// if there is none.
+fn export_path() -> PathBuf {
+    let directory = std::env::var_os(EXPORT_DIR_VAR)
+        .map(PathBuf::from)
+        .or_else(dirs::document_dir)
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(std::env::temp_dir);
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    directory.join(format!("robo-depop-repairs-{timestamp}.csv"))
 }
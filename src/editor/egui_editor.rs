@@ -0,0 +1,147 @@
+use nih_plug::prelude::{util, BoolParam, Editor, Enum, EnumParam, Param, ParamSetter};
+use nih_plug_egui::egui::{self, ProgressBar, RichText, ScrollArea};
+use nih_plug_egui::widgets::ParamSlider;
+use nih_plug_egui::{create_egui_editor, EguiState};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use super::{click_line, EditorData, Theme, LISTED_CLICKS};
+use crate::algorithm::Algorithm;
+use crate::meter::MeterMode;
+
+/// The lowest level shown on the meters.
+const METER_MIN_DB: f32 = -80.0;
+
+pub(crate) fn default_state() -> Arc<EguiState> {
+    EguiState::from_size(260, 560)
+}
+
+/// A simpler editor built on egui instead of iced, for when iced or its windowing doesn't work on
+/// the user's system. It works on the same parameters and meters as the iced editor, but leaves
+/// out the visualizations, the analysis, and the presets.
+pub(crate) fn create(data: EditorData, egui_state: Arc<EguiState>) -> Option<Box<dyn Editor>> {
+    create_egui_editor(
+        egui_state,
+        data,
+        |_, _| {},
+        |ctx, setter, data| {
+            let params = &data.params;
+            let theme = *params.editor_theme.lock();
+            let strings = params.editor_language.lock().strings();
+            ctx.set_visuals(match theme {
+                Theme::Dark => egui::Visuals::dark(),
+                Theme::Light => egui::Visuals::light(),
+            });
+            // The meters keep moving even when nothing else happens
+            ctx.request_repaint();
+
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Robo Depop");
+                    if ui.button(strings.theme(theme.other())).clicked() {
+                        *params.editor_theme.lock() = theme.other();
+                    }
+                });
+                ui.separator();
+
+                ui.label(strings.threshold);
+                ui.add(ParamSlider::for_param(&params.detection.threshold, setter));
+                enum_picker::<Algorithm>(
+                    ui,
+                    setter,
+                    strings.algorithm,
+                    &params.detection.algorithm,
+                );
+                ui.horizontal(|ui| {
+                    toggle(ui, setter, &params.output.bypass);
+                    toggle(ui, setter, &params.output.listen);
+                    toggle(ui, setter, &params.output.limiter);
+                });
+                ui.separator();
+
+                for (label, meter) in [
+                    (strings.input, &data.input_peak_meter),
+                    (strings.output, &data.peak_meter),
+                ] {
+                    let db = util::gain_to_db(meter.load(Ordering::Relaxed));
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        ui.add(
+                            ProgressBar::new(((db - METER_MIN_DB) / -METER_MIN_DB).clamp(0.0, 1.0))
+                                .text(format!("{db:.1} dB")),
+                        );
+                    });
+                }
+                ui.horizontal(|ui| {
+                    enum_picker::<MeterMode>(ui, setter, strings.meter_mode, &params.metering.mode);
+                    let clip = if data.clipped.load(Ordering::Relaxed) {
+                        RichText::new(strings.clip).color(ui.visuals().error_fg_color)
+                    } else {
+                        RichText::new(strings.clip)
+                    };
+                    if ui.button(clip).clicked() {
+                        data.clipped.store(false, Ordering::Relaxed);
+                    }
+                });
+                ui.label((strings.cpu_load)(
+                    data.cpu_load.load(Ordering::Relaxed) * 100.0,
+                ));
+                ui.separator();
+
+                let reset = {
+                    let click_log = data.click_log.lock();
+                    let reset = ui
+                        .horizontal(|ui| {
+                            ui.label((strings.repairs)(click_log.total()));
+                            ui.button(strings.reset).clicked()
+                        })
+                        .inner;
+                    ScrollArea::vertical().show(ui, |ui| {
+                        for click in click_log.iter().rev().take(LISTED_CLICKS) {
+                            ui.small(click_line(click, strings));
+                        }
+                    });
+
+                    reset
+                };
+                if reset {
+                    data.click_log.lock().clear();
+                    data.detection_history.lock().reset_total();
+                }
+            });
+        },
+    )
+}
+
+/// Flip a boolean parameter with a checkbox, as a single gesture.
+fn toggle(ui: &mut egui::Ui, setter: &ParamSetter, param: &BoolParam) {
+    let mut value = param.value();
+    if ui.checkbox(&mut value, param.name()).changed() {
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, value);
+        setter.end_set_parameter(param);
+    }
+}
+
+/// A drop-down list with all of an enum parameter's variants.
+fn enum_picker<T: Enum + PartialEq + ToString>(
+    ui: &mut egui::Ui,
+    setter: &ParamSetter,
+    label: &str,
+    param: &EnumParam<T>,
+) {
+    let current = param.value();
+    egui::ComboBox::from_label(label)
+        .selected_text(current.to_string())
+        .show_ui(ui, |ui| {
+            for variant in (0..T::variants().len()).map(T::from_index) {
+                let selected = variant == current;
+                let text = variant.to_string();
+                if ui.selectable_label(selected, text).clicked() && !selected {
+                    setter.begin_set_parameter(param);
+                    setter.set_parameter(param, variant);
+                    setter.end_set_parameter(param);
+                }
+            }
+        });
+}
@@ -0,0 +1,122 @@
+use nih_plug::prelude::util;
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer, Size,
+    Widget,
+};
+use std::marker::PhantomData;
+
+use super::theme::Palette;
+use crate::analysis::NUM_BINS;
+use crate::waveform::Column;
+
+/// The detection envelope over the same stretch of time as the waveform view: how far the signal
+/// strayed from what the detection expected, with the threshold drawn across it. Everything that
+/// pokes through the threshold line gets repaired.
+pub struct EnvelopeGraph<Message> {
+    columns: Vec<Column>,
+    threshold_db: f32,
+    palette: Palette,
+    width: Length,
+    height: Length,
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> EnvelopeGraph<Message> {
+    /// Show the deviations in `columns`, from oldest to newest.
+    pub fn new(columns: Vec<Column>, threshold_db: f32, palette: Palette) -> Self {
+        Self {
+            columns,
+            threshold_db,
+            palette,
+            width: Length::Fill,
+            height: Length::Units(40),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Message> Widget<Message, Renderer> for EnvelopeGraph<Message> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let palette = self.palette;
+        fill(renderer, bounds, palette.panel);
+
+        // Like the histogram, this spans the threshold parameter's range from 0 dB up
+        let y_for_db =
+            |db: f32| bounds.y + bounds.height * (1.0 - (db / NUM_BINS as f32).clamp(0.0, 1.0));
+        let column_width = bounds.width / self.columns.len().max(1) as f32;
+        for (i, column) in self.columns.iter().enumerate() {
+            let db = util::gain_to_db(column.deviation);
+            let top = y_for_db(db);
+            if top >= bounds.y + bounds.height {
+                continue;
+            }
+
+            fill(
+                renderer,
+                Rectangle {
+                    x: bounds.x + i as f32 * column_width,
+                    y: top,
+                    width: column_width,
+                    height: bounds.y + bounds.height - top,
+                },
+                if db > self.threshold_db {
+                    palette.accent
+                } else {
+                    palette.waveform
+                },
+            );
+        }
+
+        fill(
+            renderer,
+            Rectangle {
+                y: y_for_db(self.threshold_db),
+                height: 1.0,
+                ..bounds
+            },
+            palette.text,
+        );
+    }
+}
+
+impl<'a, Message: 'a> From<EnvelopeGraph<Message>> for Element<'a, Message> {
+    fn from(widget: EnvelopeGraph<Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
@@ -0,0 +1,130 @@
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer, Size,
+    Widget,
+};
+use std::marker::PhantomData;
+
+use super::theme::Palette;
+use crate::analysis::{Histogram, NUM_BINS};
+
+/// A bar chart of how far samples strayed from their neighbours, one bar per decibel. The bars at
+/// or above the threshold, the ones that got repaired, are highlighted. The counts are shown on a
+/// logarithmic scale since program material easily outnumbers the pops a thousand to one.
+pub struct HistogramView<Message> {
+    histogram: Histogram,
+    threshold_db: f32,
+    palette: Palette,
+    width: Length,
+    height: Length,
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> HistogramView<Message> {
+    pub fn new(histogram: Histogram, threshold_db: f32, palette: Palette) -> Self {
+        Self {
+            histogram,
+            threshold_db,
+            palette,
+            width: Length::Fill,
+            height: Length::Units(50),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl<Message> Widget<Message, Renderer> for HistogramView<Message> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let palette = self.palette;
+        fill(renderer, bounds, palette.panel);
+
+        let max_count = self.histogram.counts.iter().copied().max().unwrap_or(0);
+        let scale = (max_count as f32).ln_1p();
+        let bar_width = bounds.width / NUM_BINS as f32;
+        for (bin, &count) in self.histogram.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+
+            let height = bounds.height * (count as f32).ln_1p() / scale;
+            fill(
+                renderer,
+                Rectangle {
+                    x: bounds.x + bin as f32 * bar_width,
+                    y: bounds.y + bounds.height - height,
+                    // Leave a gap between the bars
+                    width: (bar_width - 1.0).max(1.0),
+                    height,
+                },
+                if bin as f32 >= self.threshold_db.floor() {
+                    palette.accent
+                } else {
+                    palette.waveform
+                },
+            );
+        }
+
+        // The threshold itself, within its bin
+        let threshold_x = bounds.x + self.threshold_db.clamp(0.0, NUM_BINS as f32) * bar_width;
+        fill(
+            renderer,
+            Rectangle {
+                x: threshold_x,
+                width: 1.0,
+                ..bounds
+            },
+            palette.text,
+        );
+    }
+}
+
+impl<'a, Message: 'a> From<HistogramView<Message>> for Element<'a, Message> {
+    fn from(widget: HistogramView<Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
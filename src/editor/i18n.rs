@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use super::Theme;
+
+/// The language of the editor's text. This is stored with the plugin's state like the theme.
+///
+/// Adding a translation means adding a variant here and a [`Strings`] for it. Parameter names,
+/// the names of the enum parameters' options, and the exported CSV files are left alone, since
+/// hosts and other tools rely on those.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Language::English => &ENGLISH,
+            Language::German => &GERMAN,
+        }
+    }
+}
+
+impl fmt::Display for Language {
+    /// Every language is listed by its own name, so it can be found without understanding the
+    /// current one.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        })
+    }
+}
+
+/// Every piece of text shown in the editor for a single language. Text with values in it is
+/// produced by a function, so translations can put the values wherever their grammar needs them.
+pub struct Strings {
+    pub dark: &'static str,
+    pub light: &'static str,
+    pub waveform: &'static str,
+    pub spectrogram: &'static str,
+    pub log: &'static str,
+    pub size: &'static str,
+    pub size_tooltip: &'static str,
+    pub language: &'static str,
+
+    pub threshold: &'static str,
+    pub threshold_slider_tooltip: &'static str,
+    pub threshold_value_tooltip: &'static str,
+    pub algorithm: &'static str,
+
+    pub presets: &'static str,
+    pub preset_name: &'static str,
+    pub save: &'static str,
+    pub rename: &'static str,
+    pub delete: &'static str,
+    pub preset_saved: fn(&str) -> String,
+    pub preset_renamed: fn(&str) -> String,
+    pub preset_deleted: fn(&str) -> String,
+    pub preset_failed: fn(&io::Error) -> String,
+    pub presets_unreadable: fn(&io::Error) -> String,
+    pub select_user_preset: &'static str,
+    pub copy_settings: &'static str,
+    pub paste_settings: &'static str,
+    pub settings_copied: &'static str,
+    pub settings_pasted: &'static str,
+    pub clipboard_failed: fn(&arboard::Error) -> String,
+
+    pub analyze: &'static str,
+    pub apply: &'static str,
+    pub reset: &'static str,
+    pub not_analyzed: &'static str,
+    /// The number of detections, the number of seconds analyzed, and the suggested threshold in
+    /// decibels.
+    pub analysis_summary: fn(u32, usize, f32) -> String,
+    pub detections_summary: fn(u32) -> String,
+
+    pub no_repairs: &'static str,
+    /// The total number of repairs and the position of the last one.
+    pub repair_summary: fn(u64, &dyn fmt::Display) -> String,
+    /// The prefix for channel numbers in the log.
+    pub channel: &'static str,
+    pub export: &'static str,
+    pub exported: fn(&Path) -> String,
+    pub export_failed: fn(&io::Error) -> String,
+    pub resized: fn((u32, u32)) -> String,
+
+    pub input: &'static str,
+    pub output: &'static str,
+    pub limit: &'static str,
+    pub clip: &'static str,
+    pub meter_mode: &'static str,
+    /// The CPU load as a percentage of the real-time budget.
+    pub cpu_load: fn(f32) -> String,
+    pub repairs: fn(u64) -> String,
+}
+
+impl Strings {
+    /// The name shown on the button that switches to `theme`.
+    pub fn theme(&self, theme: Theme) -> &'static str {
+        match theme {
+            Theme::Dark => self.dark,
+            Theme::Light => self.light,
+        }
+    }
+}
+
+static ENGLISH: Strings = Strings {
+    dark: "Dark",
+    light: "Light",
+    waveform: "Wave",
+    spectrogram: "Spectrum",
+    log: "Log",
+    size: "Size",
+    size_tooltip: "Takes effect when the editor is reopened",
+    language: "Language",
+
+    threshold: "Threshold",
+    threshold_slider_tooltip: "Double-click to reset, shift-drag to fine-tune",
+    threshold_value_tooltip: "Double-click to type a value",
+    algorithm: "Algorithm",
+
+    presets: "Presets",
+    preset_name: "Preset name",
+    save: "Save",
+    rename: "Rename",
+    delete: "Delete",
+    preset_saved: |name| format!("Saved \"{name}\""),
+    preset_renamed: |name| format!("Renamed to \"{name}\""),
+    preset_deleted: |name| format!("Deleted \"{name}\""),
+    preset_failed: |err| format!("Could not do that: {err}"),
+    presets_unreadable: |err| format!("Could not load the presets: {err}"),
+    select_user_preset: "select a user preset first",
+    copy_settings: "Copy",
+    paste_settings: "Paste",
+    settings_copied: "Copied the settings",
+    settings_pasted: "Pasted the settings",
+    clipboard_failed: |err| format!("Could not use the clipboard: {err}"),
+
+    analyze: "Analyze",
+    apply: "Apply",
+    reset: "Reset",
+    not_analyzed: "Not analyzed yet",
+    analysis_summary: |total, seconds, threshold_db| {
+        format!("{total} in {seconds} s, try {threshold_db:.0} dB")
+    },
+    detections_summary: |total| format!("{total} near-misses and pops"),
+
+    no_repairs: "No repairs yet",
+    repair_summary: |total, position| format!("{total} repairs, last at {position}"),
+    channel: "Ch",
+    export: "Export CSV",
+    exported: |path| format!("Saved to {}", path.display()),
+    export_failed: |err| format!("Could not save the log: {err}"),
+    resized: |(width, height)| format!("{width} × {height} when reopened"),
+
+    input: "In",
+    output: "Out",
+    limit: "LIMIT",
+    clip: "CLIP",
+    meter_mode: "Mode",
+    cpu_load: |percent| format!("CPU {percent:.1}%"),
+    repairs: |total| format!("{total} repairs"),
+};
+
+static GERMAN: Strings = Strings {
+    dark: "Dunkel",
+    light: "Hell",
+    waveform: "Welle",
+    spectrogram: "Spektrum",
+    log: "Log",
+    size: "Größe",
+    size_tooltip: "Wird beim nächsten Öffnen des Editors übernommen",
+    language: "Sprache",
+
+    threshold: "Schwelle",
+    threshold_slider_tooltip: "Doppelklick zum Zurücksetzen, Shift-Ziehen zum Feinjustieren",
+    threshold_value_tooltip: "Doppelklick, um einen Wert einzugeben",
+    algorithm: "Algorithmus",
+
+    presets: "Presets",
+    preset_name: "Name des Presets",
+    save: "Speichern",
+    rename: "Umbenennen",
+    delete: "Löschen",
+    preset_saved: |name| format!("\"{name}\" gespeichert"),
+    preset_renamed: |name| format!("In \"{name}\" umbenannt"),
+    preset_deleted: |name| format!("\"{name}\" gelöscht"),
+    preset_failed: |err| format!("Das hat nicht geklappt: {err}"),
+    presets_unreadable: |err| format!("Die Presets konnten nicht geladen werden: {err}"),
+    select_user_preset: "zuerst ein eigenes Preset auswählen",
+    copy_settings: "Kopieren",
+    paste_settings: "Einfügen",
+    settings_copied: "Einstellungen kopiert",
+    settings_pasted: "Einstellungen eingefügt",
+    clipboard_failed: |err| format!("Die Zwischenablage ist nicht verfügbar: {err}"),
+
+    analyze: "Analysieren",
+    apply: "Übernehmen",
+    reset: "Zurücksetzen",
+    not_analyzed: "Noch nicht analysiert",
+    analysis_summary: |total, seconds, threshold_db| {
+        format!("{total} in {seconds} s, {threshold_db:.0} dB probieren")
+    },
+    detections_summary: |total| format!("{total} Beinahe-Treffer und Knackser"),
+
+    no_repairs: "Noch keine Reparaturen",
+    repair_summary: |total, position| format!("{total} Reparaturen, zuletzt bei {position}"),
+    channel: "K",
+    export: "CSV exportieren",
+    exported: |path| format!("Gespeichert unter {}", path.display()),
+    export_failed: |err| format!("Das Log konnte nicht gespeichert werden: {err}"),
+    resized: |(width, height)| format!("{width} × {height} beim nächsten Öffnen"),
+
+    input: "Ein",
+    output: "Aus",
+    limit: "LIMIT",
+    clip: "CLIP",
+    meter_mode: "Modus",
+    cpu_load: |percent| format!("CPU {percent:.1} %"),
+    repairs: |total| format!("{total} Reparaturen"),
+};
@@ -0,0 +1,133 @@
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer, Size,
+    Widget,
+};
+use std::marker::PhantomData;
+
+use super::theme::Palette;
+use crate::clicks::RepairSnapshot;
+
+/// The original and repaired samples around the last repair drawn on top of each other. The
+/// original is drawn faintly underneath, so it only shows where the repair changed something.
+pub struct Oscilloscope<Message> {
+    snapshot: RepairSnapshot,
+    palette: Palette,
+    width: Length,
+    height: Length,
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> Oscilloscope<Message> {
+    pub fn new(snapshot: RepairSnapshot, palette: Palette) -> Self {
+        Self {
+            snapshot,
+            palette,
+            width: Length::Fill,
+            height: Length::Units(60),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl<Message> Widget<Message, Renderer> for Oscilloscope<Message> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let palette = self.palette;
+        fill(renderer, bounds, palette.panel);
+
+        // Both traces share the same scale, based on the larger of the two, so the pop fits
+        let original = self.snapshot.original();
+        let repaired = self.snapshot.repaired();
+        let peak = original
+            .iter()
+            .chain(repaired)
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        if peak == 0.0 {
+            return;
+        }
+
+        let scale = bounds.height / 2.0 / peak;
+        let center = bounds.center_y();
+        let sample_width = bounds.width / original.len() as f32;
+        for (samples, color) in [
+            (
+                original,
+                Color {
+                    a: 0.4,
+                    ..palette.waveform
+                },
+            ),
+            (repaired, palette.accent),
+        ] {
+            // Every sample is drawn as a vertical line from the previous sample, which reads as a
+            // continuous trace
+            let mut previous = samples[0];
+            for (i, &sample) in samples.iter().enumerate() {
+                let top = center - previous.max(sample) * scale;
+                let bottom = center - previous.min(sample) * scale;
+                fill(
+                    renderer,
+                    Rectangle {
+                        x: bounds.x + i as f32 * sample_width,
+                        y: top,
+                        width: sample_width.max(1.0),
+                        height: (bottom - top).max(1.0),
+                    },
+                    color,
+                );
+                previous = sample;
+            }
+        }
+    }
+}
+
+impl<'a, Message: 'a> From<Oscilloscope<Message>> for Element<'a, Message> {
+    fn from(widget: Oscilloscope<Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
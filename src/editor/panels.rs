@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+/// Which of the editor's optional views are shown. This is stored with the plugin's state like the
+/// theme, so the editor reopens the way it was left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Panels {
+    pub waveform: bool,
+    pub spectrogram: bool,
+    /// The repair log and its export button.
+    pub log: bool,
+}
+
+impl Default for Panels {
+    fn default() -> Self {
+        Self {
+            waveform: true,
+            // This costs a bit of CPU time on the audio thread, so it's opt-in
+            spectrogram: false,
+            log: true,
+        }
+    }
+}
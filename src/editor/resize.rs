@@ -0,0 +1,183 @@
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    event, layout, mouse, renderer, Background, Clipboard, Color, Element, Event, Layout, Length,
+    Point, Rectangle, Renderer, Shell, Size, Widget,
+};
+
+use super::theme::Palette;
+
+/// The width and height of the grip, which sits in the editor's bottom right corner.
+const GRIP_SIZE: f32 = 12.0;
+/// The size of the grip's dots, and the space between them.
+const DOT_SIZE: f32 = 2.0;
+
+/// Where a drag started, and the editor's size at that point.
+#[derive(Debug, Default)]
+pub struct State {
+    drag: Option<(Point, (u32, u32))>,
+}
+
+/// A grip for resizing the editor by dragging it. The new size is reported while dragging, and
+/// once more when the mouse button is released, clamped between `min_size` and `max_size`.
+pub struct ResizeHandle<'a, Message> {
+    state: &'a mut State,
+    size: (u32, u32),
+    min_size: (u32, u32),
+    max_size: (u32, u32),
+    on_drag: fn((u32, u32)) -> Message,
+    on_release: Message,
+    palette: Palette,
+}
+
+impl<'a, Message> ResizeHandle<'a, Message> {
+    pub fn new(
+        state: &'a mut State,
+        size: (u32, u32),
+        on_drag: fn((u32, u32)) -> Message,
+        on_release: Message,
+        palette: Palette,
+    ) -> Self {
+        Self {
+            state,
+            size,
+            min_size: size,
+            max_size: size,
+            on_drag,
+            on_release,
+            palette,
+        }
+    }
+
+    pub fn min_size(mut self, min_size: (u32, u32)) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn max_size(mut self, max_size: (u32, u32)) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+impl<'a, Message: Clone> Widget<Message, Renderer> for ResizeHandle<'a, Message> {
+    fn width(&self) -> Length {
+        Length::Units(GRIP_SIZE as u16)
+    }
+
+    fn height(&self) -> Length {
+        Length::Units(GRIP_SIZE as u16)
+    }
+
+    fn layout(&self, _renderer: &Renderer, _limits: &layout::Limits) -> layout::Node {
+        layout::Node::new(Size::new(GRIP_SIZE, GRIP_SIZE))
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) -> event::Status {
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if layout.bounds().contains(cursor_position) =>
+            {
+                self.state.drag = Some((cursor_position, self.size));
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => {
+                match self.state.drag {
+                    Some((start, (width, height))) => {
+                        // The cursor's position is in the same logical units as the editor's size
+                        let width = (width as f32 + position.x - start.x).round().max(0.0) as u32;
+                        let height = (height as f32 + position.y - start.y).round().max(0.0) as u32;
+                        shell.publish((self.on_drag)((
+                            width.clamp(self.min_size.0, self.max_size.0),
+                            height.clamp(self.min_size.1, self.max_size.1),
+                        )));
+                        event::Status::Captured
+                    }
+                    None => event::Status::Ignored,
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+                if self.state.drag.is_some() =>
+            {
+                self.state.drag = None;
+                shell.publish(self.on_release.clone());
+                event::Status::Captured
+            }
+            _ => event::Status::Ignored,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if self.state.drag.is_some() {
+            mouse::Interaction::Grabbing
+        } else if layout.bounds().contains(cursor_position) {
+            mouse::Interaction::Grab
+        } else {
+            mouse::Interaction::Idle
+        }
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let color = if self.state.drag.is_some() {
+            self.palette.accent
+        } else {
+            self.palette.text
+        };
+
+        // A triangle of dots pointing into the corner, like most windows' resize grips
+        let dots = (GRIP_SIZE / (DOT_SIZE * 2.0)) as usize;
+        for row in 0..dots {
+            for column in (dots - 1 - row)..dots {
+                fill(
+                    renderer,
+                    Rectangle {
+                        x: bounds.x + DOT_SIZE * (column * 2 + 1) as f32,
+                        y: bounds.y + DOT_SIZE * (row * 2 + 1) as f32,
+                        width: DOT_SIZE,
+                        height: DOT_SIZE,
+                    },
+                    color,
+                );
+            }
+        }
+    }
+}
+
+impl<'a, Message: Clone + 'a> From<ResizeHandle<'a, Message>> for Element<'a, Message> {
+    fn from(widget: ResizeHandle<'a, Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
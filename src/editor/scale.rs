@@ -0,0 +1,138 @@
+use nih_plug::prelude::{Editor, GuiContext, ParentWindowHandle};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::RoboDepopParams;
+
+/// The user's choice of editor size, on top of whatever scaling the host or the system applies.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UiScale {
+    #[default]
+    Percent100,
+    Percent125,
+    Percent150,
+    Percent200,
+}
+
+impl UiScale {
+    pub const ALL: [UiScale; 4] = [
+        UiScale::Percent100,
+        UiScale::Percent125,
+        UiScale::Percent150,
+        UiScale::Percent200,
+    ];
+
+    pub fn factor(self) -> f32 {
+        match self {
+            UiScale::Percent100 => 1.0,
+            UiScale::Percent125 => 1.25,
+            UiScale::Percent150 => 1.5,
+            UiScale::Percent200 => 2.0,
+        }
+    }
+}
+
+impl fmt::Display for UiScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.0}%", self.factor() * 100.0)
+    }
+}
+
+/// Applies the [`UiScale`] to the iced editor. The iced editor can only be scaled and resized when
+/// its window is created, so a new scale or size takes effect the next time the editor is opened.
+pub struct ScaledEditor {
+    inner: Box<dyn Editor>,
+    params: Arc<RoboDepopParams>,
+    /// The scale factor the host asked for, if it asked for one.
+    host_scale_factor: Mutex<Option<f32>>,
+    /// The UI scale the editor was last opened with. Picking another scale doesn't affect the
+    /// open editor, so the host should keep seeing the old size until it's reopened.
+    open_ui_scale: Mutex<f32>,
+    /// The same for the size the editor was last opened with, before scaling.
+    open_size: Mutex<(u32, u32)>,
+}
+
+impl ScaledEditor {
+    pub fn new(inner: Box<dyn Editor>, params: Arc<RoboDepopParams>) -> Self {
+        let open_size = inner.size();
+        Self {
+            inner,
+            params,
+            host_scale_factor: Mutex::new(None),
+            open_ui_scale: Mutex::new(1.0),
+            open_size: Mutex::new(open_size),
+        }
+    }
+
+    fn ui_scale(&self) -> f32 {
+        if self.params.editor_state.is_open() {
+            *self.open_ui_scale.lock()
+        } else {
+            self.params.editor_scale.lock().factor()
+        }
+    }
+
+    fn unscaled_size(&self) -> (u32, u32) {
+        if self.params.editor_state.is_open() {
+            *self.open_size.lock()
+        } else {
+            self.inner.size()
+        }
+    }
+}
+
+impl Editor for ScaledEditor {
+    fn spawn(
+        &self,
+        parent: ParentWindowHandle,
+        context: Arc<dyn GuiContext>,
+    ) -> Box<dyn Any + Send> {
+        // Without a scale factor from the host the system's scale factor is used, which should be
+        // left alone unless the user picked a different size
+        let host_scale_factor = *self.host_scale_factor.lock();
+        let ui_scale = self.params.editor_scale.lock().factor();
+        *self.open_ui_scale.lock() = ui_scale;
+        *self.open_size.lock() = self.inner.size();
+        if host_scale_factor.is_some() || ui_scale != 1.0 {
+            self.inner
+                .set_scale_factor(host_scale_factor.unwrap_or(1.0) * ui_scale);
+        }
+
+        self.inner.spawn(parent, context)
+    }
+
+    fn size(&self) -> (u32, u32) {
+        // The host applies its own scale factor on top of this
+        let (width, height) = self.unscaled_size();
+        let ui_scale = self.ui_scale();
+
+        (
+            (width as f32 * ui_scale).round() as u32,
+            (height as f32 * ui_scale).round() as u32,
+        )
+    }
+
+    fn set_scale_factor(&self, factor: f32) -> bool {
+        let accepted = self.inner.set_scale_factor(factor * self.ui_scale());
+        if accepted {
+            *self.host_scale_factor.lock() = Some(factor);
+        }
+
+        accepted
+    }
+
+    fn param_value_changed(&self, id: &str, normalized_value: f32) {
+        self.inner.param_value_changed(id, normalized_value)
+    }
+
+    fn param_modulation_changed(&self, id: &str, modulation_offset: f32) {
+        self.inner.param_modulation_changed(id, modulation_offset)
+    }
+
+    fn param_values_changed(&self) {
+        self.inner.param_values_changed()
+    }
+}
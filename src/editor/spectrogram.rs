@@ -0,0 +1,120 @@
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer, Size,
+    Widget,
+};
+use std::marker::PhantomData;
+
+use super::theme::Palette;
+use crate::spectrogram::{MIN_DB, NUM_BANDS};
+use crate::waveform::NUM_COLUMNS;
+
+/// A scrolling spectrogram with the low frequencies at the bottom. The columns line up with the
+/// [`Waveform`][super::waveform::Waveform]'s, so broadband clicks show up as vertical lines right
+/// below the repairs.
+pub struct Spectrogram<Message> {
+    columns: Vec<[f32; NUM_BANDS]>,
+    palette: Palette,
+    width: Length,
+    height: Length,
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> Spectrogram<Message> {
+    /// Show `columns`, from oldest to newest. Missing columns are left empty on the left side.
+    pub fn new(columns: Vec<[f32; NUM_BANDS]>, palette: Palette) -> Self {
+        Self {
+            columns,
+            palette,
+            width: Length::Fill,
+            height: Length::Units(60),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl<Message> Widget<Message, Renderer> for Spectrogram<Message> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let palette = self.palette;
+        fill(renderer, bounds, palette.panel);
+
+        let column_width = bounds.width / NUM_COLUMNS as f32;
+        let band_height = bounds.height / NUM_BANDS as f32;
+        let first_column = NUM_COLUMNS.saturating_sub(self.columns.len());
+        for (i, column) in self.columns.iter().enumerate() {
+            let x = bounds.x + (first_column + i) as f32 * column_width;
+            for (band, &level) in column.iter().enumerate() {
+                let intensity = ((level - MIN_DB) / -MIN_DB).clamp(0.0, 1.0);
+                // Drawing the quiet bands would only hide the background
+                if intensity < 0.05 {
+                    continue;
+                }
+
+                fill(
+                    renderer,
+                    Rectangle {
+                        x,
+                        y: bounds.y + bounds.height - (band + 1) as f32 * band_height,
+                        width: column_width,
+                        height: band_height,
+                    },
+                    Color {
+                        a: intensity,
+                        ..palette.waveform
+                    },
+                );
+            }
+        }
+    }
+}
+
+impl<'a, Message: 'a> From<Spectrogram<Message>> for Element<'a, Message> {
+    fn from(widget: Spectrogram<Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
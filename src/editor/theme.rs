@@ -0,0 +1,94 @@
+use nih_plug_iced::{button, Background, Color, Vector};
+use serde::{Deserialize, Serialize};
+
+/// The editor's color scheme. This is stored with the plugin's state so it's restored with the
+/// project.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub fn other(self) -> Self {
+        match self {
+            Theme::Dark => Theme::Light,
+            Theme::Light => Theme::Dark,
+        }
+    }
+
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Dark => Palette {
+                background: Color::from_rgb(0.13, 0.13, 0.14),
+                text: Color::from_rgb(0.9, 0.9, 0.9),
+                panel: Color::from_rgb(0.2, 0.2, 0.22),
+                waveform: Color::from_rgb(0.75, 0.75, 0.75),
+                accent: Color::from_rgb(0.95, 0.3, 0.3),
+            },
+            Theme::Light => Palette {
+                background: Color::from_rgb(0.98, 0.98, 0.98),
+                text: Color::BLACK,
+                panel: Color::from_rgb(0.92, 0.92, 0.92),
+                waveform: Color::from_rgb(0.3, 0.3, 0.3),
+                accent: Color::from_rgb(0.8, 0.1, 0.1),
+            },
+        }
+    }
+}
+
+/// The colors used throughout the editor for a [`Theme`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub background: Color,
+    pub text: Color,
+    /// The background of the waveform and the buttons.
+    pub panel: Color,
+    pub waveform: Color,
+    /// Repairs, the limiter indicator, and anything else that should stand out.
+    pub accent: Color,
+}
+
+impl button::StyleSheet for Palette {
+    fn active(&self) -> button::Style {
+        button::Style {
+            shadow_offset: Vector::default(),
+            background: Some(Background::Color(self.panel)),
+            border_radius: 2.0,
+            border_width: 1.0,
+            border_color: self.waveform,
+            text_color: self.text,
+        }
+    }
+
+    fn hovered(&self) -> button::Style {
+        button::Style {
+            border_color: self.text,
+            ..self.active()
+        }
+    }
+}
+
+/// An indicator light drawn as a button, lit up in the accent color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Led {
+    pub palette: Palette,
+    pub lit: bool,
+}
+
+impl button::StyleSheet for Led {
+    fn active(&self) -> button::Style {
+        let style = button::StyleSheet::active(&self.palette);
+        if self.lit {
+            button::Style {
+                background: Some(Background::Color(self.palette.accent)),
+                border_color: self.palette.accent,
+                text_color: Color::WHITE,
+                ..style
+            }
+        } else {
+            style
+        }
+    }
+}
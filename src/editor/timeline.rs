@@ -0,0 +1,160 @@
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer, Size,
+    Widget,
+};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use super::theme::Palette;
+
+/// How far back the timeline goes.
+pub const TIMELINE_SPAN: Duration = Duration::from_secs(30);
+/// The distance between the timeline's grid lines.
+const GRID_INTERVAL: Duration = Duration::from_secs(5);
+
+/// When the editor saw new repairs come in, and how many, going back [`TIMELINE_SPAN`].
+#[derive(Debug, Default)]
+pub struct RepairTimes {
+    marks: VecDeque<(Instant, u64)>,
+}
+
+impl RepairTimes {
+    /// Add `count` repairs that were made since the last frame, and forget the ones that have
+    /// scrolled off the timeline.
+    pub fn record(&mut self, now: Instant, count: u64) {
+        if count > 0 {
+            self.marks.push_back((now, count));
+        }
+        while self
+            .marks
+            .front()
+            .is_some_and(|&(time, _)| now.duration_since(time) > TIMELINE_SPAN)
+        {
+            self.marks.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.marks.clear();
+    }
+
+    /// How long ago each mark was made and how many repairs it stands for, from newest to oldest.
+    pub fn marks(&self, now: Instant) -> Vec<(Duration, u64)> {
+        self.marks
+            .iter()
+            .rev()
+            .map(|&(time, count)| (now.duration_since(time), count))
+            .collect()
+    }
+}
+
+/// A strip scrolling from right to left with a tick for every repair, so the density of the
+/// repairs over the last half minute can be seen at a glance.
+pub struct Timeline<Message> {
+    marks: Vec<(Duration, u64)>,
+    palette: Palette,
+    width: Length,
+    height: Length,
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> Timeline<Message> {
+    /// Show the marks from [`RepairTimes::marks()`].
+    pub fn new(marks: Vec<(Duration, u64)>, palette: Palette) -> Self {
+        Self {
+            marks,
+            palette,
+            width: Length::Fill,
+            height: Length::Units(16),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<Message> Widget<Message, Renderer> for Timeline<Message> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let palette = self.palette;
+        fill(renderer, bounds, palette.panel);
+
+        // The present is on the right edge
+        let x_for_age = |age: Duration| {
+            bounds.x + bounds.width * (1.0 - age.as_secs_f32() / TIMELINE_SPAN.as_secs_f32())
+        };
+        let grid_color = Color {
+            a: 0.3,
+            ..palette.waveform
+        };
+        for line in 1..(TIMELINE_SPAN.as_secs() / GRID_INTERVAL.as_secs()) {
+            let x = x_for_age(GRID_INTERVAL * line as u32);
+            fill(
+                renderer,
+                Rectangle {
+                    x,
+                    width: 1.0,
+                    ..bounds
+                },
+                grid_color,
+            );
+        }
+
+        for &(age, count) in &self.marks {
+            // A single repair is already clearly visible, and bursts stand out more
+            let alpha = (0.5 + 0.1 * count as f32).min(1.0);
+            fill(
+                renderer,
+                Rectangle {
+                    x: x_for_age(age).min(bounds.x + bounds.width - 1.0),
+                    width: 1.0,
+                    ..bounds
+                },
+                Color {
+                    a: alpha,
+                    ..palette.accent
+                },
+            );
+        }
+    }
+}
+
+impl<'a, Message: 'a> From<Timeline<Message>> for Element<'a, Message> {
+    fn from(widget: Timeline<Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
@@ -0,0 +1,75 @@
+use nih_plug_iced::{button, text_input, Button, Element, Text, TextInput};
+use std::time::{Duration, Instant};
+
+use super::theme::Palette;
+
+/// Two presses on the value this close together count as a double click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+/// A parameter's value as text, which turns into a text field to type in a new value when it's
+/// double clicked. The threshold is the only continuous parameter the editor has a control for, so
+/// it's the only one with an entry. The others are switches and lists, which have nothing to type.
+#[derive(Debug, Default)]
+pub struct ValueEntry {
+    button_state: button::State,
+    input_state: text_input::State,
+    /// The text being typed, while the text field is open.
+    text: Option<String>,
+    last_press: Option<Instant>,
+}
+
+impl ValueEntry {
+    /// Whether the text field is open, in which case keyboard shortcuts should be left alone.
+    pub fn is_open(&self) -> bool {
+        self.text.is_some()
+    }
+
+    /// Handle a click on the value. The second click of a double click opens the text field,
+    /// starting out with `value` selected.
+    pub fn press(&mut self, value: String) {
+        let now = Instant::now();
+        match self.last_press {
+            Some(last_press) if now - last_press < DOUBLE_CLICK_TIME => {
+                self.text = Some(value);
+                self.input_state.focus();
+                self.input_state.select_all();
+                self.last_press = None;
+            }
+            _ => self.last_press = Some(now),
+        }
+    }
+
+    pub fn edit(&mut self, text: String) {
+        if self.text.is_some() {
+            self.text = Some(text);
+        }
+    }
+
+    /// Close the text field, returning what was typed.
+    pub fn close(&mut self) -> Option<String> {
+        self.input_state.unfocus();
+        self.text.take()
+    }
+
+    pub fn view<'a, Message: Clone + 'a>(
+        &'a mut self,
+        value: String,
+        palette: Palette,
+        on_press: Message,
+        on_edit: impl Fn(String) -> Message + 'a,
+        on_submit: Message,
+    ) -> Element<'a, Message> {
+        match &self.text {
+            Some(text) => TextInput::new(&mut self.input_state, "", text, on_edit)
+                .size(12)
+                .padding(3)
+                .width(80.into())
+                .on_submit(on_submit)
+                .into(),
+            None => Button::new(&mut self.button_state, Text::new(value).size(12))
+                .style(palette)
+                .on_press(on_press)
+                .into(),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use nih_plug_iced::renderer::Renderer as GraphicsRenderer;
+use nih_plug_iced::{
+    layout, renderer, Background, Color, Element, Layout, Length, Point, Rectangle, Renderer, Size,
+    Widget,
+};
+use std::marker::PhantomData;
+
+use super::theme::Palette;
+use crate::waveform::Column;
+
+/// A scrolling view of the output, drawn as one min/max line per column. Columns in which a sample
+/// was repaired are highlighted.
+pub struct Waveform<Message> {
+    columns: Vec<Column>,
+    palette: Palette,
+    width: Length,
+    height: Length,
+    _phantom: PhantomData<Message>,
+}
+
+impl<Message> Waveform<Message> {
+    /// Show `columns`, from oldest to newest.
+    pub fn new(columns: Vec<Column>, palette: Palette) -> Self {
+        Self {
+            columns,
+            palette,
+            width: Length::Fill,
+            height: Length::Units(60),
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+}
+
+impl<Message> Widget<Message, Renderer> for Waveform<Message> {
+    fn width(&self) -> Length {
+        self.width
+    }
+
+    fn height(&self) -> Length {
+        self.height
+    }
+
+    fn layout(&self, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let limits = limits.width(self.width).height(self.height);
+        let size = limits.resolve(Size::ZERO);
+
+        layout::Node::new(size)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor_position: Point,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let palette = self.palette;
+        fill(renderer, bounds, palette.panel);
+
+        let column_width = bounds.width / self.columns.len().max(1) as f32;
+        let center = bounds.center_y();
+        let scale = bounds.height / 2.0;
+        for (i, column) in self.columns.iter().enumerate() {
+            let x = bounds.x + i as f32 * column_width;
+            if column.repaired {
+                fill(
+                    renderer,
+                    Rectangle {
+                        x,
+                        width: column_width,
+                        ..bounds
+                    },
+                    Color {
+                        a: 0.25,
+                        ..palette.accent
+                    },
+                );
+            }
+            if column.is_empty() {
+                continue;
+            }
+
+            let top = center - column.max.clamp(-1.0, 1.0) * scale;
+            let bottom = center - column.min.clamp(-1.0, 1.0) * scale;
+            fill(
+                renderer,
+                Rectangle {
+                    x,
+                    y: top,
+                    width: column_width,
+                    // Silence should still show up as a line
+                    height: (bottom - top).max(1.0),
+                },
+                if column.repaired {
+                    palette.accent
+                } else {
+                    palette.waveform
+                },
+            );
+        }
+    }
+}
+
+impl<'a, Message: 'a> From<Waveform<Message>> for Element<'a, Message> {
+    fn from(widget: Waveform<Message>) -> Self {
+        Element::new(widget)
+    }
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        renderer::Quad {
+            bounds,
+            border_radius: 0.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        },
+        Background::Color(color),
+    );
+}
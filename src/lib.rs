@@ -7,69 +7,404 @@
 use atomic_float::AtomicF32;
 use core::f32;
 use nih_plug::prelude::*;
+#[cfg(feature = "gui")]
 use nih_plug_iced::IcedState;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-
+use std::time::Instant;
+
+mod algorithm;
+mod analysis;
+mod clicks;
+mod compare;
+mod compensation;
+mod crossover;
+#[cfg(feature = "gui")]
 mod editor;
-
-/// The time it takes for the peak meter to decay by 12 dB after switching to complete silence.
-const PEAK_METER_DECAY_MS: f64 = 150.0;
-
-/// This is mostly identical to the gain example, minus some fluff, and with a GUI.
-pub struct Gain {
-    params: Arc<GainParams>,
-
-    /// Needed to normalize the peak meter's response based on the sample rate.
-    peak_meter_decay_weight: f32,
+mod gain;
+mod limiter;
+mod meter;
+mod midi;
+mod presets;
+mod ring;
+mod spectrogram;
+mod state;
+mod waveform;
+
+pub use algorithm::{Algorithm, MAX_RADIUS};
+use analysis::{AnalysisResult, AnalysisTask, DetectionHistory, DetectionRecorder, Histogram};
+use clicks::{BlockPosition, Click, ClickLog, RepairSnapshot};
+use compare::CompareSlots;
+use compensation::GainCompensation;
+use crossover::{Crossover, MAX_BANDS};
+pub use gain::Gain;
+use meter::{Ballistics, BlockLevels, MeterMode, PeakDetector};
+use midi::MidiControl;
+use presets::Preset;
+use ring::SampleRing;
+use waveform::{WaveformHistory, WaveformRecorder};
+
+/// Parameters that control monitoring rather than the repair itself, and which are thus left alone
+/// when switching between the A/B compare slots.
+const NOT_COMPARED: &[&str] = &[
+    "bypass",
+    "listen",
+    "bypass-cc",
+    "listen-cc",
+    "threshold-cc",
+    "meter-decay",
+    "meter-hold",
+    "true-peak",
+    "meter-mode",
+];
+
+/// The block size the buffer is split into during processing.
+const BLOCK_SIZE: usize = 128;
+
+/// The number of times an offline render runs over every block. Repairing a large pop can reveal a
+/// smaller one next to it that was hidden by the large pop widening the window's range.
+const OFFLINE_PASSES: usize = 2;
+
+/// The latency in lookahead mode. Every window then has real samples on both sides, even at the
+/// edges of a block, regardless of the window length.
+const LOOKAHEAD_SAMPLES: usize = algorithm::MAX_RADIUS;
+
+/// The maximum number of channels in any of the supported audio IO layouts.
+const MAX_CHANNELS: usize = 2;
+
+/// The default time it takes for the peak meter to decay by 12 dB after switching to complete
+/// silence.
+const PEAK_METER_DECAY_MS: f32 = 150.0;
+/// The default time the peak meter holds on to its highest value.
+const PEAK_METER_HOLD_MS: f32 = 600.0;
+/// The time constant of the CPU load reading, so it can actually be read instead of jumping
+/// around with every buffer.
+const CPU_LOAD_SECONDS: f32 = 0.5;
+
+/// The de-popping plugin. This started out as the gain example, minus some fluff, and with a GUI.
+pub struct RoboDepop {
+    params: Arc<RoboDepopParams>,
+
+    /// Whether the plugin runs in the mono to stereo layout, in which case only the first channel
+    /// is processed and then copied to the second one.
+    upmix_mono: bool,
+
+    /// Needed to normalize the peak meter's response and the detection window based on the sample
+    /// rate.
+    sample_rate: f32,
+    /// The number of samples on either side of the sample being checked, derived from the window
+    /// parameter and the sample rate.
+    window_radius: usize,
+    /// Whether the host is rendering offline, in which case there's time for the slower high
+    /// quality repair.
+    offline_render: bool,
+    /// Whether lookahead was enabled during the last process call, to notice when the reported
+    /// latency needs to change.
+    lookahead: bool,
+    /// The last `2 * LOOKAHEAD_SAMPLES` input samples of every band of every channel in lookahead
+    /// mode. The first half has already been repaired and sent to the output.
+    lookahead_history: [[[f32; 2 * LOOKAHEAD_SAMPLES]; MAX_BANDS]; MAX_CHANNELS],
+    /// Splits the channels into bands when multiband processing is enabled.
+    crossovers: [Crossover; MAX_CHANNELS],
+    /// Find the input's and the output's (true) peaks for the peak meters.
+    input_peak_detectors: [PeakDetector; MAX_CHANNELS],
+    peak_detectors: [PeakDetector; MAX_CHANNELS],
+    /// The meters' attack and release behavior for the input and the output.
+    input_ballistics: Ballistics,
+    ballistics: Ballistics,
     /// The current data for the peak meter. This is stored as an [`Arc`] so we can share it between
     /// the GUI and the audio processing parts. If you have more state to share, then it's a good
     /// idea to put all of that in a struct behind a single `Arc`.
     ///
     /// This is stored as voltage gain.
     peak_meter: Arc<AtomicF32>,
+    /// The same as `peak_meter`, but for the input before it's repaired.
+    input_peak_meter: Arc<AtomicF32>,
+    /// Makes up for the level lost (or gained) by repairing when auto gain is enabled.
+    gain_compensation: GainCompensation,
+    /// Set whenever the output limiter had to reduce the level. The editor clears it again.
+    limiter_engaged: Arc<AtomicBool>,
+    /// Set whenever an output sample reached 0 dBFS. This stays set until the user clears the
+    /// editor's clip indicator.
+    clipped: Arc<AtomicBool>,
+    /// How long `process()` takes as a fraction of the time the buffer takes to play back, so 1.0
+    /// means the plugin barely keeps up. This is smoothed over `CPU_LOAD_SECONDS`.
+    cpu_load: Arc<AtomicF32>,
+
+    /// Bypass, listen, and threshold changes made through MIDI CCs.
+    midi_control: MidiControl,
+
+    /// Collects how far samples stray from their neighbours. The history is analyzed on the
+    /// background thread when requested from the GUI, and the result is shared with the editor.
+    detection_recorder: DetectionRecorder,
+    detection_history: Arc<Mutex<DetectionHistory>>,
+    analysis_result: Arc<Mutex<Option<AnalysisResult>>>,
+
+    /// The output's recent waveform along with where repairs happened, for the editor.
+    waveform_recorder: WaveformRecorder,
+    waveform: Arc<Mutex<WaveformHistory>>,
+    /// The output mixed down to mono for the editor's spectrogram, which is computed on the
+    /// editor's thread. This is only written to while the spectrogram is shown.
+    spectrogram_samples: Arc<SampleRing>,
+    spectrogram_enabled: Arc<AtomicBool>,
+
+    /// Every repair along with its position in the host's timeline, shared with the editor.
+    click_log: Arc<Mutex<ClickLog>>,
+    /// The block around the most recent repair, for the editor's before and after view.
+    last_repair: Arc<Mutex<RepairSnapshot>>,
+    /// The timeline position of the current process call, and the offset of the current block
+    /// from it. The offset includes the latency, so the logged positions are those of the audio
+    /// that was actually repaired.
+    block_position: BlockPosition,
+    block_offset: i64,
 
     working_buffer: Vec<f32>,
+    /// The unprocessed block, which is turned into the removed signal (the original minus the
+    /// repaired samples) once the block has been repaired. The bands' removed signals are summed up
+    /// for listen mode and the auxiliary output.
+    removed_buffer: Vec<f32>,
 }
 
 #[derive(Params)]
-struct GainParams {
-    /// The editor state, saved together with the parameter state so the custom scaling can be
-    /// restored.
+struct RoboDepopParams {
+    /// The layout version of the saved state, see [`state::migrate()`].
+    #[persist = "state-version"]
+    state_version: Mutex<u32>,
+
+    /// The editor state, saved together with the parameter state so the editor opens at the size
+    /// it was resized to.
+    #[cfg(feature = "gui")]
     #[persist = "editor-state"]
     editor_state: Arc<IcedState>,
+    /// The same for the egui editor, which is used instead of the iced editor when the `egui`
+    /// feature is enabled.
+    #[cfg(feature = "egui")]
+    #[persist = "egui-editor-state"]
+    egui_editor_state: Arc<nih_plug_egui::EguiState>,
+    /// The editor's color scheme, kept next to the editor state so it survives reopening the
+    /// editor and reloading the project.
+    #[cfg(feature = "gui")]
+    #[persist = "editor-theme"]
+    editor_theme: Mutex<editor::Theme>,
+    /// The editor's size relative to the host's or the system's scaling.
+    #[cfg(feature = "gui")]
+    #[persist = "editor-scale"]
+    editor_scale: Mutex<editor::UiScale>,
+    /// Which of the editor's optional views are shown.
+    #[cfg(feature = "gui")]
+    #[persist = "editor-panels"]
+    editor_panels: Mutex<editor::Panels>,
+    /// The language of the editor's text.
+    #[cfg(feature = "gui")]
+    #[persist = "editor-language"]
+    editor_language: Mutex<editor::Language>,
+
+    /// The A/B compare snapshots. These are only ever touched from the editor.
+    #[persist = "compare-slots"]
+    compare_slots: Mutex<CompareSlots>,
+
+    /// The parameters are split into groups so hosts' generic editors show them in some sensible
+    /// order. The groups don't affect the parameter IDs, so older sessions still load.
+    #[nested(group = "Detection")]
+    pub detection: DetectionParams,
+    #[nested(group = "Repair")]
+    pub repair: RepairParams,
+    #[nested(group = "Output")]
+    pub output: OutputParams,
+    #[nested(group = "MIDI")]
+    pub midi: midi::MidiParams,
+    #[nested(group = "Metering")]
+    pub metering: MeteringParams,
+}
+
+#[derive(Params)]
+struct DetectionParams {
+    /// How a sample's expected value is estimated, and thus what it's replaced with.
+    #[id = "algorithm"]
+    pub algorithm: EnumParam<Algorithm>,
+
+    /// The length of the window a sample is compared against, in microseconds so the detection
+    /// behaves the same at every sample rate. This is converted to a number of samples when the
+    /// plugin is initialized.
+    #[id = "window"]
+    pub window_us: FloatParam,
+
+    /// How far a sample may stray from its neighbours, as a multiple of the neighbours' own
+    /// spread, before it is considered a pop. Lower values make the detection more sensitive.
+    #[id = "threshold"]
+    pub threshold: FloatParam,
+}
+
+#[derive(Params)]
+struct RepairParams {
+    /// Switch to LPC with a wider window and multiple passes when the host renders offline.
+    #[id = "offline-hq"]
+    pub offline_hq: BoolParam,
+    /// Delay the output so that pops right at the edge of a processing block are detected too.
+    /// Changing this changes the plugin's latency.
+    #[id = "lookahead"]
+    pub lookahead: BoolParam,
+
+    /// Split the signal into this many bands and repair all but the lowest one separately. Pops
+    /// mostly consist of high frequencies, so this leaves the low end of the program material
+    /// alone entirely.
+    #[id = "bands"]
+    pub bands: IntParam,
+    /// The crossover frequencies between the bands. The high crossover is only used with three
+    /// bands.
+    #[id = "crossover-low"]
+    pub crossover_low: FloatParam,
+    #[id = "crossover-high"]
+    pub crossover_high: FloatParam,
+
+    /// Per-channel switches so a pop confined to one channel can be treated without running the
+    /// other channels through the detector.
+    #[nested(array, group = "Channels")]
+    pub channels: [ChannelParams; MAX_CHANNELS],
+}
+
+#[derive(Params)]
+struct ChannelParams {
+    /// This parameter's ID will get a `_1` and `_2` suffix because of how it's used in `channels`
+    /// above.
+    #[id = "enabled"]
+    pub enabled: BoolParam,
+}
+
+#[derive(Params)]
+struct OutputParams {
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+    /// Output only what the algorithm removes (the original minus the repaired signal).
+    #[id = "listen"]
+    pub listen: BoolParam,
+
+    /// Match the output's level to the input's, so comparing the processed signal with the bypassed
+    /// one isn't biased by the level difference.
+    #[id = "auto-gain"]
+    pub auto_gain: BoolParam,
+
+    /// Softly limit the output so that the replacement values can never push it over 0 dBFS.
+    #[id = "limiter"]
+    pub limiter: BoolParam,
+}
 
-    #[id = "gain"]
-    pub gain: FloatParam,
+/// The peak meter's ballistics. These only affect the editor, so they're not automatable.
+#[derive(Params)]
+struct MeteringParams {
+    #[id = "meter-decay"]
+    pub decay_ms: FloatParam,
+    #[id = "meter-hold"]
+    pub hold_ms: FloatParam,
+    /// Whether the peak meter also catches the peaks between samples.
+    #[id = "true-peak"]
+    pub true_peak: BoolParam,
+    /// Whether the meters show peaks, RMS, or VU levels.
+    #[id = "meter-mode"]
+    pub mode: EnumParam<MeterMode>,
 }
 
-impl Default for Gain {
+impl Default for RoboDepop {
     fn default() -> Self {
-        Self {
-            params: Arc::new(GainParams::default()),
+        let detection_history = Arc::new(Mutex::new(DetectionHistory::default()));
+        let waveform = Arc::new(Mutex::new(WaveformHistory::default()));
 
-            peak_meter_decay_weight: 1.0,
+        Self {
+            params: Arc::new(RoboDepopParams::default()),
+
+            upmix_mono: false,
+
+            sample_rate: 1.0,
+            window_radius: 2,
+            offline_render: false,
+            lookahead: false,
+            lookahead_history: [[[0.0; 2 * LOOKAHEAD_SAMPLES]; MAX_BANDS]; MAX_CHANNELS],
+            crossovers: [Crossover::default(); MAX_CHANNELS],
+            input_peak_detectors: [PeakDetector::default(); MAX_CHANNELS],
+            peak_detectors: [PeakDetector::default(); MAX_CHANNELS],
+            input_ballistics: Ballistics::default(),
+            ballistics: Ballistics::default(),
             peak_meter: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
+            input_peak_meter: Arc::new(AtomicF32::new(util::MINUS_INFINITY_DB)),
+            gain_compensation: GainCompensation::default(),
+            limiter_engaged: Arc::new(AtomicBool::new(false)),
+            clipped: Arc::new(AtomicBool::new(false)),
+            cpu_load: Arc::new(AtomicF32::new(0.0)),
+            midi_control: MidiControl::default(),
+            detection_recorder: DetectionRecorder::new(detection_history.clone()),
+            detection_history,
+            analysis_result: Arc::new(Mutex::new(None)),
+            waveform_recorder: WaveformRecorder::new(waveform.clone()),
+            waveform,
+            spectrogram_samples: Arc::new(SampleRing::new(spectrogram::RING_SIZE)),
+            spectrogram_enabled: Arc::new(AtomicBool::new(false)),
+            click_log: Arc::new(Mutex::new(ClickLog::default())),
+            last_repair: Arc::new(Mutex::new(RepairSnapshot::default())),
+            block_position: BlockPosition::default(),
+            block_offset: 0,
             working_buffer: Vec::new(),
+            removed_buffer: Vec::new(),
         }
     }
 }
 
-impl Default for GainParams {
+impl Default for RoboDepopParams {
     fn default() -> Self {
         Self {
+            state_version: Mutex::new(state::STATE_VERSION),
+            #[cfg(feature = "gui")]
             editor_state: editor::default_state(),
+            #[cfg(feature = "egui")]
+            egui_editor_state: editor::egui_editor::default_state(),
+            #[cfg(feature = "gui")]
+            editor_theme: Mutex::new(editor::Theme::default()),
+            #[cfg(feature = "gui")]
+            editor_scale: Mutex::new(editor::UiScale::default()),
+            #[cfg(feature = "gui")]
+            editor_panels: Mutex::new(editor::Panels::default()),
+            #[cfg(feature = "gui")]
+            editor_language: Mutex::new(editor::Language::default()),
+            compare_slots: Mutex::new(CompareSlots::default()),
+
+            detection: DetectionParams::default(),
+            repair: RepairParams::default(),
+            output: OutputParams::default(),
+            midi: midi::MidiParams::default(),
+            metering: MeteringParams::default(),
+        }
+    }
+}
 
-            // See the main gain example for more details
-            gain: FloatParam::new(
-                "Gain",
-                util::db_to_gain(0.0),
+impl Default for DetectionParams {
+    fn default() -> Self {
+        Self {
+            algorithm: EnumParam::new("Algorithm", Algorithm::default()),
+            // The default spans five samples at 44.1 kHz, which is what the algorithm was designed
+            // around
+            window_us: FloatParam::new(
+                "Window",
+                113.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 1500.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_unit(" µs")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .non_automatable(),
+            // Stored as the plain multiplier used by the algorithm, but shown in decibels since
+            // that's how the distance from the neighbouring samples is usually thought about
+            threshold: FloatParam::new(
+                "Threshold",
+                2.0,
                 FloatRange::Skewed {
-                    min: util::db_to_gain(-30.0),
-                    max: util::db_to_gain(30.0),
-                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
                 },
             )
-            .with_smoother(SmoothingStyle::Logarithmic(50.0))
             .with_unit(" dB")
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
@@ -77,157 +412,662 @@ impl Default for GainParams {
     }
 }
 
-impl Plugin for Gain {
-    const NAME: &'static str = "Gain GUI (iced)";
+impl Default for RepairParams {
+    fn default() -> Self {
+        Self {
+            offline_hq: BoolParam::new("High Quality Offline Render", true),
+            lookahead: BoolParam::new("Lookahead", false).non_automatable(),
+            bands: IntParam::new(
+                "Bands",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_BANDS as i32,
+                },
+            )
+            .non_automatable(),
+            crossover_low: crossover_param("Low Crossover", 1_000.0),
+            crossover_high: crossover_param("High Crossover", 6_000.0),
+            channels: [1, 2].map(|index| ChannelParams {
+                enabled: BoolParam::new(format!("Channel {index} Enabled"), true),
+            }),
+        }
+    }
+}
+
+fn crossover_param(name: &'static str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: 200.0,
+            max: 16_000.0,
+            factor: FloatRange::skew_factor(-1.5),
+        },
+    )
+    .with_unit(" Hz")
+    .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+    .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+}
+
+impl Default for OutputParams {
+    fn default() -> Self {
+        Self {
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+            listen: BoolParam::new("Listen", false),
+            auto_gain: BoolParam::new("Auto Gain", false),
+            limiter: BoolParam::new("Output Limiter", false),
+        }
+    }
+}
+
+impl Default for MeteringParams {
+    fn default() -> Self {
+        Self {
+            decay_ms: FloatParam::new(
+                "Meter Decay",
+                PEAK_METER_DECAY_MS,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .non_automatable(),
+            hold_ms: FloatParam::new(
+                "Meter Hold",
+                PEAK_METER_HOLD_MS,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 5000.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(10.0)
+            .with_value_to_string(formatters::v2s_f32_rounded(0))
+            .non_automatable(),
+            true_peak: BoolParam::new("True Peak Meter", true).non_automatable(),
+            mode: EnumParam::new("Meter Mode", MeterMode::default()).non_automatable(),
+        }
+    }
+}
+
+/// The presets that ship with the plugin. These start out from the default values, and like the
+/// user presets they leave the monitoring parameters alone.
+fn factory_presets() -> Vec<Preset> {
+    let params = RoboDepopParams::default();
+    let defaults = compare::capture(&params, NOT_COMPARED);
+    let preset = |name: &str, values: &[(&str, f32)]| {
+        let mut snapshot = defaults.clone();
+        for &(id, normalized) in values {
+            snapshot.insert(String::from(id), normalized);
+        }
+
+        Preset {
+            name: String::from(name),
+            snapshot,
+            factory: true,
+        }
+    };
+    let detection = &params.detection;
+    let repair = &params.repair;
+
+    vec![
+        preset("Default", &[]),
+        // Only the obvious pops, for material with lots of transients
+        preset(
+            "Gentle",
+            &[
+                (
+                    "algorithm",
+                    detection.algorithm.preview_normalized(Algorithm::Median),
+                ),
+                (
+                    "threshold",
+                    detection
+                        .threshold
+                        .preview_normalized(util::db_to_gain(12.0)),
+                ),
+            ],
+        ),
+        // Worn records, where a wider window and the better estimate are worth the latency
+        preset(
+            "Heavy Crackle",
+            &[
+                (
+                    "algorithm",
+                    detection.algorithm.preview_normalized(Algorithm::Lpc),
+                ),
+                ("window", detection.window_us.preview_normalized(300.0)),
+                (
+                    "threshold",
+                    detection
+                        .threshold
+                        .preview_normalized(util::db_to_gain(4.0)),
+                ),
+                ("lookahead", repair.lookahead.preview_normalized(true)),
+            ],
+        ),
+        // Leaves bass and kick drums alone entirely
+        preset(
+            "Protect Low End",
+            &[
+                ("bands", repair.bands.preview_normalized(2)),
+                (
+                    "crossover-low",
+                    repair.crossover_low.preview_normalized(500.0),
+                ),
+            ],
+        ),
+    ]
+}
+
+impl Plugin for RoboDepop {
+    const NAME: &'static str = "Robo Depop";
     const VENDOR: &'static str = "Robopeter";
     const URL: &'static str = "https://robopeter.com";
     const EMAIL: &'static str = "peter@robopeter.com";
 
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
+    // Every layout has an auxiliary output carrying the removed signal with the same number of
+    // channels as the main output, so it can be recorded to a separate track
     const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
         AudioIOLayout {
             main_input_channels: NonZeroU32::new(2),
             main_output_channels: NonZeroU32::new(2),
+            aux_output_ports: &[new_nonzero_u32(2)],
+            names: PortNames {
+                aux_outputs: &["Removed"],
+                ..PortNames::const_default()
+            },
             ..AudioIOLayout::const_default()
         },
         AudioIOLayout {
             main_input_channels: NonZeroU32::new(1),
             main_output_channels: NonZeroU32::new(1),
+            aux_output_ports: &[new_nonzero_u32(1)],
+            names: PortNames {
+                aux_outputs: &["Removed"],
+                ..PortNames::const_default()
+            },
+            ..AudioIOLayout::const_default()
+        },
+        // Lets the plugin sit on a mono source that feeds a stereo bus. The cleaned mono signal is
+        // copied to both outputs. The opposite direction is not offered since the wrapper only
+        // passes on as many input channels as there are outputs, so it couldn't be downmixed.
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(2),
+            aux_output_ports: &[new_nonzero_u32(2)],
+            names: PortNames {
+                layout: Some("Mono to Stereo"),
+                aux_outputs: &["Removed"],
+                ..PortNames::const_default()
+            },
             ..AudioIOLayout::const_default()
         },
     ];
 
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = AnalysisTask;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
-    fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
-        editor::create(
-            self.params.clone(),
-            self.peak_meter.clone(),
-            self.params.editor_state.clone(),
-        )
+    fn filter_state(state: &mut PluginState) {
+        state::migrate(state);
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let detection_history = self.detection_history.clone();
+        let analysis_result = self.analysis_result.clone();
+        Box::new(move |task| analysis::run_task(task, &detection_history, &analysis_result))
+    }
+
+    #[cfg(feature = "gui")]
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        let data = editor::EditorData {
+            params: self.params.clone(),
+            peak_meter: self.peak_meter.clone(),
+            input_peak_meter: self.input_peak_meter.clone(),
+            limiter_engaged: self.limiter_engaged.clone(),
+            clipped: self.clipped.clone(),
+            cpu_load: self.cpu_load.clone(),
+            analysis_result: self.analysis_result.clone(),
+            detection_history: self.detection_history.clone(),
+            click_log: self.click_log.clone(),
+            last_repair: self.last_repair.clone(),
+            waveform: self.waveform.clone(),
+            spectrogram_samples: self.spectrogram_samples.clone(),
+            spectrogram_enabled: self.spectrogram_enabled.clone(),
+            async_executor,
+        };
+
+        self.params.create_editor(data)
     }
 
     fn initialize(
         &mut self,
-        _audio_io_layout: &AudioIOLayout,
+        audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
-        // After `PEAK_METER_DECAY_MS` milliseconds of pure silence, the peak meter's value should
-        // have dropped by 12 dB
-        self.peak_meter_decay_weight = 0.25f64
-            .powf((buffer_config.sample_rate as f64 * PEAK_METER_DECAY_MS / 1000.0).recip())
-            as f32;
-        self.working_buffer = vec![0.0; buffer_config.max_buffer_size as usize + 10];
+        self.upmix_mono = audio_io_layout.main_input_channels == NonZeroU32::new(1)
+            && audio_io_layout.main_output_channels == NonZeroU32::new(2);
+        self.sample_rate = buffer_config.sample_rate;
+        // Hosts reinitialize the plugin when switching between realtime and offline processing
+        self.offline_render = buffer_config.process_mode == ProcessMode::Offline;
+        self.window_radius = algorithm::radius_for_duration(
+            self.params.detection.window_us.value(),
+            self.sample_rate,
+        );
+        self.lookahead = self.params.repair.lookahead.value();
+        context.set_latency_samples(self.latency_samples());
+        self.working_buffer =
+            vec![0.0; buffer_config.max_buffer_size as usize + 2 * LOOKAHEAD_SAMPLES];
+        self.removed_buffer = vec![0.0; BLOCK_SIZE];
+        self.detection_recorder
+            .set_sample_rate(buffer_config.sample_rate);
+        self.gain_compensation
+            .set_sample_rate(buffer_config.sample_rate);
+        self.waveform_recorder
+            .set_sample_rate(buffer_config.sample_rate);
+        self.spectrogram_samples
+            .set_sample_rate(buffer_config.sample_rate);
         true
     }
 
+    fn reset(&mut self) {
+        self.midi_control.reset();
+        self.gain_compensation.reset();
+        self.lookahead_history = [[[0.0; 2 * LOOKAHEAD_SAMPLES]; MAX_BANDS]; MAX_CHANNELS];
+        for crossover in &mut self.crossovers {
+            crossover.reset();
+        }
+        self.input_ballistics.reset();
+        self.ballistics.reset();
+        for peak_detector in self
+            .input_peak_detectors
+            .iter_mut()
+            .chain(&mut self.peak_detectors)
+        {
+            peak_detector.reset();
+        }
+    }
+
     fn process(
         &mut self,
         buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        for (_, block) in buffer.iter_blocks(128) {
+        let started = Instant::now();
+        while let Some(event) = context.next_event() {
+            if let NoteEvent::MidiCC { cc, value, .. } = event {
+                self.midi_control.handle_cc(cc, value, &self.params);
+            }
+        }
+
+        let meter_decay_ms = self.params.metering.decay_ms.value();
+        self.input_ballistics
+            .configure(self.sample_rate, meter_decay_ms);
+        self.ballistics.configure(self.sample_rate, meter_decay_ms);
+        let meter_mode = self.params.metering.mode.value();
+
+        // The host needs to know about the new latency for its delay compensation right away. The
+        // old history would contain a gap, so the delay line starts out silent again.
+        let lookahead = self.params.repair.lookahead.value();
+        if lookahead != self.lookahead {
+            self.lookahead = lookahead;
+            self.lookahead_history = [[[0.0; 2 * LOOKAHEAD_SAMPLES]; MAX_BANDS]; MAX_CHANNELS];
+            context.set_latency_samples(self.latency_samples());
+        }
+
+        let bypass = self.midi_control.bypass(&self.params);
+        let listen = self.midi_control.listen(&self.params);
+        // The window may also have been changed since the plugin was initialized
+        self.window_radius = algorithm::radius_for_duration(
+            self.params.detection.window_us.value(),
+            self.sample_rate,
+        );
+        let (algorithm, passes) = if self.offline_render && self.params.repair.offline_hq.value() {
+            self.window_radius = (self.window_radius * 2).min(algorithm::MAX_RADIUS);
+            (Algorithm::Lpc, OFFLINE_PASSES)
+        } else {
+            (self.params.detection.algorithm.value(), 1)
+        };
+        let threshold = self.midi_control.threshold(&self.params);
+        let num_bands = self.params.repair.bands.value() as usize;
+        for crossover in &mut self.crossovers {
+            crossover.set_frequencies(
+                self.params.repair.crossover_low.value(),
+                self.params.repair.crossover_high.value(),
+                self.sample_rate,
+            );
+        }
+        let limiter = self.params.output.limiter.value();
+        // Listening to the removed signal would throw off the measurement
+        let auto_gain = self.params.output.auto_gain.value() && !bypass && !listen;
+        let true_peak = self.params.metering.true_peak.value();
+        let spectrogram =
+            self.params.editor_open() && self.spectrogram_enabled.load(Ordering::Relaxed);
+        self.block_position = BlockPosition::from_transport(context.transport());
+        for (block_start, block) in buffer.iter_blocks(BLOCK_SIZE) {
+            let num_samples = block.samples();
+            self.block_offset = block_start as i64 - self.latency_samples() as i64;
+            self.detection_recorder.advance(num_samples);
+            let compensation_gain = self.gain_compensation.gain();
+            let mut measured_samples = 0;
+            let mut input_levels = BlockLevels::default();
+            let mut output_levels = BlockLevels::default();
+            let mut block_mono = [0.0; BLOCK_SIZE];
+            let mut mixed_channels = 0;
             let block_channels = block.into_iter();
 
-            for channel in block_channels {
-                self.clean_data_f(channel);
+            for (channel_idx, channel) in block_channels.enumerate() {
+                if self.upmix_mono && channel_idx > 0 {
+                    continue;
+                }
 
-                let mut amplitude: f32 = channel.iter().sum();
-                let num_samples = channel.len();
+                // Bypassed and disabled channels still need to be delayed in lookahead mode
+                let repair = !bypass && self.params.repair.channels[channel_idx].enabled.value();
+                if self.params.editor_open() {
+                    let peak = self.input_peak_detectors[channel_idx].process(channel, true_peak);
+                    input_levels.add(channel, peak);
+                }
+                if auto_gain {
+                    self.gain_compensation.measure_input(channel);
+                }
+
+                let mut removed = [0.0; BLOCK_SIZE];
+                if repair || self.lookahead {
+                    let mut bands = [[0.0; BLOCK_SIZE]; MAX_BANDS];
+                    for (i, sample) in channel.iter().enumerate() {
+                        let split = self.crossovers[channel_idx].split(num_bands, *sample);
+                        for (band, split) in bands.iter_mut().zip(split) {
+                            band[i] = split;
+                        }
+                    }
+
+                    channel.fill(0.0);
+                    for (band_idx, band) in bands[..num_bands].iter_mut().enumerate() {
+                        // The lowest band is only delayed to stay aligned with the others
+                        let passes = if repair && (num_bands == 1 || band_idx > 0) {
+                            passes
+                        } else {
+                            0
+                        };
+                        let band = &mut band[..num_samples];
+                        self.clean_data_f(
+                            channel_idx,
+                            band_idx,
+                            band,
+                            algorithm,
+                            threshold,
+                            passes,
+                        );
+
+                        for (sample, band_sample) in channel.iter_mut().zip(band.iter()) {
+                            *sample += band_sample;
+                        }
+                        let band_removed = &self.removed_buffer[..num_samples];
+                        for (removed, band_removed) in removed.iter_mut().zip(band_removed) {
+                            *removed += band_removed;
+                        }
+                    }
+
+                    if listen && repair {
+                        channel.copy_from_slice(&removed[..num_samples]);
+                    }
+                }
+                if let Some(aux_channel) = aux
+                    .outputs
+                    .first_mut()
+                    .and_then(|removed| removed.as_slice().get_mut(channel_idx))
+                {
+                    aux_channel[block_start..block_start + num_samples]
+                        .copy_from_slice(&removed[..num_samples]);
+                }
+                if auto_gain {
+                    self.gain_compensation.measure_output(channel);
+                    measured_samples += num_samples;
+                    for sample in channel.iter_mut() {
+                        *sample *= compensation_gain;
+                    }
+                }
+                if !bypass && limiter && limiter::soft_clip(channel) {
+                    self.limiter_engaged.store(true, Ordering::Relaxed);
+                }
+                if channel.iter().any(|sample| sample.abs() >= 1.0) {
+                    self.clipped.store(true, Ordering::Relaxed);
+                }
 
                 // To save resources, a plugin can (and probably should!) only perform expensive
                 // calculations that are only displayed on the GUI while the GUI is open
-                if self.params.editor_state.is_open() {
-                    amplitude = (amplitude / num_samples as f32).abs();
-                    let current_peak_meter =
-                        self.peak_meter.load(std::sync::atomic::Ordering::Relaxed);
-                    let new_peak_meter = if amplitude > current_peak_meter {
-                        amplitude
-                    } else {
-                        current_peak_meter * self.peak_meter_decay_weight
-                            + amplitude * (1.0 - self.peak_meter_decay_weight)
-                    };
-
-                    self.peak_meter
-                        .store(new_peak_meter, std::sync::atomic::Ordering::Relaxed)
+                if self.params.editor_open() {
+                    self.waveform_recorder.record(channel);
+                    let peak = self.peak_detectors[channel_idx].process(channel, true_peak);
+                    output_levels.add(channel, peak);
+                }
+                if spectrogram {
+                    for (mono, sample) in block_mono.iter_mut().zip(channel.iter()) {
+                        *mono += *sample;
+                    }
+                    mixed_channels += 1;
+                }
+            }
+
+            if auto_gain {
+                self.gain_compensation.finish_block(measured_samples);
+            }
+            if self.params.editor_open() {
+                self.waveform_recorder.advance(num_samples);
+                self.input_peak_meter.store(
+                    self.input_ballistics
+                        .process(meter_mode, &input_levels, num_samples),
+                    Ordering::Relaxed,
+                );
+                self.peak_meter.store(
+                    self.ballistics
+                        .process(meter_mode, &output_levels, num_samples),
+                    Ordering::Relaxed,
+                );
+            }
+            if spectrogram && mixed_channels > 0 {
+                let block_mono = &mut block_mono[..num_samples];
+                for sample in block_mono.iter_mut() {
+                    *sample /= mixed_channels as f32;
                 }
+                self.spectrogram_samples.push(block_mono);
+            }
+        }
+
+        if self.upmix_mono {
+            if let [mono, right] = buffer.as_slice() {
+                right.copy_from_slice(mono);
             }
+            if let Some([mono, right]) = aux.outputs.first_mut().map(Buffer::as_slice) {
+                right.copy_from_slice(mono);
+            }
+        }
+
+        // Empty buffers have no time budget to measure the load against
+        if self.params.editor_open() && buffer.samples() > 0 {
+            let budget = buffer.samples() as f32 / self.sample_rate;
+            let load = started.elapsed().as_secs_f32() / budget;
+            let weight = (-budget / CPU_LOAD_SECONDS).exp();
+            let smoothed = self.cpu_load.load(Ordering::Relaxed) * weight + load * (1.0 - weight);
+            self.cpu_load.store(smoothed, Ordering::Relaxed);
         }
 
         ProcessStatus::Normal
     }
 }
 
-impl ClapPlugin for Gain {
+impl ClapPlugin for RoboDepop {
+    // The IDs are older than the plugin's name and still say iced. Hosts use them to find the plugin
+    // in saved projects, so changing them would break every project that already uses it.
     const CLAP_ID: &'static str = "com.robopeter.robo_depop_plugin-iced";
     const CLAP_DESCRIPTION: Option<&'static str> = Some("Remove single sample pops");
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
     const CLAP_FEATURES: &'static [ClapFeature] = &[
         ClapFeature::AudioEffect,
+        ClapFeature::Restoration,
         ClapFeature::Stereo,
         ClapFeature::Mono,
-        ClapFeature::Utility,
     ];
 }
 
-impl Vst3Plugin for Gain {
+impl Vst3Plugin for RoboDepop {
+    // Kept for the same reason as `CLAP_ID`
     const VST3_CLASS_ID: [u8; 16] = *b"RoboDepopIcedAaA";
     const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
-        &[Vst3SubCategory::Fx, Vst3SubCategory::Tools];
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Restoration];
 }
 
-nih_export_clap!(Gain);
-nih_export_vst3!(Gain);
+nih_export_clap!(RoboDepop, Gain);
+nih_export_vst3!(RoboDepop, Gain);
 
-impl Gain {
-    fn clean_data_f(&mut self, data: &mut [f32]) {
-        clean_data_f_inner(data, &mut self.working_buffer);
+impl RoboDepopParams {
+    #[cfg(all(feature = "gui", not(feature = "egui")))]
+    fn create_editor(&self, data: editor::EditorData) -> Option<Box<dyn Editor>> {
+        editor::create(data, self.editor_state.clone())
     }
-}
 
-fn clean_data_f_inner(data: &mut [f32], working_buffer: &mut [f32]) {
-    working_buffer[0] = f32::MAX;
-    working_buffer[1] = f32::MIN;
+    #[cfg(feature = "egui")]
+    fn create_editor(&self, data: editor::EditorData) -> Option<Box<dyn Editor>> {
+        editor::egui_editor::create(data, self.egui_editor_state.clone())
+    }
 
-    // We do this manually here to prevent a sneeky allocation which seems to
-    // occur somewhere in the codepath of the suggested way to do this.
-    #[allow(clippy::manual_memcpy)]
-    for i in 0..data.len() {
-        working_buffer[i + 2] = data[i];
+    #[cfg(all(feature = "gui", not(feature = "egui")))]
+    fn editor_open(&self) -> bool {
+        self.editor_state.is_open()
     }
 
-    working_buffer[data.len() + 2] = f32::MAX;
-    working_buffer[data.len() + 3] = f32::MIN;
-
-    for i in 0..data.len() {
-        let a = working_buffer[i];
-        let b = working_buffer[i + 1];
-        let c = working_buffer[i + 2];
-        let d = working_buffer[i + 3];
-        let e = working_buffer[i + 4];
-        let point = c;
-        let min = (a).min(b).min(d).min(e);
-        let max = (a).max(b).max(d).max(e);
-        let distance = (max as f64 - min as f64).abs();
-        let avg = (max as f64 + min as f64) / 2.0;
-
-        data[i] =
-            if point as f64 > (avg + distance * 2.0) || (point as f64) < (avg - distance * 2.0) {
-                avg as f32
+    #[cfg(feature = "egui")]
+    fn editor_open(&self) -> bool {
+        self.egui_editor_state.is_open()
+    }
+
+    /// Without the GUI there is never anyone looking at the meters.
+    #[cfg(not(feature = "gui"))]
+    fn editor_open(&self) -> bool {
+        false
+    }
+}
+
+impl RoboDepop {
+    fn latency_samples(&self) -> u32 {
+        if self.lookahead {
+            LOOKAHEAD_SAMPLES as u32
+        } else {
+            0
+        }
+    }
+
+    /// Repair one band of a channel in place with `passes` passes, and leave what was removed in
+    /// `removed_buffer`. In lookahead mode `data` is replaced by the repaired samples from
+    /// `LOOKAHEAD_SAMPLES` samples ago, even if `passes` is zero.
+    fn clean_data_f(
+        &mut self,
+        channel_idx: usize,
+        band_idx: usize,
+        data: &mut [f32],
+        algorithm: Algorithm,
+        threshold: f32,
+        passes: usize,
+    ) {
+        let radius = self.window_radius;
+        let len = data.len();
+        // The samples being repaired always start at `LOOKAHEAD_SAMPLES`, surrounded by at least
+        // `radius` samples of context on either side
+        let working_buffer = &mut self.working_buffer[..len + 2 * LOOKAHEAD_SAMPLES];
+        let history = &mut self.lookahead_history[channel_idx][band_idx];
+        if self.lookahead {
+            working_buffer[..2 * LOOKAHEAD_SAMPLES].copy_from_slice(history);
+            working_buffer[2 * LOOKAHEAD_SAMPLES..].copy_from_slice(data);
+        } else {
+            // Without lookahead the block is padded with alternating extreme values so the samples
+            // at its edges are never considered pops
+            for i in 0..radius {
+                let padding = if i % 2 == 0 { f32::MAX } else { f32::MIN };
+                working_buffer[LOOKAHEAD_SAMPLES - radius + i] = padding;
+                working_buffer[LOOKAHEAD_SAMPLES + len + i] = padding;
+            }
+            working_buffer[LOOKAHEAD_SAMPLES..LOOKAHEAD_SAMPLES + len].copy_from_slice(data);
+        }
+
+        self.removed_buffer[..len]
+            .copy_from_slice(&working_buffer[LOOKAHEAD_SAMPLES..LOOKAHEAD_SAMPLES + len]);
+
+        for pass in 0..passes {
+            // The later passes would count the same samples again
+            let mut discarded = Histogram::default();
+            let histogram = if pass == 0 {
+                &mut self.detection_recorder.current
             } else {
-                point
+                &mut discarded
+            };
+
+            let peak_deviation = algorithm::clean(
+                algorithm,
+                &working_buffer[LOOKAHEAD_SAMPLES - radius..LOOKAHEAD_SAMPLES + len + radius],
+                data,
+                radius,
+                threshold,
+                histogram,
+            );
+            if pass == 0 {
+                self.waveform_recorder
+                    .record_deviation(peak_deviation as f32);
+            }
+            working_buffer[LOOKAHEAD_SAMPLES..LOOKAHEAD_SAMPLES + len].copy_from_slice(data);
+        }
+
+        let repaired = &working_buffer[LOOKAHEAD_SAMPLES..LOOKAHEAD_SAMPLES + len];
+        let original = &self.removed_buffer[..len];
+        if repaired != original {
+            self.waveform_recorder.mark_repair();
+
+            // Repairs are simply not logged if the editor happens to be reading the log
+            if let Some(mut click_log) = self.click_log.try_lock() {
+                for (i, (&repaired, &original)) in repaired.iter().zip(original).enumerate() {
+                    if repaired != original {
+                        click_log.push(Click {
+                            channel: channel_idx,
+                            position: self.block_position.at(self.block_offset + i as i64),
+                            original,
+                            repaired,
+                        });
+                    }
+                }
+            }
+            if let Some(mut last_repair) = self.last_repair.try_lock() {
+                last_repair.channel = channel_idx;
+                last_repair.position = self.block_position.at(self.block_offset);
+                last_repair.original[..len].copy_from_slice(original);
+                last_repair.repaired[..len].copy_from_slice(repaired);
+                last_repair.len = len;
             }
+        }
+
+        // Repaired samples stay repaired in the history, so they don't affect the next windows
+        if self.lookahead {
+            data.copy_from_slice(&working_buffer[LOOKAHEAD_SAMPLES..LOOKAHEAD_SAMPLES + len]);
+            history.copy_from_slice(&working_buffer[len..len + 2 * LOOKAHEAD_SAMPLES]);
+        }
+
+        for (removed, repaired) in self.removed_buffer.iter_mut().zip(data.iter()) {
+            *removed -= repaired;
+        }
     }
 }
 
@@ -284,39 +1124,142 @@ fn clean_data_f(data: &[f32]) -> Vec<f32> {
     clean
 }
 
+/// The settings for [`clean_data_with()`]. These are the plugin's detection parameters, for
+/// cleaning whole recordings outside of a host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CleanConfig {
+    /// How a sample's expected value is estimated, and thus what it's replaced with.
+    pub algorithm: Algorithm,
+    /// How far a sample may stray from its expected value, as a multiple of its neighbours' range,
+    /// before it is considered a pop. Lower values make the detection more sensitive.
+    pub threshold: f64,
+    /// The number of samples on either side of the sample being checked, up to [`MAX_RADIUS`].
+    pub radius: usize,
+    /// How many times the data is cleaned. Two pops next to each other widen each other's window
+    /// range enough to hide both, but once the larger one is repaired the next pass finds the other.
+    pub passes: usize,
+}
+
+impl Default for CleanConfig {
+    /// The original five sample window described in the readme.
+    fn default() -> Self {
+        Self {
+            algorithm: Algorithm::WindowAverage,
+            threshold: 2.0,
+            radius: 2,
+            passes: 1,
+        }
+    }
+}
+
 pub fn clean_data(data: &[i32]) -> Vec<i32> {
-    let mut data_copy = Vec::with_capacity(data.len() + 4);
-    data_copy.push(i32::MAX);
-    data_copy.push(i32::MIN);
-    data_copy.extend(data.iter());
-    data_copy.push(i32::MAX);
-    data_copy.push(i32::MIN);
+    clean_data_with(data, &CleanConfig::default())
+}
 
-    let clean = data_copy
-        .iter()
-        .map_windows(|[a, b, c, d, e]| {
-            let point = **c;
-            let min = *(*a).min(*b).min(*d).min(*e);
-            let max = *(*a).max(*b).max(*d).max(*e);
-            let distance = (max as i64 - min as i64).abs();
-            let avg = (max as i64 + min as i64) / 2;
-            if point as i64 > (avg + distance * 2) || (point as i64) < (avg - distance * 2) {
-                avg.try_into().unwrap_or({
-                    if avg > (i32::MAX as i64) {
-                        i32::MAX
-                    } else {
-                        i32::MIN
-                    }
-                })
-            } else {
-                point
+/// Clean `data` with the given settings. The samples within `config.radius` of either end don't
+/// have a full window around them, so they are never replaced.
+pub fn clean_data_with(data: &[i32], config: &CleanConfig) -> Vec<i32> {
+    let mut clean = clean_pass(data, config);
+    for _ in 1..config.passes {
+        clean = clean_pass(&clean, config);
+    }
+
+    clean
+}
+
+/// Clean `channels` like [`clean_data_with()`], but with the channels linked: a sample that's
+/// found to be a pop in any of them is replaced in all of them. This keeps the stereo image of a
+/// repair intact when a click hits both sides at slightly different strengths. The channels
+/// should all be the same length.
+pub fn clean_linked_with(channels: &[Vec<i32>], config: &CleanConfig) -> Vec<Vec<i32>> {
+    let mut clean = clean_linked_pass(channels, config);
+    for _ in 1..config.passes {
+        clean = clean_linked_pass(&clean, config);
+    }
+
+    clean
+}
+
+fn clean_pass(data: &[i32], config: &CleanConfig) -> Vec<i32> {
+    let radius = config.radius.clamp(1, MAX_RADIUS);
+    let mut clean = data.to_vec();
+    let mut estimator = Estimator::default();
+
+    for (i, window) in data.windows(2 * radius + 1).enumerate() {
+        let (estimate, is_pop) = estimator.check(window, config);
+        if is_pop {
+            // Casting saturates at the edges of the sample range
+            clean[i + radius] = estimate.round() as i32;
+        }
+    }
+
+    clean
+}
+
+fn clean_linked_pass(channels: &[Vec<i32>], config: &CleanConfig) -> Vec<Vec<i32>> {
+    let radius = config.radius.clamp(1, MAX_RADIUS);
+    let mut clean = channels.to_vec();
+    let mut estimator = Estimator::default();
+    let mut estimates = vec![0.0; channels.len()];
+    let len = channels.iter().map(Vec::len).min().unwrap_or(0);
+
+    for i in 0..len.saturating_sub(2 * radius) {
+        let mut is_pop = false;
+        for (channel, estimate) in channels.iter().zip(&mut estimates) {
+            let (channel_estimate, channel_is_pop) =
+                estimator.check(&channel[i..i + 2 * radius + 1], config);
+            *estimate = channel_estimate;
+            is_pop |= channel_is_pop;
+        }
+        if is_pop {
+            for (clean, estimate) in clean.iter_mut().zip(&estimates) {
+                clean[i + radius] = estimate.round() as i32;
             }
-        })
-        .collect::<Vec<i32>>();
+        }
+    }
 
     clean
 }
 
+/// Judges the center samples of windows, reusing its buffers between them.
+#[derive(Default)]
+struct Estimator {
+    left: [f32; MAX_RADIUS],
+    right: [f32; MAX_RADIUS],
+}
+
+impl Estimator {
+    /// Estimate the center sample of `window`, and whether it strays far enough from that to be a
+    /// pop.
+    fn check(&mut self, window: &[i32], config: &CleanConfig) -> (f64, bool) {
+        let radius = window.len() / 2;
+        let point = window[radius];
+        let neighbours = window[..radius].iter().chain(&window[radius + 1..]);
+        let min = *neighbours.clone().min().unwrap();
+        let max = *neighbours.max().unwrap();
+        let distance = (max as i64 - min as i64) as f64;
+        let estimate = match config.algorithm {
+            // Kept in integers, so this gives exactly the same results as the original algorithm
+            Algorithm::WindowAverage => ((max as i64 + min as i64) / 2) as f64,
+            // The other estimates work on floating point samples, which hold up to 24 bits exactly
+            algorithm => {
+                for (target, &sample) in self.left.iter_mut().zip(&window[..radius]) {
+                    *target = sample as f32;
+                }
+                for (target, &sample) in self.right.iter_mut().zip(&window[radius + 1..]) {
+                    *target = sample as f32;
+                }
+                algorithm.estimate(&self.left[..radius], &self.right[..radius])
+            }
+        };
+
+        (
+            estimate,
+            (point as f64 - estimate).abs() > distance * config.threshold,
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
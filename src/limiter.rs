@@ -0,0 +1,42 @@
+/// The level in linear gain (-1 dBFS) above which the soft clipper starts bending the signal.
+const KNEE: f32 = 0.891_250_9;
+
+/// Softly clip `data` so that no sample ever exceeds 0 dBFS. Samples below the knee are left
+/// untouched, and everything above it is smoothly compressed into the remaining headroom. Returns
+/// whether any sample was affected.
+pub fn soft_clip(data: &mut [f32]) -> bool {
+    let mut engaged = false;
+    for sample in data.iter_mut() {
+        let magnitude = sample.abs();
+        if magnitude > KNEE {
+            let headroom = 1.0 - KNEE;
+            let clipped = KNEE + headroom * ((magnitude - KNEE) / headroom).tanh();
+            *sample = clipped.copysign(*sample);
+            engaged = true;
+        }
+    }
+
+    engaged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_exceeds_full_scale() {
+        let mut data = [0.5, -0.95, 1.0, -4.0, 1000.0, f32::MAX];
+        assert!(soft_clip(&mut data));
+
+        assert_eq!(data[0], 0.5);
+        assert!(data[1] < -KNEE && data[1] > -0.95);
+        assert!(data.iter().all(|sample| sample.abs() <= 1.0));
+    }
+
+    #[test]
+    fn quiet_signals_are_untouched() {
+        let mut data = [0.0, 0.1, -0.8];
+        assert!(!soft_clip(&mut data));
+        assert_eq!(data, [0.0, 0.1, -0.8]);
+    }
+}
@@ -0,0 +1,262 @@
+use nih_plug::prelude::Enum;
+use std::f32::consts::{FRAC_PI_2, SQRT_2};
+use std::fmt;
+
+/// The integration time of the RMS and VU meters. A VU meter takes this long to reach 99% of a
+/// steady tone's level.
+const INTEGRATION_SECONDS: f32 = 0.3;
+
+/// How the meters respond to the signal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum MeterMode {
+    /// Jumps to every peak right away, then falls back at the meter decay rate.
+    #[default]
+    Peak,
+    /// The signal's power averaged over the integration time.
+    #[name = "RMS"]
+    Rms,
+    /// The rectified signal averaged like an analog VU meter's needle, calibrated so a sine wave
+    /// reads the same as on the RMS meter.
+    #[name = "VU"]
+    Vu,
+}
+
+impl fmt::Display for MeterMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(Self::variants()[self.to_index()])
+    }
+}
+
+/// The levels of a block across all channels, from which the meters are updated.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BlockLevels {
+    peak: f32,
+    sum_squares: f32,
+    sum_abs: f32,
+    num_samples: usize,
+}
+
+impl BlockLevels {
+    /// Add a channel of the block. `peak` is that channel's peak from a [`PeakDetector`].
+    pub fn add(&mut self, samples: &[f32], peak: f32) {
+        self.peak = self.peak.max(peak);
+        for sample in samples {
+            self.sum_squares += sample * sample;
+            self.sum_abs += sample.abs();
+        }
+        self.num_samples += samples.len();
+    }
+}
+
+/// The attack and release behavior of a meter. All modes are kept up to date at the same time so
+/// switching between them doesn't start from scratch.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Ballistics {
+    peak: f32,
+    mean_square: f32,
+    mean_abs: f32,
+    /// The per-sample weights of the old value when the peak meter decays, and when averaging.
+    decay_weight: f32,
+    integration_weight: f32,
+}
+
+impl Ballistics {
+    /// After `decay_ms` milliseconds of pure silence, the peak meter's value should have dropped
+    /// by 12 dB.
+    pub fn configure(&mut self, sample_rate: f32, decay_ms: f32) {
+        self.decay_weight =
+            0.25f64.powf((sample_rate as f64 * decay_ms as f64 / 1000.0).recip()) as f32;
+        self.integration_weight = 0.01f32.powf((sample_rate * INTEGRATION_SECONDS).recip());
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = 0.0;
+        self.mean_square = 0.0;
+        self.mean_abs = 0.0;
+    }
+
+    /// Update the meter with a block that's `num_samples` samples long, and return the level to
+    /// display as voltage gain.
+    pub fn process(&mut self, mode: MeterMode, levels: &BlockLevels, num_samples: usize) -> f32 {
+        // The weights are for a single sample, so they're applied once for every sample in the
+        // block
+        let decay_weight = self.decay_weight.powi(num_samples as i32);
+        self.peak = if levels.peak > self.peak {
+            levels.peak
+        } else {
+            self.peak * decay_weight + levels.peak * (1.0 - decay_weight)
+        };
+
+        if levels.num_samples > 0 {
+            let integration_weight = self.integration_weight.powi(num_samples as i32);
+            let mean_square = levels.sum_squares / levels.num_samples as f32;
+            let mean_abs = levels.sum_abs / levels.num_samples as f32;
+            self.mean_square =
+                self.mean_square * integration_weight + mean_square * (1.0 - integration_weight);
+            self.mean_abs =
+                self.mean_abs * integration_weight + mean_abs * (1.0 - integration_weight);
+        }
+
+        match mode {
+            MeterMode::Peak => self.peak,
+            MeterMode::Rms => self.mean_square.sqrt(),
+            // A sine's average rectified value is 2/pi times its peak, and its RMS is 1/sqrt(2)
+            // times its peak
+            MeterMode::Vu => self.mean_abs * FRAC_PI_2 / SQRT_2,
+        }
+    }
+}
+
+/// The number of taps per phase of the oversampling filter.
+const TAPS: usize = 12;
+
+/// The polyphase interpolation filter from ITU-R BS.1770-4, Annex 2, which upsamples by a factor
+/// of four. Each row produces one of the four interpolated samples.
+const FILTER: [[f32; TAPS]; 4] = [
+    [
+        0.001_708_984_4,
+        0.010_986_328,
+        -0.019_653_32,
+        0.033_203_125,
+        -0.059_448_242,
+        0.137_329_1,
+        0.972_167_97,
+        -0.102_294_92,
+        0.047_607_42,
+        -0.026_611_328,
+        0.014_892_578,
+        -0.008_300_781,
+    ],
+    [
+        -0.029_174_805,
+        0.029_296_875,
+        -0.051_757_812,
+        0.089_111_33,
+        -0.166_503_9,
+        0.465_087_9,
+        0.779_785_16,
+        -0.200_317_38,
+        0.101_562_5,
+        -0.058_227_54,
+        0.033_081_055,
+        -0.018_920_898,
+    ],
+    [
+        -0.018_920_898,
+        0.033_081_055,
+        -0.058_227_54,
+        0.101_562_5,
+        -0.200_317_38,
+        0.779_785_16,
+        0.465_087_9,
+        -0.166_503_9,
+        0.089_111_33,
+        -0.051_757_812,
+        0.029_296_875,
+        -0.029_174_805,
+    ],
+    [
+        -0.008_300_781,
+        0.014_892_578,
+        -0.026_611_328,
+        0.047_607_42,
+        -0.102_294_92,
+        0.972_167_97,
+        0.137_329_1,
+        -0.059_448_242,
+        0.033_203_125,
+        -0.019_653_32,
+        0.010_986_328,
+        0.001_708_984_4,
+    ],
+];
+
+/// Measures the peak level of a single channel. Peaks between samples are found by oversampling
+/// the signal by a factor of four, as those still end up clipping after the conversion to analog.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PeakDetector {
+    /// The most recent input samples, with the newest sample at `TAPS - 1`.
+    history: [f32; TAPS],
+}
+
+impl PeakDetector {
+    pub fn reset(&mut self) {
+        self.history = [0.0; TAPS];
+    }
+
+    /// The highest absolute value in `samples`, including the peaks between samples if
+    /// `true_peak` is set.
+    pub fn process(&mut self, samples: &[f32], true_peak: bool) -> f32 {
+        if !true_peak {
+            // The history would be stale when switching true peak metering back on, but that only
+            // affects the first few samples
+            return samples
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        }
+
+        let mut peak = 0.0f32;
+        for &sample in samples {
+            self.history.copy_within(1.., 0);
+            self.history[TAPS - 1] = sample;
+
+            for phase in &FILTER {
+                let interpolated: f32 = phase
+                    .iter()
+                    .zip(self.history.iter().rev())
+                    .map(|(coefficient, sample)| coefficient * sample)
+                    .sum();
+                peak = peak.max(interpolated.abs());
+            }
+        }
+
+        peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn ballistics_settle_on_a_sine() {
+        let sample_rate = 48_000.0;
+        let samples: Vec<f32> = (0..128)
+            .map(|i| (2.0 * PI * 375.0 * i as f32 / sample_rate).sin())
+            .collect();
+        let mut levels = BlockLevels::default();
+        levels.add(&samples, 1.0);
+
+        for (mode, expected) in [
+            (MeterMode::Peak, 1.0),
+            (MeterMode::Rms, 0.5f32.sqrt()),
+            (MeterMode::Vu, 0.5f32.sqrt()),
+        ] {
+            let mut ballistics = Ballistics::default();
+            ballistics.configure(sample_rate, 150.0);
+
+            // One second is plenty to settle
+            let mut level = 0.0;
+            for _ in 0..375 {
+                level = ballistics.process(mode, &levels, samples.len());
+            }
+            assert!((level - expected).abs() < 1e-3, "{mode:?}: {level}");
+        }
+    }
+
+    #[test]
+    fn finds_peaks_between_samples() {
+        // A quarter of the sample rate with the samples falling halfway between the peaks, so
+        // every sample is at -3 dB while the waveform itself reaches full scale
+        let samples: Vec<f32> = (0..256)
+            .map(|i| (PI / 2.0 * i as f32 + PI / 4.0).sin())
+            .collect();
+
+        let mut detector = PeakDetector::default();
+        let sample_peak = detector.process(&samples, false);
+        let true_peak = detector.process(&samples, true);
+        assert!((sample_peak - 0.5f32.sqrt()).abs() < 1e-4);
+        assert!((true_peak - 1.0).abs() < 0.05, "{true_peak}");
+    }
+}
@@ -0,0 +1,95 @@
+use nih_plug::prelude::*;
+
+/// State toggled from MIDI CCs. Parameters can't be set from the audio thread, so the CCs instead
+/// flip or override the parameter values for as long as the plugin is active.
+#[derive(Debug, Default)]
+pub struct MidiControl {
+    /// Inverts the bypass parameter when set.
+    bypass_toggled: bool,
+    /// Inverts the listen parameter when set.
+    listen_toggled: bool,
+    /// Whether the toggle CCs were last seen in their pressed (>= 64) position, so that a
+    /// momentary button only flips the state once per press.
+    bypass_pressed: bool,
+    listen_pressed: bool,
+    /// A threshold set through MIDI, along with the parameter value it overrides. The override is
+    /// dropped as soon as the parameter itself is changed.
+    threshold_override: Option<(f32, f32)>,
+}
+
+impl MidiControl {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Handle an incoming CC, `value` is normalized to `[0, 1]`.
+    pub fn handle_cc(&mut self, cc: u8, value: f32, params: &crate::RoboDepopParams) {
+        let pressed = value >= 0.5;
+        let cc = cc as i32;
+
+        if cc == params.midi.bypass_cc.value() {
+            if pressed && !self.bypass_pressed {
+                self.bypass_toggled = !self.bypass_toggled;
+            }
+            self.bypass_pressed = pressed;
+        }
+        if cc == params.midi.listen_cc.value() {
+            if pressed && !self.listen_pressed {
+                self.listen_toggled = !self.listen_toggled;
+            }
+            self.listen_pressed = pressed;
+        }
+        if cc == params.midi.threshold_cc.value() {
+            self.threshold_override = Some((
+                params.detection.threshold.preview_plain(value),
+                params.detection.threshold.value(),
+            ));
+        }
+    }
+
+    pub fn bypass(&self, params: &crate::RoboDepopParams) -> bool {
+        params.output.bypass.value() ^ self.bypass_toggled
+    }
+
+    pub fn listen(&self, params: &crate::RoboDepopParams) -> bool {
+        params.output.listen.value() ^ self.listen_toggled
+    }
+
+    pub fn threshold(&mut self, params: &crate::RoboDepopParams) -> f32 {
+        let threshold = params.detection.threshold.value();
+        match self.threshold_override {
+            Some((value, overridden)) if overridden == threshold => value,
+            _ => {
+                self.threshold_override = None;
+                threshold
+            }
+        }
+    }
+}
+
+/// The CC numbers that toggle bypass and listen and that control the threshold.
+#[derive(Params)]
+pub struct MidiParams {
+    #[id = "bypass-cc"]
+    pub bypass_cc: IntParam,
+    #[id = "listen-cc"]
+    pub listen_cc: IntParam,
+    #[id = "threshold-cc"]
+    pub threshold_cc: IntParam,
+}
+
+impl Default for MidiParams {
+    fn default() -> Self {
+        Self {
+            // General purpose controllers 1-3 on most hardware
+            bypass_cc: cc_param("Bypass CC", 80),
+            listen_cc: cc_param("Listen CC", 81),
+            threshold_cc: cc_param("Threshold CC", 82),
+        }
+    }
+}
+
+/// The parameter for the CC number mapped to one of the MIDI controllable functions.
+fn cc_param(name: &'static str, default: i32) -> IntParam {
+    IntParam::new(name, default, IntRange::Linear { min: 0, max: 127 }).non_automatable()
+}
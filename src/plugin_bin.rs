@@ -1,6 +1,8 @@
 use nih_plug::prelude::*;
-use robo_depop_plugin::Gain;
+use robo_depop_plugin::RoboDepop;
 
+/// Runs the de-popper directly against the sound card, see `--help` for the available audio backends
+/// and devices.
 pub fn main() {
-    nih_export_standalone::<Gain>();
+    nih_export_standalone::<RoboDepop>();
 }
@@ -0,0 +1,229 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::compare::Snapshot;
+
+/// The extension of the user preset files.
+const EXTENSION: &str = "json";
+/// The start of the text produced by [`to_text()`], which also versions the format.
+const TEXT_PREFIX: &str = "robo-depop:1";
+
+/// A named set of parameter values. Like the A/B compare snapshots these are stored as normalized
+/// values by parameter ID, and the monitoring parameters are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub snapshot: Snapshot,
+    /// Factory presets are built into the plugin and can't be changed.
+    pub factory: bool,
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.factory {
+            write!(f, "{} (factory)", self.name)
+        } else {
+            f.write_str(&self.name)
+        }
+    }
+}
+
+/// The user presets, stored as one JSON file per preset in a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetStore {
+    directory: PathBuf,
+}
+
+impl Default for PresetStore {
+    /// The presets directory in the user's home directory, or in the temporary directory if that
+    /// can't be found.
+    fn default() -> Self {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+
+        Self::new(home.join(".robo-depop").join("presets"))
+    }
+}
+
+impl PresetStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// All user presets sorted by name. A missing directory simply means there are no presets
+    /// yet, and files that can't be read are skipped.
+    pub fn load_all(&self) -> io::Result<Vec<Preset>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut presets = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if let Ok(snapshot) = read_snapshot(&path) {
+                presets.push(Preset {
+                    name: name.to_owned(),
+                    snapshot,
+                    factory: false,
+                });
+            }
+        }
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(presets)
+    }
+
+    /// Save `snapshot` as a user preset, overwriting any existing preset with the same name.
+    pub fn save(&self, name: &str, snapshot: &Snapshot) -> io::Result<()> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.directory)?;
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, snapshot)?;
+        writer.flush()
+    }
+
+    /// Rename a user preset. This refuses to overwrite another preset.
+    pub fn rename(&self, name: &str, new_name: &str) -> io::Result<()> {
+        let new_path = self.path(new_name)?;
+        if new_path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("there already is a preset called \"{new_name}\""),
+            ));
+        }
+
+        fs::rename(self.path(name)?, new_path)
+    }
+
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        fs::remove_file(self.path(name)?)
+    }
+
+    /// The file a preset is stored in. The name becomes the file name, so anything that could
+    /// escape the presets directory or trip up a file system is rejected.
+    fn path(&self, name: &str) -> io::Result<PathBuf> {
+        let valid = !name.trim().is_empty()
+            && name.trim() == name
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '(' | ')'));
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "preset names can only contain letters, numbers, spaces, dashes, underscores, \
+                 and parentheses",
+            ));
+        }
+
+        Ok(self.directory.join(format!("{name}.{EXTENSION}")))
+    }
+}
+
+/// Turn a snapshot into a single line of text, for sharing settings through the clipboard, e.g. in
+/// bug reports. This looks like `robo-depop:1;algorithm=0.5;threshold=0.2523`.
+pub fn to_text(snapshot: &Snapshot) -> String {
+    let mut text = String::from(TEXT_PREFIX);
+    for (id, value) in snapshot {
+        text.push_str(&format!(";{id}={value}"));
+    }
+
+    text
+}
+
+/// The inverse of [`to_text()`]. Surrounding whitespace is ignored, since pasting text often adds
+/// some.
+pub fn from_text(text: &str) -> io::Result<Snapshot> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "these aren't Robo Depop settings",
+        )
+    };
+
+    let mut fields = text.trim().split(';');
+    if fields.next() != Some(TEXT_PREFIX) {
+        return Err(invalid());
+    }
+    fields
+        .map(|field| {
+            let (id, value) = field.split_once('=').ok_or_else(invalid)?;
+            let value: f32 = value.parse().map_err(|_| invalid())?;
+            if !(0.0..=1.0).contains(&value) {
+                return Err(invalid());
+            }
+
+            Ok((id.to_owned(), value))
+        })
+        .collect()
+}
+
+fn read_snapshot(path: &Path) -> io::Result<Snapshot> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_rename_delete() {
+        let directory =
+            std::env::temp_dir().join(format!("robo-depop-presets-{}", std::process::id()));
+        let store = PresetStore::new(&directory);
+        let snapshot = Snapshot::from([(String::from("threshold"), 0.25)]);
+
+        assert_eq!(store.load_all().unwrap(), Vec::new());
+        store.save("Crackle", &snapshot).unwrap();
+        store.save("Another", &Snapshot::new()).unwrap();
+        assert!(store.save("../escape", &snapshot).is_err());
+
+        let presets = store.load_all().unwrap();
+        assert_eq!(presets.len(), 2);
+        assert_eq!(presets[1].name, "Crackle");
+        assert_eq!(presets[1].snapshot, snapshot);
+
+        assert!(store.rename("Crackle", "Another").is_err());
+        store.rename("Crackle", "Light Crackle").unwrap();
+        store.delete("Another").unwrap();
+        let names: Vec<_> = store
+            .load_all()
+            .unwrap()
+            .into_iter()
+            .map(|preset| preset.name)
+            .collect();
+        assert_eq!(names, ["Light Crackle"]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let snapshot = Snapshot::from([
+            (String::from("threshold"), 0.25),
+            (String::from("algorithm"), 1.0 / 3.0),
+        ]);
+
+        let text = to_text(&snapshot);
+        assert!(text.starts_with("robo-depop:1;algorithm="));
+        assert_eq!(from_text(&format!(" {text}\n")).unwrap(), snapshot);
+        assert!(from_text("threshold=0.25").is_err());
+        assert!(from_text("robo-depop:1;threshold=1.5").is_err());
+    }
+}
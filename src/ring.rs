@@ -0,0 +1,111 @@
+use atomic_float::AtomicF32;
+use std::sync::atomic::{self, AtomicU32, AtomicU64, Ordering};
+
+/// A fixed size ring of samples for streaming audio from the audio thread to the editor. Writing
+/// is wait-free and never allocates, and reading never blocks the writer: samples the reader
+/// didn't pick up in time are overwritten, and the reader notices and skips them.
+///
+/// There must only ever be a single writer, which is the audio thread. Readers only keep track of
+/// their own position, so the editor can read from any thread.
+#[derive(Debug)]
+pub struct SampleRing {
+    /// The samples' bits, as there are no atomic floats in the standard library.
+    samples: Box<[AtomicU32]>,
+    /// The total number of samples the writer has started writing. Slots up to this many samples
+    /// back may be in the middle of being overwritten.
+    claimed: AtomicU64,
+    /// The total number of samples that have been completely written.
+    written: AtomicU64,
+    sample_rate: AtomicF32,
+}
+
+impl SampleRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            claimed: AtomicU64::new(0),
+            written: AtomicU64::new(0),
+            sample_rate: AtomicF32::new(44_100.0),
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Append `samples` to the ring. This must only be called from a single thread.
+    pub fn push(&self, samples: &[f32]) {
+        let start = self.written.load(Ordering::Relaxed);
+        let end = start + samples.len() as u64;
+
+        // Readers check this after reading to find out which of the samples they read may have
+        // been overwritten in the meantime
+        self.claimed.store(end, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        for (position, &sample) in (start..end).zip(samples) {
+            self.slot(position)
+                .store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.written.store(end, Ordering::Release);
+    }
+
+    /// Append everything written after `position` to `output`, and return the position to
+    /// continue reading from next time. Samples that have already been overwritten are skipped.
+    pub fn read_since(&self, position: u64, output: &mut Vec<f32>) -> u64 {
+        let capacity = self.samples.len() as u64;
+        let end = self.written.load(Ordering::Acquire);
+        let start = position.max(end.saturating_sub(capacity)).min(end);
+
+        let output_start = output.len();
+        output.extend(
+            (start..end)
+                .map(|position| f32::from_bits(self.slot(position).load(Ordering::Relaxed))),
+        );
+
+        // Anything the writer claimed while this was reading can't be trusted anymore
+        atomic::fence(Ordering::Acquire);
+        let first_intact = self
+            .claimed
+            .load(Ordering::Relaxed)
+            .saturating_sub(capacity);
+        let overwritten = first_intact.saturating_sub(start).min(end - start) as usize;
+        output.drain(output_start..output_start + overwritten);
+
+        end
+    }
+
+    fn slot(&self, position: u64) -> &AtomicU32 {
+        &self.samples[(position % self.samples.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_skip_overwritten_samples() {
+        let ring = SampleRing::new(8);
+        let mut output = Vec::new();
+
+        ring.push(&[1.0, 2.0, 3.0]);
+        let position = ring.read_since(0, &mut output);
+        assert_eq!(output, [1.0, 2.0, 3.0]);
+
+        // The reader fell behind, so only the last eight samples are left
+        let samples: Vec<f32> = (4..14).map(|i| i as f32).collect();
+        ring.push(&samples);
+        output.clear();
+        let position = ring.read_since(position, &mut output);
+        assert_eq!(output, &samples[2..]);
+        assert_eq!(position, 13);
+
+        output.clear();
+        assert_eq!(ring.read_since(position, &mut output), 13);
+        assert!(output.is_empty());
+    }
+}
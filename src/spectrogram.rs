@@ -0,0 +1,177 @@
+use realfft::num_complex::Complex32;
+use realfft::{RealFftPlanner, RealToComplex};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::ring::SampleRing;
+use crate::waveform::{NUM_COLUMNS, WAVEFORM_SECONDS};
+
+/// The number of samples in the ring the plugin streams its mono output through. This only needs
+/// to cover the time between two editor frames, with plenty of room to spare.
+pub const RING_SIZE: usize = 1 << 15;
+/// The length of each FFT. This is kept short since clicks are short, and a short window keeps
+/// them from being smeared out over time.
+const FFT_SIZE: usize = 512;
+/// The number of logarithmically spaced frequency bands shown in the spectrogram.
+pub const NUM_BANDS: usize = 40;
+/// The lowest frequency shown in the spectrogram.
+const MIN_FREQUENCY: f32 = 40.0;
+/// The level shown as silence, everything in between is scaled linearly in decibels.
+pub const MIN_DB: f32 = -90.0;
+
+/// Turns the samples from a [`SampleRing`] into spectrogram columns. This runs on the editor's
+/// thread, the audio thread only ever copies samples into the ring.
+pub struct SpectrogramAnalyzer {
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    fft_input: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    /// The first and one past the last FFT bin of each band, for the current sample rate.
+    band_bins: [(usize, usize); NUM_BANDS],
+    sample_rate: f32,
+
+    /// The position in the ring the next read continues from.
+    position: u64,
+    /// Samples read from the ring that haven't been fully analyzed yet.
+    pending: Vec<f32>,
+    /// The band levels in decibels for every column, from oldest to newest.
+    columns: VecDeque<[f32; NUM_BANDS]>,
+}
+
+impl Default for SpectrogramAnalyzer {
+    fn default() -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            fft_input: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            fft,
+            window,
+            band_bins: [(0, 0); NUM_BANDS],
+            sample_rate: 0.0,
+
+            position: 0,
+            pending: Vec::new(),
+            columns: VecDeque::with_capacity(NUM_COLUMNS),
+        }
+    }
+}
+
+impl SpectrogramAnalyzer {
+    /// Analyze everything that was added to `ring` since the last update. The columns line up
+    /// with the waveform view's columns.
+    pub fn update(&mut self, ring: &SampleRing) {
+        if ring.sample_rate() != self.sample_rate {
+            self.set_sample_rate(ring.sample_rate());
+        }
+        self.position = ring.read_since(self.position, &mut self.pending);
+
+        // A column usually spans more than a single FFT. Every part of it is analyzed so short
+        // clicks don't fall through the cracks, with the FFTs overlapping by half.
+        let hop = ((self.sample_rate * WAVEFORM_SECONDS / NUM_COLUMNS as f32) as usize).max(1);
+        let frames = hop.div_ceil(FFT_SIZE / 2);
+        let mut start = 0;
+        while self.pending.len() - start >= (frames - 1) * FFT_SIZE / 2 + FFT_SIZE {
+            let mut column = [MIN_DB; NUM_BANDS];
+            for frame in 0..frames {
+                let frame = self.analyze(start + frame * FFT_SIZE / 2);
+                for (level, frame_level) in column.iter_mut().zip(frame) {
+                    *level = level.max(frame_level);
+                }
+            }
+
+            if self.columns.len() == NUM_COLUMNS {
+                self.columns.pop_front();
+            }
+            self.columns.push_back(column);
+            start += hop;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// The band levels in decibels from the lowest to the highest band, for every column from
+    /// oldest to newest.
+    pub fn columns(&self) -> impl Iterator<Item = &[f32; NUM_BANDS]> {
+        self.columns.iter()
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.columns.clear();
+
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        let num_bins = FFT_SIZE / 2 + 1;
+        let ratio = (sample_rate / 2.0 / MIN_FREQUENCY).powf(1.0 / NUM_BANDS as f32);
+        for (band, bins) in self.band_bins.iter_mut().enumerate() {
+            let low = MIN_FREQUENCY * ratio.powi(band as i32) / bin_width;
+            let high = MIN_FREQUENCY * ratio.powi(band as i32 + 1) / bin_width;
+            // The lower bands are narrower than a single bin
+            let first = (low.round() as usize).min(num_bins - 1);
+            let last = (high.round() as usize).clamp(first + 1, num_bins);
+            *bins = (first, last);
+        }
+    }
+
+    fn analyze(&mut self, start: usize) -> [f32; NUM_BANDS] {
+        for ((input, sample), window) in self
+            .fft_input
+            .iter_mut()
+            .zip(&self.pending[start..start + FFT_SIZE])
+            .zip(&self.window)
+        {
+            *input = sample * window;
+        }
+        self.fft
+            .process(&mut self.fft_input, &mut self.spectrum)
+            .expect("The buffers have the right sizes");
+
+        // The Hann window halves the amplitude, and the energy is split between the positive and
+        // negative frequencies
+        let scale = 4.0 / FFT_SIZE as f32;
+        self.band_bins.map(|(first, last)| {
+            let magnitude = self.spectrum[first..last]
+                .iter()
+                .fold(0.0f32, |peak, bin| peak.max(bin.norm()));
+
+            (20.0 * (magnitude * scale).log10()).max(MIN_DB)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tones_end_up_in_the_right_band() {
+        let sample_rate = 48_000.0;
+        let ring = SampleRing::new(RING_SIZE);
+        ring.set_sample_rate(sample_rate);
+        let sine: Vec<f32> = (0..48_000 * 4)
+            .map(|i| (2.0 * PI * 1_000.0 * i as f32 / sample_rate).sin())
+            .collect();
+
+        // The ring is smaller than this, so it needs to be read while it's being written to
+        let mut analyzer = SpectrogramAnalyzer::default();
+        for chunk in sine.chunks(4_800) {
+            ring.push(chunk);
+            analyzer.update(&ring);
+        }
+        let column = analyzer.columns().last().unwrap();
+        let (loudest, level) = column
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+
+        let (first, last) = analyzer.band_bins[loudest];
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        assert!(first as f32 * bin_width <= 1_000.0 && last as f32 * bin_width >= 1_000.0);
+        assert!(level.abs() < 2.0, "{level}");
+        assert_eq!(analyzer.columns().count(), NUM_COLUMNS);
+    }
+}
@@ -0,0 +1,106 @@
+use nih_plug::prelude::*;
+
+/// The version of the parameter layout. Bump this and add a step to [`MIGRATIONS`] whenever
+/// parameters are renamed, removed, or change their meaning, so existing projects keep sounding
+/// the same.
+pub const STATE_VERSION: u32 = 2;
+/// The persistent field the version is stored in.
+pub const STATE_VERSION_KEY: &str = "state-version";
+
+/// `MIGRATIONS[n]` upgrades a state from version `n + 1` to version `n + 2`.
+const MIGRATIONS: &[fn(&mut PluginState)] = &[remove_gain];
+
+/// Upgrade `state` in place to the current [`STATE_VERSION`]. Sessions saved before the version
+/// was recorded use the first layout, and so do states claiming version 0, which was never written.
+pub fn migrate(state: &mut PluginState) {
+    let version = state
+        .fields
+        .get(STATE_VERSION_KEY)
+        .and_then(|version| version.parse::<u32>().ok())
+        .unwrap_or(1)
+        .max(1);
+    if version > STATE_VERSION {
+        nih_log!(
+            "Loading a state from a newer version ({version} > {STATE_VERSION}), some settings may \
+             not be restored"
+        );
+        return;
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(state);
+    }
+    state
+        .fields
+        .insert(String::from(STATE_VERSION_KEY), STATE_VERSION.to_string());
+}
+
+/// Version 2 moved the gain knob out into the separate Robo Gain plugin. There's nothing left here
+/// to carry it over to, so sessions that had it turned away from 0 dB get a note in the log.
+fn remove_gain(state: &mut PluginState) {
+    if let Some(ParamValue::F32(gain)) = state.params.remove("gain") {
+        if gain != 1.0 {
+            nih_log!(
+                "This session had a gain of {:.2} dB, which is now part of the Robo Gain plugin",
+                util::gain_to_db(gain)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn unversioned_state_is_stamped() {
+        let mut state = PluginState {
+            version: String::from("0.1.0"),
+            params: BTreeMap::from([(String::from("threshold"), ParamValue::F32(0.5))]),
+            fields: BTreeMap::new(),
+        };
+        migrate(&mut state);
+
+        assert_eq!(
+            state.fields.get(STATE_VERSION_KEY),
+            Some(&STATE_VERSION.to_string())
+        );
+        assert_eq!(state.params.len(), 1);
+    }
+
+    #[test]
+    fn the_gain_is_removed_from_first_version_states() {
+        let mut state = PluginState {
+            version: String::from("0.1.0"),
+            params: BTreeMap::from([
+                (String::from("gain"), ParamValue::F32(0.5)),
+                (String::from("threshold"), ParamValue::F32(2.0)),
+            ]),
+            fields: BTreeMap::from([(String::from(STATE_VERSION_KEY), String::from("1"))]),
+        };
+        migrate(&mut state);
+
+        assert!(!state.params.contains_key("gain"));
+        assert!(state.params.contains_key("threshold"));
+        assert_eq!(
+            state.fields.get(STATE_VERSION_KEY),
+            Some(&String::from("2"))
+        );
+    }
+
+    #[test]
+    fn version_zero_is_treated_as_the_first_version() {
+        let mut state = PluginState {
+            version: String::from("0.1.0"),
+            params: BTreeMap::new(),
+            fields: BTreeMap::from([(String::from(STATE_VERSION_KEY), String::from("0"))]),
+        };
+        migrate(&mut state);
+
+        assert_eq!(
+            state.fields.get(STATE_VERSION_KEY),
+            Some(&STATE_VERSION.to_string())
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The number of columns in the waveform view, one per pixel.
+pub const NUM_COLUMNS: usize = 180;
+/// How much audio the waveform view spans.
+pub const WAVEFORM_SECONDS: f32 = 3.0;
+
+/// The peaks of the output over a short stretch of time, along with whether anything was repaired
+/// in that stretch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Column {
+    pub min: f32,
+    pub max: f32,
+    pub repaired: bool,
+    /// The largest deviation the detection saw in this stretch, as a ratio to compare against the
+    /// threshold.
+    pub deviation: f32,
+}
+
+impl Default for Column {
+    fn default() -> Self {
+        Self {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            repaired: false,
+            deviation: 0.0,
+        }
+    }
+}
+
+impl Column {
+    /// Whether any samples were added to this column.
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+}
+
+/// The most recent waveform columns, shared between the audio thread and the editor.
+#[derive(Debug)]
+pub struct WaveformHistory {
+    columns: Vec<Column>,
+    /// The index in `columns` the next column will be written to.
+    next: usize,
+}
+
+impl Default for WaveformHistory {
+    fn default() -> Self {
+        Self {
+            columns: vec![Column::default(); NUM_COLUMNS],
+            next: 0,
+        }
+    }
+}
+
+impl WaveformHistory {
+    fn push(&mut self, column: Column) {
+        self.columns[self.next] = column;
+        self.next = (self.next + 1) % NUM_COLUMNS;
+    }
+
+    /// The columns from oldest to newest. Columns that haven't been written yet are empty.
+    pub fn columns(&self) -> impl Iterator<Item = &Column> {
+        self.columns[self.next..]
+            .iter()
+            .chain(&self.columns[..self.next])
+    }
+}
+
+/// The audio thread's side of the [`WaveformHistory`]. This works the same way as the
+/// [`DetectionRecorder`][crate::analysis::DetectionRecorder].
+#[derive(Debug, Default)]
+pub struct WaveformRecorder {
+    current: Column,
+    samples_in_column: usize,
+    column_length: usize,
+    history: Arc<Mutex<WaveformHistory>>,
+}
+
+impl WaveformRecorder {
+    pub fn new(history: Arc<Mutex<WaveformHistory>>) -> Self {
+        Self {
+            history,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.column_length =
+            ((sample_rate * WAVEFORM_SECONDS / NUM_COLUMNS as f32) as usize).max(1);
+    }
+
+    /// Add a channel's samples to the current column.
+    pub fn record(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.current.min = self.current.min.min(sample);
+            self.current.max = self.current.max.max(sample);
+        }
+    }
+
+    /// Record a block's largest deviation from [`algorithm::clean()`][crate::algorithm::clean()].
+    pub fn record_deviation(&mut self, deviation: f32) {
+        self.current.deviation = self.current.deviation.max(deviation);
+    }
+
+    /// Mark the current column as containing a repair.
+    pub fn mark_repair(&mut self) {
+        self.current.repaired = true;
+    }
+
+    /// Advance the recorder's clock after `num_samples` samples have been recorded.
+    pub fn advance(&mut self, num_samples: usize) {
+        self.samples_in_column += num_samples;
+        if self.samples_in_column < self.column_length {
+            return;
+        }
+
+        if let Some(mut history) = self.history.try_lock() {
+            history.push(self.current);
+            self.current = Column::default();
+            self.samples_in_column -= self.column_length;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_scroll_from_oldest_to_newest() {
+        let history = Arc::new(Mutex::new(WaveformHistory::default()));
+        let mut recorder = WaveformRecorder::new(history.clone());
+        recorder.set_sample_rate(NUM_COLUMNS as f32 / WAVEFORM_SECONDS * 10.0);
+
+        recorder.record(&[0.5, -0.25]);
+        recorder.record_deviation(3.0);
+        recorder.record_deviation(2.0);
+        recorder.mark_repair();
+        recorder.advance(10);
+        recorder.record(&[0.1]);
+        recorder.advance(10);
+
+        let history = history.lock();
+        let columns: Vec<_> = history.columns().collect();
+        assert_eq!(columns.len(), NUM_COLUMNS);
+        assert!(columns[0].is_empty());
+        assert_eq!(
+            *columns[NUM_COLUMNS - 2],
+            Column {
+                min: -0.25,
+                max: 0.5,
+                repaired: true,
+                deviation: 3.0,
+            }
+        );
+        assert!(!columns[NUM_COLUMNS - 1].repaired);
+    }
+}